@@ -0,0 +1,313 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use clap::ValueEnum;
+use colored::Colorize;
+use comfy_table::{Cell, Table};
+use humansize::{DECIMAL, format_size};
+
+use crate::progress;
+use crate::tree;
+use crate::utils;
+
+/// A duration of `n` days
+fn days(n: u64) -> Duration {
+    Duration::from_secs(n.saturating_mul(24 * 60 * 60))
+}
+
+/// Upper bounds of the age buckets, in days, with their labels
+const BUCKETS: [(u64, &str); 4] = [
+    (1, "< 1 day"),
+    (7, "< 7 days"),
+    (30, "< 30 days"),
+    (365, "< 1 year"),
+];
+
+/// Which timestamp of a file is used for age analysis
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TimeField {
+    /// Last modification time (mtime)
+    #[default]
+    Modified,
+    /// Last access time (atime)
+    Accessed,
+    /// Last status change time (ctime)
+    Changed,
+}
+
+/// The timestamps of a file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FileTimes {
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub changed: Option<SystemTime>,
+}
+
+impl FileTimes {
+    /// Read the timestamps from a file's metadata
+    pub fn from_metadata(metadata: &fs::Metadata) -> Self {
+        FileTimes {
+            modified: metadata.modified().ok(),
+            accessed: metadata.accessed().ok(),
+            changed: changed(metadata),
+        }
+    }
+
+    /// The newest of each timestamp in `self` and `other`
+    pub fn newest(self, other: FileTimes) -> Self {
+        FileTimes {
+            modified: self.modified.max(other.modified),
+            accessed: self.accessed.max(other.accessed),
+            changed: self.changed.max(other.changed),
+        }
+    }
+
+    /// The timestamp selected by `field`
+    pub fn get(&self, field: TimeField) -> Option<SystemTime> {
+        match field {
+            TimeField::Modified => self.modified,
+            TimeField::Accessed => self.accessed,
+            TimeField::Changed => self.changed,
+        }
+    }
+
+    /// Whether the timestamp selected by `field` is known and older than `cutoff`
+    pub fn older_than(&self, field: TimeField, cutoff: SystemTime) -> bool {
+        matches!(self.get(field), Some(time) if time < cutoff)
+    }
+}
+
+#[cfg(unix)]
+fn changed(metadata: &fs::Metadata) -> Option<SystemTime> {
+    use std::os::unix::fs::MetadataExt;
+    let secs = u64::try_from(metadata.ctime()).ok()?;
+    let nanos = u32::try_from(metadata.ctime_nsec()).unwrap_or(0);
+    SystemTime::UNIX_EPOCH.checked_add(Duration::new(secs, nanos))
+}
+
+#[cfg(not(unix))]
+fn changed(_metadata: &fs::Metadata) -> Option<SystemTime> {
+    None
+}
+
+/// Calculate the size of a directory and the newest timestamps of any file below it
+///
+/// # Arguments
+///
+/// * `dir_path`: Path to the directory
+///
+/// returns: (u64, FileTimes) - The size in bytes and the newest timestamps
+pub fn dir_size_and_times(dir_path: &Path) -> (u64, FileTimes) {
+    utils::fold_files(
+        dir_path,
+        &|| (0, FileTimes::default()),
+        &|(size, times), _, metadata| {
+            (
+                size + metadata.len(),
+                times.newest(FileTimes::from_metadata(metadata)),
+            )
+        },
+        &|a, b| (a.0 + b.0, a.1.newest(b.1)),
+    )
+}
+
+/// Bytes and file count of files in one age range
+#[derive(Clone, Debug, PartialEq)]
+pub struct AgeBucket {
+    pub label: &'static str,
+    pub size: u64,
+    pub files: u64,
+}
+
+/// Index of the bucket a timestamp falls into, the last two being "older" and "unknown"
+fn bucket_index(time: Option<SystemTime>, now: SystemTime) -> usize {
+    let Some(time) = time else {
+        return BUCKETS.len() + 1;
+    };
+    // Timestamps in the future count as brand new
+    let age = now.duration_since(time).unwrap_or_default();
+    BUCKETS
+        .iter()
+        .position(|(limit, _)| age < days(*limit))
+        .unwrap_or(BUCKETS.len())
+}
+
+/// Bucket bytes and file counts by age for everything under the given paths
+///
+/// # Arguments
+///
+/// * `paths` - The files or directories to scan.
+/// * `field` - Which timestamp to measure age by.
+/// * `now` - The time ages are measured from.
+///
+/// # Returns
+///
+/// * Every bucket from newest to oldest, followed by files with no timestamp.
+pub fn age_breakdown(paths: &[&Path], field: TimeField, now: SystemTime) -> Vec<AgeBucket> {
+    type Totals = [(u64, u64); BUCKETS.len() + 2];
+    let add = |mut totals: Totals, metadata: &fs::Metadata| {
+        let index = bucket_index(FileTimes::from_metadata(metadata).get(field), now);
+        totals[index].0 += metadata.len();
+        totals[index].1 += 1;
+        totals
+    };
+    let merge = |mut a: Totals, b: Totals| {
+        for (a, b) in a.iter_mut().zip(b) {
+            a.0 += b.0;
+            a.1 += b.1;
+        }
+        a
+    };
+
+    let mut totals: Totals = Default::default();
+    for path in paths {
+        if path.is_dir() {
+            let found = utils::fold_files(
+                path,
+                &Totals::default,
+                &|totals, _, metadata| add(totals, metadata),
+                &merge,
+            );
+            totals = merge(totals, found);
+        } else if let Ok(metadata) = path.metadata() {
+            totals = add(totals, &metadata);
+        }
+        progress::root_completed(path);
+    }
+
+    let labels = BUCKETS
+        .iter()
+        .map(|(_, label)| *label)
+        .chain(["older", "unknown"]);
+    labels
+        .zip(totals)
+        .map(|(label, (size, files))| AgeBucket { label, size, files })
+        .collect()
+}
+
+/// A directory with no recent activity anywhere below it
+#[derive(Clone, Debug, PartialEq)]
+pub struct StaleDir {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Newest timestamp of any file below the directory
+    pub newest: Option<SystemTime>,
+}
+
+/// Find the largest directories in which no file has been touched for `stale_days` days
+///
+/// Only the top-most stale directories are reported: once a directory is
+/// stale, its subdirectories are not listed again. Empty directories are
+/// ignored.
+///
+/// # Arguments
+///
+/// * `paths` - The directories to scan.
+/// * `stale_days` - How many days without activity make a directory stale.
+/// * `field` - Which timestamp to measure activity by.
+/// * `now` - The time ages are measured from.
+///
+/// # Returns
+///
+/// * The stale directories, largest first.
+pub fn stale_dirs(
+    paths: &[&Path],
+    stale_days: u64,
+    field: TimeField,
+    now: SystemTime,
+) -> Vec<StaleDir> {
+    let cutoff = now
+        .checked_sub(days(stale_days))
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    // A directory whose timestamp is unknown is never reported as stale
+    let is_stale = |size: u64, times: &FileTimes| size > 0 && times.older_than(field, cutoff);
+
+    let mut stale = Vec::new();
+    for path in paths.iter().filter(|p| p.is_dir()) {
+        let (entries, size, times) = tree::scan(path, None);
+        progress::root_completed(path);
+        if is_stale(size, &times) {
+            stale.push(StaleDir {
+                path: path.to_path_buf(),
+                size,
+                newest: times.get(field),
+            });
+            continue;
+        }
+
+        // Entries are listed depth first with parents before their children, so the
+        // subtree of a stale directory follows it directly and only the last one needs checking
+        let mut inside: Option<&Path> = None;
+        for entry in entries.iter().filter(|e| e.is_dir) {
+            if inside.is_some_and(|parent| Path::new(&entry.path).starts_with(parent)) {
+                continue;
+            }
+            inside = None;
+            if !is_stale(entry.size, &entry.times) {
+                continue;
+            }
+            inside = Some(Path::new(&entry.path));
+            let entry_path = path.join(&entry.path);
+            stale.push(StaleDir {
+                path: entry_path,
+                size: entry.size,
+                newest: entry.times.get(field),
+            });
+        }
+    }
+
+    stale.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    stale
+}
+
+/// Add rows to a table from age buckets
+pub fn add_bucket_rows(table: &mut Table, buckets: &[AgeBucket]) {
+    for bucket in buckets {
+        table.add_row(vec![
+            Cell::new(bucket.label.yellow()),
+            Cell::new(format_size(bucket.size, DECIMAL).green()),
+            Cell::new(format!("{} files", bucket.files)),
+        ]);
+    }
+}
+
+/// Add rows to a table from stale directories
+pub fn add_stale_rows(table: &mut Table, dirs: &[StaleDir]) {
+    for dir in dirs {
+        table.add_row(vec![
+            Cell::new(format!("{}/", dir.path.display().to_string().blue())),
+            Cell::new(format_size(dir.size, DECIMAL).blue()),
+            Cell::new(format!("newest {}", utils::format_time(dir.newest))),
+        ]);
+    }
+}
+
+/// Convert age buckets to JSON values
+pub fn buckets_to_json(buckets: &[AgeBucket]) -> Vec<serde_json::Value> {
+    buckets
+        .iter()
+        .map(|bucket| {
+            serde_json::json!({
+                "age": bucket.label,
+                "size_bytes": bucket.size,
+                "size_human": format_size(bucket.size, DECIMAL),
+                "files": bucket.files,
+            })
+        })
+        .collect()
+}
+
+/// Convert stale directories to JSON values
+pub fn stale_to_json(dirs: &[StaleDir]) -> Vec<serde_json::Value> {
+    dirs.iter()
+        .map(|dir| {
+            serde_json::json!({
+                "path": dir.path.display().to_string(),
+                "size_bytes": dir.size,
+                "size_human": format_size(dir.size, DECIMAL),
+                "newest": utils::format_rfc3339(dir.newest),
+            })
+        })
+        .collect()
+}
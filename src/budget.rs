@@ -0,0 +1,339 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+use comfy_table::{Cell, Table};
+use glob::Pattern;
+use humansize::{DECIMAL, format_size};
+
+use crate::progress;
+use crate::utils::{self, Totals};
+
+/// A limit on the size and file count of the paths matching a pattern
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Budget {
+    /// A path or glob, relative to the scanned root unless absolute
+    pub pattern: String,
+    /// Largest allowed total size in bytes
+    pub max_size: Option<u64>,
+    /// Largest allowed number of files
+    pub max_files: Option<u64>,
+}
+
+/// Parse a budget file
+///
+/// Each line holds a path or glob followed by `size=SIZE` and/or
+/// `files=COUNT`. Patterns containing spaces can be double-quoted. Blank
+/// lines and lines starting with `#` are ignored.
+///
+/// # Arguments
+///
+/// * `text` - The contents of the budget file.
+///
+/// # Returns
+///
+/// * The budgets in file order, or an error naming the offending line.
+///
+/// # Examples
+///
+/// ```
+/// use fs_rs::budget::parse_budgets;
+/// let budgets = parse_budgets("# artefacts\ndist size=5MB files=100\n\"docs/api bundle\" size=1MiB\n").unwrap();
+/// assert_eq!(budgets.len(), 2);
+/// assert_eq!(budgets[0].max_size, Some(5_000_000));
+/// assert_eq!(budgets[0].max_files, Some(100));
+/// assert_eq!(budgets[1].pattern, "docs/api bundle");
+/// assert!(parse_budgets("dist 5MB").is_err());
+/// ```
+pub fn parse_budgets(text: &str) -> Result<Vec<Budget>, String> {
+    let mut budgets = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let budget = parse_line(line).map_err(|e| format!("line {}: {e}", index + 1))?;
+        budgets.push(budget);
+    }
+    Ok(budgets)
+}
+
+/// Parse a single non-empty budget line
+fn parse_line(line: &str) -> Result<Budget, String> {
+    let (pattern, rest) = match line.strip_prefix('"') {
+        Some(quoted) => quoted
+            .split_once('"')
+            .ok_or_else(|| "unterminated quote".to_string())?,
+        None => line.split_once(char::is_whitespace).unwrap_or((line, "")),
+    };
+
+    let mut budget = Budget {
+        pattern: pattern.to_string(),
+        max_size: None,
+        max_files: None,
+    };
+    for limit in rest.split_whitespace() {
+        match limit.split_once('=') {
+            Some(("size", size)) => budget.max_size = Some(utils::parse_size(size)?),
+            Some(("files", count)) => {
+                let count = count
+                    .parse()
+                    .map_err(|_| format!("invalid file count '{count}'"))?;
+                budget.max_files = Some(count);
+            }
+            _ => {
+                return Err(format!(
+                    "invalid limit '{limit}', expected size=SIZE or files=COUNT"
+                ));
+            }
+        }
+    }
+
+    if budget.max_size.is_none() && budget.max_files.is_none() {
+        return Err(format!("no limit for '{}'", budget.pattern));
+    }
+    Ok(budget)
+}
+
+/// Read and parse a budget file
+pub fn load_budgets(path: &Path) -> Result<Vec<Budget>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    parse_budgets(&text)
+}
+
+/// The outcome of checking one budget against one root
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BudgetResult {
+    pub budget: Budget,
+    /// The root the pattern was resolved against
+    pub root: PathBuf,
+    /// The paths matching the pattern, without those nested in another match
+    pub matched: Vec<PathBuf>,
+    /// Size and file count of everything matched
+    pub totals: Totals,
+}
+
+impl BudgetResult {
+    /// Bytes over the size limit, if exceeded
+    pub fn size_overage(&self) -> Option<u64> {
+        self.budget
+            .max_size
+            .filter(|max| self.totals.size > *max)
+            .map(|max| self.totals.size - max)
+    }
+
+    /// Files over the file count limit, if exceeded
+    pub fn files_overage(&self) -> Option<u64> {
+        self.budget
+            .max_files
+            .filter(|max| self.totals.files > *max)
+            .map(|max| self.totals.files - max)
+    }
+
+    /// Whether everything matched stays within the budget
+    pub fn passed(&self) -> bool {
+        self.size_overage().is_none() && self.files_overage().is_none()
+    }
+}
+
+/// Size and file count of a file or everything under a directory
+fn totals_of(path: &Path) -> Totals {
+    let mut totals = Totals::default();
+    if path.is_dir() {
+        totals = utils::calculate_dir_totals(path);
+    } else if let Ok(metadata) = fs::symlink_metadata(path)
+        && metadata.is_file()
+    {
+        totals.add(metadata.len());
+    }
+    totals
+}
+
+/// Check budgets against a root
+///
+/// Everything matching a budget's pattern counts towards that budget, so a
+/// glob such as `dist/*.js` limits the combined size of the scripts. Matches
+/// nested inside another match are only counted once.
+///
+/// # Arguments
+///
+/// * `budgets` - The budgets to check.
+/// * `root` - The directory relative patterns are resolved against.
+///
+/// # Returns
+///
+/// * A result per budget, in the order of `budgets`.
+pub fn check(budgets: &[Budget], root: &Path) -> Vec<BudgetResult> {
+    let results = budgets
+        .iter()
+        .map(|budget| {
+            // Escape the root so that only the budget's own pattern is treated as a glob
+            let pattern = if Path::new(&budget.pattern).is_absolute() {
+                budget.pattern.clone()
+            } else {
+                let root = Pattern::escape(&root.to_string_lossy());
+                format!("{root}/{}", budget.pattern)
+            };
+            let mut matched: Vec<PathBuf> = match glob::glob(&pattern) {
+                Ok(paths) => paths.filter_map(Result::ok).collect(),
+                // Not a valid glob, so treat it as a plain path
+                Err(_) => Some(root.join(&budget.pattern))
+                    .filter(|path| path.exists())
+                    .into_iter()
+                    .collect(),
+            };
+            matched.sort();
+            let mut outermost: Vec<PathBuf> = Vec::new();
+            for path in matched {
+                if !outermost.iter().any(|outer| path.starts_with(outer)) {
+                    outermost.push(path);
+                }
+            }
+
+            let mut totals = Totals::default();
+            for path in &outermost {
+                totals.merge(totals_of(path));
+            }
+            BudgetResult {
+                budget: budget.clone(),
+                root: root.to_path_buf(),
+                matched: outermost,
+                totals,
+            }
+        })
+        .collect();
+    progress::root_completed(root);
+    results
+}
+
+/// Format a measured value against its limit, e.g. `4.20 MB / 5 MB`
+fn against_limit(value: String, limit: Option<String>) -> String {
+    match limit {
+        Some(limit) => format!("{value} / {limit}"),
+        None => value,
+    }
+}
+
+/// Header row of a budget report
+pub fn header() -> Vec<&'static str> {
+    vec!["Status", "Budget", "Size", "Files", "Over by"]
+}
+
+/// Add a row per budget result to a table
+pub fn add_rows(table: &mut Table, results: &[BudgetResult]) {
+    for result in results {
+        let status = if !result.passed() {
+            Cell::new("FAIL".red().bold())
+        } else if result.matched.is_empty() {
+            Cell::new("NONE".yellow())
+        } else {
+            Cell::new("PASS".green())
+        };
+        let size = against_limit(
+            format_size(result.totals.size, DECIMAL),
+            result.budget.max_size.map(|max| format_size(max, DECIMAL)),
+        );
+        let files = against_limit(
+            result.totals.files.to_string(),
+            result.budget.max_files.map(|max| max.to_string()),
+        );
+        let mut over = Vec::new();
+        if let Some(bytes) = result.size_overage() {
+            over.push(format_size(bytes, DECIMAL));
+        }
+        if let Some(files) = result.files_overage() {
+            over.push(format!("{files} files"));
+        }
+
+        table.add_row(vec![
+            status,
+            Cell::new(result.budget.pattern.as_str().blue()),
+            Cell::new(size),
+            Cell::new(files),
+            Cell::new(over.join(", ").red()),
+        ]);
+    }
+}
+
+/// Convert budget results to a JSON object with `passed` and `results`
+pub fn to_json(results: &[BudgetResult]) -> serde_json::Value {
+    let values: Vec<serde_json::Value> = results
+        .iter()
+        .map(|result| {
+            serde_json::json!({
+                "root": result.root.display().to_string(),
+                "pattern": result.budget.pattern,
+                "passed": result.passed(),
+                "matched": result.matched.iter().map(|p| p.display().to_string()).collect::<Vec<_>>(),
+                "size": result.totals.size,
+                "files": result.totals.files,
+                "max_size": result.budget.max_size,
+                "max_files": result.budget.max_files,
+                "size_overage": result.size_overage(),
+                "files_overage": result.files_overage(),
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "passed": results.iter().all(BudgetResult::passed),
+        "results": values,
+    })
+}
+
+/// Escape text for an XML attribute or element
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render budget results as a JUnit XML report, one test case per budget and root
+pub fn to_junit(results: &[BudgetResult]) -> String {
+    let failures = results.iter().filter(|r| !r.passed()).count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"fs_rs budgets\" tests=\"{}\" failures=\"{failures}\">\n",
+        results.len()
+    ));
+    for result in results {
+        xml.push_str(&format!(
+            "  <testcase classname=\"{}\" name=\"{}\">\n",
+            escape_xml(&result.root.display().to_string()),
+            escape_xml(&result.budget.pattern)
+        ));
+        let mut reasons = Vec::new();
+        if let (Some(bytes), Some(max)) = (result.size_overage(), result.budget.max_size) {
+            reasons.push(format!(
+                "size {} exceeds {} by {}",
+                format_size(result.totals.size, DECIMAL),
+                format_size(max, DECIMAL),
+                format_size(bytes, DECIMAL)
+            ));
+        }
+        if let (Some(files), Some(max)) = (result.files_overage(), result.budget.max_files) {
+            reasons.push(format!(
+                "{} files exceed {max} by {files}",
+                result.totals.files
+            ));
+        }
+        if !reasons.is_empty() {
+            let message = escape_xml(&reasons.join("; "));
+            xml.push_str(&format!(
+                "    <failure message=\"{message}\">{message}</failure>\n"
+            ));
+        }
+        xml.push_str("  </testcase>\n");
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
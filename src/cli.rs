@@ -1,20 +1,58 @@
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
-use clap::{ArgAction, Parser};
-use colored::Colorize;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgGroup, ArgMatches, CommandFactory, FromArgMatches, Parser};
 use colored::control::set_override;
+use colored::{ColoredString, Colorize};
 use comfy_table::Table;
 use comfy_table::presets::{ASCII_MARKDOWN, NOTHING};
+use glob::Pattern;
 use humansize::{DECIMAL, format_size};
-use spinoff::{Color, Spinner, spinners};
-use sysinfo::Disks;
+use spinoff::{Color, Spinner, Streams, spinners};
 
-use crate::tree;
+use crate::age;
+use crate::budget;
+use crate::compare;
+use crate::daemon;
+use crate::disks;
+use crate::dupes;
+use crate::error::Error;
+use crate::events::{Destination, EventWriter};
+use crate::filetypes;
+use crate::filter::{EntryInfo, Filter};
+use crate::import;
+use crate::ncdu;
+use crate::owners;
+use crate::progress::{self, ScanContext};
+use crate::prometheus;
+use crate::query::Expression;
+use crate::report;
+use crate::serve;
+use crate::top;
+use crate::tree::{self, Snapshot};
+use crate::treemap::{self, TreemapOptions};
 use crate::utils;
 
 /// CLI arguments
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("drawing").args(["tree", "treemap"])))]
+// At most one mode can be selected
+#[command(group(ArgGroup::new("mode").multiple(false).args([
+    "disk_usage", "treemap", "html", "export_ncdu", "top", "duplicates", "duplicate_dirs", "by_type", "age",
+    "stale", "owners", "compare", "budget", "prometheus", "serve", "daemon", "query",
+])))]
+// The modes that scan the paths themselves, so they cannot work on an --import
+#[command(group(ArgGroup::new("scan").multiple(true).args([
+    "disk_usage", "top", "duplicates", "duplicate_dirs", "by_type", "age", "stale", "owners", "compare",
+    "budget", "prometheus", "serve", "daemon", "query",
+])))]
 struct Args {
     /// Optional path to the files or folders
     #[arg(default_values_t = [".".to_string()])]
@@ -24,24 +62,169 @@ struct Args {
     #[arg(long, short, action = ArgAction::SetTrue)]
     sort_by_size: bool,
 
-    /// Show disk usage
-    #[arg(long, action = ArgAction::SetTrue)]
+    /// Show the usage of every mounted filesystem instead of listing paths
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["tree", "depth", "min_size", "max_size", "filter"])]
     disk_usage: bool,
 
+    /// Only show filesystems of these types, e.g. ext4,btrfs. Pseudo filesystems such as squashfs are hidden unless listed
+    #[arg(
+        long,
+        value_name = "TYPE",
+        value_delimiter = ',',
+        requires = "disk_usage"
+    )]
+    fs_type: Vec<String>,
+
+    /// Only show filesystems mounted on a path matching GLOB, e.g. '/mnt/*'. Can be repeated
+    #[arg(long, value_name = "GLOB", value_parser = Pattern::new, requires = "disk_usage")]
+    mount: Vec<Pattern>,
+
+    /// Flag filesystems that are more than PERCENT full, exiting with status 4
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100), requires = "disk_usage")]
+    threshold: Option<u8>,
+
     /// Show as JSON output
     #[arg(long, action = ArgAction::SetTrue)]
     json: bool,
 
     /// Show tree representation
-    #[arg(long, short, action = ArgAction::SetTrue, conflicts_with_all = ["sort_by_size", "disk_usage", "json"])]
+    #[arg(long, short, action = ArgAction::SetTrue, conflicts_with_all = ["sort_by_size", "json"])]
     tree: bool,
 
-    /// Depth of the tree representation. Only applicable if --tree is set. Defaults to unlimited depth.
-    #[arg(long, short, action = ArgAction::Set, requires = "tree")]
+    /// Draw the children of each path as a squarified treemap of nested boxes sized by their share.
+    /// With --depth, nests at most that many levels of directories
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["sort_by_size", "json", "min_size", "max_size", "filter"])]
+    treemap: bool,
+
+    /// Write a self-contained HTML report with an interactive treemap and sunburst of the paths to FILE.
+    /// Use - for stdout
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["tree", "sort_by_size", "json", "min_size", "max_size", "filter"])]
+    html: Option<PathBuf>,
+
+    /// Write the scan of a single directory to FILE in ncdu's JSON dump format, for `ncdu -f FILE`.
+    /// Use - for stdout. With --import, converts the imported dump
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["tree", "depth", "sort_by_size", "json", "min_size", "max_size", "filter"])]
+    export_ncdu: Option<PathBuf>,
+
+    /// Show a scan loaded from FILE instead of scanning the paths: an ncdu JSON dump, `du -ab` output or
+    /// `find -printf '%s %y %p\n'` output, optionally gzipped. Use - for stdin.
+    /// Works with the listing, --depth, --tree, --treemap, --html, sorting, filters and --json
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["path", "scan"])]
+    import: Option<PathBuf>,
+
+    /// Size of the treemap in columns and rows, e.g. 120x40, at most 10000 each. Defaults to the terminal size
+    #[arg(long, value_name = "COLSxROWS", value_parser = treemap::parse_dimensions, requires = "treemap")]
+    treemap_size: Option<(usize, usize)>,
+
+    /// Depth of the tree representation. Without --tree, lists every directory down to this depth. Defaults to unlimited depth.
+    #[arg(long, short, action = ArgAction::Set, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
     depth: Option<usize>,
 
-    /// Use ASCII characters for tree representation instead of Unicode
-    #[arg(long, action = ArgAction::SetTrue, requires = "tree")]
+    /// Show the N largest files anywhere under the given paths, at most 100000
+    #[arg(long, action = ArgAction::Set, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=top::MAX_COUNT as u64), conflicts_with_all = ["tree", "depth", "min_size", "max_size", "filter"])]
+    top: Option<usize>,
+
+    /// Find files with identical content
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["tree", "depth", "min_size", "max_size", "filter"])]
+    duplicates: bool,
+
+    /// Find directories with identical subtrees
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["tree", "depth", "min_size", "max_size", "filter"])]
+    duplicate_dirs: bool,
+
+    /// Show a breakdown of bytes and file counts by file extension. With --tree or --depth, shows it for each directory
+    #[arg(long, action = ArgAction::SetTrue)]
+    by_type: bool,
+
+    /// Group the breakdown by file category (images, video, source, ...) instead of extension
+    #[arg(long, action = ArgAction::SetTrue, requires = "by_type")]
+    categories: bool,
+
+    /// Show a breakdown of bytes and file counts by age. With --tree, shows the newest timestamp of each directory
+    #[arg(long, action = ArgAction::SetTrue)]
+    age: bool,
+
+    /// List the largest directories in which nothing has been touched for DAYS days
+    #[arg(long, action = ArgAction::Set, value_name = "DAYS", conflicts_with_all = ["tree", "depth", "min_size", "max_size", "filter"])]
+    stale: Option<u64>,
+
+    /// Timestamp used by --age and --stale
+    #[arg(long, value_enum, value_name = "TIME", default_value_t = age::TimeField::Modified)]
+    age_by: age::TimeField,
+
+    /// Show bytes and file counts per owner and group. With --tree or --depth, shows them for each directory
+    #[arg(long, action = ArgAction::SetTrue)]
+    owners: bool,
+
+    /// Replace duplicates with links to a single copy. Asks for confirmation unless --yes is set
+    #[arg(long, value_enum, value_name = "MODE", requires = "duplicates")]
+    dedupe: Option<dupes::LinkMode>,
+
+    /// Do not ask for confirmation before replacing duplicates
+    #[arg(long, short, action = ArgAction::SetTrue, requires = "dedupe")]
+    yes: bool,
+
+    /// Hide entries smaller than SIZE, e.g. 10MB or 1.5GiB. Totals still include everything
+    #[arg(long, value_name = "SIZE", value_parser = utils::parse_size)]
+    min_size: Option<u64>,
+
+    /// Hide entries larger than SIZE, e.g. 10MB or 1.5GiB. Totals still include everything
+    #[arg(long, value_name = "SIZE", value_parser = utils::parse_size)]
+    max_size: Option<u64>,
+
+    /// Only show entries matching EXPR, e.g. 'size > 100MB and mtime < 1y and not path ~ "*/keep/*"'.
+    /// Fields: size, mtime, atime, ctime, name, path, ext, type, owner, group
+    #[arg(long, value_name = "EXPR", value_parser = Expression::parse)]
+    filter: Option<Expression>,
+
+    /// Compare the given paths side by side, matching their children by name and showing size deltas
+    /// against the first path. With --sort-by-size, the largest changes come first
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["tree", "depth", "min_size", "max_size", "filter"])]
+    compare: bool,
+
+    /// Check the paths against the size and file count limits in a budget file and fail when any is exceeded.
+    /// Each line holds a path or glob followed by size=SIZE and/or files=COUNT
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["tree", "depth", "min_size", "max_size", "filter"])]
+    budget: Option<PathBuf>,
+
+    /// Write the size and file count of every directory down to --depth (1 by default) as Prometheus
+    /// metrics to FILE, replacing it atomically. Use - for stdout
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["tree", "min_size", "max_size", "filter", "json"])]
+    prometheus: Option<PathBuf>,
+
+    /// Add a label to every Prometheus series, e.g. --label env=prod. Can be repeated
+    #[arg(long, value_name = "NAME=VALUE", value_parser = prometheus::parse_label, requires = "prometheus")]
+    label: Vec<(String, String)>,
+
+    /// Serve a JSON API and a browsable treemap of the paths on ADDR, either HOST:PORT or unix:PATH.
+    /// Defaults to 127.0.0.1:7878. Stop with Ctrl-C
+    #[arg(long, value_name = "ADDR", num_args = 0..=1, default_missing_value = "127.0.0.1:7878", conflicts_with_all = ["tree", "depth", "min_size", "max_size", "filter", "json"])]
+    serve: Option<serve::Address>,
+
+    /// Rescan the served paths every SECS seconds
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..), requires = "serve")]
+    rescan: Option<u64>,
+
+    /// Keep the sizes of the paths up to date in memory with inotify and answer queries on the Unix socket SOCKET.
+    /// Stop with Ctrl-C
+    #[arg(long, value_name = "SOCKET", conflicts_with_all = ["tree", "depth", "min_size", "max_size", "filter", "json"])]
+    daemon: Option<PathBuf>,
+
+    /// Ask the daemon listening on SOCKET for the sizes of the paths
+    #[arg(long, value_name = "SOCKET", conflicts_with_all = ["tree", "depth", "min_size", "max_size", "filter"])]
+    query: Option<PathBuf>,
+
+    /// Also write the budget results as a JUnit XML report to FILE
+    #[arg(long, value_name = "FILE", requires = "budget")]
+    junit: Option<PathBuf>,
+
+    /// Write progress events as JSON lines to stderr or to an open file descriptor, e.g. 3.
+    /// Replaces the status line
+    #[arg(long, value_name = "DEST", num_args = 0..=1, default_missing_value = "stderr", value_parser = Destination::parse)]
+    progress_json: Option<Destination>,
+
+    /// Use ASCII characters for the tree or treemap instead of Unicode
+    #[arg(long, action = ArgAction::SetTrue, requires = "drawing")]
     ascii: bool,
 
     /// Disable colored output
@@ -49,172 +232,1472 @@ struct Args {
     no_color: bool,
 }
 
+/// Outcome of a run, mapped to the process exit code
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Status {
+    /// Everything was scanned and reported
+    #[default]
+    Success,
+    /// Results were reported, but some paths or entries could not be read or an output file could not be written
+    Partial,
+    /// The arguments were invalid, e.g. none of the paths exist
+    InvalidInput,
+    /// The scan succeeded but found nothing to report
+    NothingFound,
+    /// At least one size budget or disk usage threshold was exceeded
+    BudgetExceeded,
+    /// The scan was interrupted and only partial results were reported
+    Cancelled,
+}
+
+impl Status {
+    /// The process exit code for this status
+    pub fn code(self) -> u8 {
+        match self {
+            Status::Success => 0,
+            Status::Partial => 1,
+            Status::InvalidInput => 2,
+            Status::NothingFound => 3,
+            Status::BudgetExceeded => 4,
+            Status::Cancelled => 130,
+        }
+    }
+
+    /// Keep the more severe of `self` and `other`
+    ///
+    /// Invalid input outranks cancellation, which outranks an exceeded
+    /// budget, which outranks a partial scan, which outranks finding nothing.
+    pub fn raise(&mut self, other: Status) {
+        let rank = |status: Status| match status {
+            Status::Success => 0,
+            Status::NothingFound => 1,
+            Status::Partial => 2,
+            Status::BudgetExceeded => 3,
+            Status::Cancelled => 4,
+            Status::InvalidInput => 5,
+        };
+        if rank(other) > rank(*self) {
+            *self = other;
+        }
+    }
+}
+
+/// Options that only apply together with one of the given arguments
+///
+/// They are also marked with `requires`, but clap skips that check when the
+/// required argument conflicts with another one on the command line, e.g.
+/// `--import FILE --label env=prod`, which would silently ignore the option.
+const DEPENDENT_OPTIONS: &[(&str, &[&str])] = &[
+    ("fs_type", &["disk_usage"]),
+    ("mount", &["disk_usage"]),
+    ("threshold", &["disk_usage"]),
+    ("treemap_size", &["treemap"]),
+    ("categories", &["by_type"]),
+    ("dedupe", &["duplicates"]),
+    ("yes", &["dedupe"]),
+    ("label", &["prometheus"]),
+    ("rescan", &["serve"]),
+    ("junit", &["budget"]),
+    ("ascii", &["tree", "treemap"]),
+];
+
+/// The entry filters, which only apply where entries are listed
+const FILTER_OPTIONS: &[&str] = &["min_size", "max_size", "filter"];
+
+/// Modes that only list entries, and so only take the filters, together with one of these arguments
+const FILTERED_WITH: &[(&str, &[&str])] = &[
+    ("by_type", &["tree", "depth"]),
+    ("owners", &["tree", "depth"]),
+    ("age", &["tree"]),
+];
+
+/// Reject an option given without any of the arguments it applies to
+fn check_dependent_options(matches: &ArgMatches) -> Result<(), clap::Error> {
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let flag = |id: &str| format!("--{}", id.replace('_', "-"));
+    let flags = |ids: &[&str]| {
+        ids.iter()
+            .map(|id| flag(id))
+            .collect::<Vec<_>>()
+            .join(" or ")
+    };
+    for (option, needed) in DEPENDENT_OPTIONS {
+        if given(option) && !needed.iter().any(|id| given(id)) {
+            return Err(clap::Error::raw(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                format!("{} requires {}\n", flag(option), flags(needed)),
+            ));
+        }
+    }
+    for (mode, needed) in FILTERED_WITH {
+        if !given(mode) || needed.iter().any(|id| given(id)) {
+            continue;
+        }
+        if let Some(option) = FILTER_OPTIONS.iter().find(|id| given(id)) {
+            return Err(clap::Error::raw(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!(
+                    "{} only applies to {} with {}\n",
+                    flag(option),
+                    flag(mode),
+                    flags(needed)
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
 /// Run the CLI
-pub fn run() {
-    let cli = Args::parse();
+///
+/// Results go to stdout and every diagnostic goes to stderr, so structured
+/// output such as `--json` stays machine-readable.
+///
+/// # Returns
+///
+/// * The exit code: 0 on success, 1 if some paths or entries could not be
+///   read or an output file could not be written, 2 for invalid input, 3 if nothing was found, 4 if a size budget
+///   or disk usage threshold was exceeded and 130 if the scan was cancelled
+///   with Ctrl-C.
+pub fn run() -> ExitCode {
+    let matches = Args::command().get_matches();
+    let cli = match Args::from_arg_matches(&matches).and_then(|cli| {
+        check_dependent_options(&matches)?;
+        Ok(cli)
+    }) {
+        Ok(cli) => cli,
+        Err(e) => e.format(&mut Args::command()).exit(),
+    };
 
     if cli.no_color {
         set_override(false);
     }
 
-    // Skip spinner for JSON mode — it writes control characters to stdout
-    let mut sp = if cli.json {
+    let context = Arc::new(ScanContext::new());
+    if events_on_stderr(&cli) {
+        context.set_quiet();
+    }
+
+    // Stop scanning on the first Ctrl-C and print what was found so far; quit on the second
+    let token = context.cancel_token();
+    let _ = ctrlc::set_handler(move || {
+        if token.is_cancelled() {
+            std::process::exit(i32::from(Status::Cancelled.code()));
+        }
+        token.cancel();
+    });
+
+    let events = match cli.progress_json {
+        Some(destination) => {
+            match EventWriter::start(&context, destination, &cli.path, EVENT_INTERVAL) {
+                Ok(events) => Some(events),
+                Err(e) => {
+                    eprintln!("{}", format!("Cannot write progress events: {e}").yellow());
+                    return ExitCode::from(Status::InvalidInput.code());
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Skip the status line for JSON output, progress events and daemon queries, which are meant for programs
+    let mut sp = if cli.json || events.is_some() || cli.query.is_some() {
         None
     } else {
-        Some(Spinner::new(spinners::Dots, "Computing...", Color::Yellow))
+        Some(StatusLine::start(&context))
     };
 
-    let stop_spinner = |sp: &mut Option<Spinner>| {
-        if let Some(mut spinner) = sp.take() {
-            spinner.stop_with_message("");
-        }
+    let filter = Filter {
+        min_size: cli.min_size,
+        max_size: cli.max_size,
+        expression: cli.filter.clone(),
     };
 
+    let status = context.run(|| {
+        let mut status = Status::Success;
+        run_mode(&cli, &filter, &mut sp, &mut status);
+        stop_spinner(&mut sp);
+
+        // Ctrl-C is the normal way to stop serving, so only report it for scans
+        if context.is_cancelled() && cli.serve.is_none() && cli.daemon.is_none() {
+            warn(&cli, "Cancelled, the results are partial".yellow());
+            status.raise(Status::Cancelled);
+        }
+
+        let unreadable = context.snapshot().errors;
+        if unreadable > 0 {
+            warn(
+                &cli,
+                format!("{unreadable} files or directories could not be read").yellow(),
+            );
+            status.raise(Status::Partial);
+        }
+        status
+    });
+
+    if let Some(events) = events {
+        events.finish(&context, status.code());
+    }
+    ExitCode::from(status.code())
+}
+
+/// Run the mode selected by the arguments, recording failures in `status`
+fn run_mode(cli: &Args, filter: &Filter, sp: &mut Option<StatusLine>, status: &mut Status) {
+    if cli.disk_usage {
+        run_disk_usage(cli, sp, status);
+        return;
+    }
+
+    if let Some(source) = &cli.import {
+        run_import(cli, source, filter, sp, status);
+        return;
+    }
+
+    if let Some(output) = &cli.export_ncdu {
+        run_export_ncdu(cli, output, sp, status);
+        return;
+    }
+
+    if let Some(budget_file) = &cli.budget {
+        run_budget(cli, budget_file, sp, status);
+        return;
+    }
+
+    if let Some(socket) = &cli.daemon {
+        run_daemon(cli, socket, sp, status);
+        return;
+    }
+
+    if let Some(socket) = &cli.query {
+        run_query(cli, socket, sp, status);
+        return;
+    }
+
+    if let Some(address) = &cli.serve {
+        run_serve(cli, address, sp, status);
+        return;
+    }
+
+    if let Some(output) = &cli.prometheus {
+        run_prometheus(cli, output, sp, status);
+        return;
+    }
+
     // Handle tree mode separately
     if cli.tree {
-        for input_path in cli.path.iter() {
-            let path = Path::new(&input_path);
-            if !path.exists() {
-                stop_spinner(&mut sp);
-                println!("{} {}", input_path.red().bold(), "does not exist".red());
-                continue;
-            }
-            stop_spinner(&mut sp);
-            print!("{}", tree::generate_tree(path, cli.depth, cli.ascii));
+        let file_systems = disks::file_systems();
+        for path in existing_paths(cli, sp, status) {
+            let annotations = tree::Annotations {
+                by_type: cli.by_type,
+                categories: cli.categories,
+                newest: cli.age.then_some(cli.age_by),
+                owners: cli.owners,
+            };
+            let (tree, total_size) =
+                tree::generate_sized_tree(path, cli.depth, cli.ascii, &annotations, filter);
+            stop_spinner(sp);
+            println!(
+                "{} {}",
+                path.display().to_string().yellow().bold(),
+                format!("({})", format_size(total_size, DECIMAL)).green()
+            );
+            print_file_system(path, total_size, &file_systems);
+            print!("{tree}");
+            progress::root_completed(path);
         }
         return;
     }
 
-    let mut sizes: Vec<utils::Sizes> = Vec::new();
+    if let Some(output) = &cli.html {
+        run_html(cli, output, sp, status);
+        return;
+    }
 
-    for (index, input_path) in cli.path.iter().enumerate() {
-        let path = Path::new(&input_path);
+    if cli.treemap {
+        run_treemap(cli, sp, status);
+        return;
+    }
 
-        if !path.exists() {
-            if index == 0 {
-                stop_spinner(&mut sp);
+    // Largest files anywhere under the given paths
+    if let Some(count) = cli.top {
+        let paths = existing_paths(cli, sp, status);
+        let files = top::largest_files(&paths, count);
+
+        if cli.json {
+            print_json_values(cli, &top::to_json(&files));
+            return;
+        }
+
+        let mut table = Table::new();
+        table.load_preset(NOTHING);
+
+        top::add_rows(&mut table, &files);
+        stop_spinner(sp);
+        println!("{table}");
+
+        let total_size = files.iter().map(|f| f.size).sum::<u64>();
+        let sz = format_size(total_size, DECIMAL);
+        println!("\n{} {}", "Total size:".green(), sz.green().bold());
+        println!(
+            "{} {}\n",
+            "Number of files:".green(),
+            files.len().to_string().green().bold()
+        );
+        return;
+    }
+
+    // Breakdown by file type
+    if cli.by_type {
+        if let Some(depth) = cli.depth {
+            run_dir_breakdowns(cli, depth, filter, sp, status);
+            return;
+        }
+        let paths = existing_paths(cli, sp, status);
+        let types = filetypes::breakdown(&paths, cli.categories);
+
+        if cli.json {
+            print_json_values(cli, &filetypes::to_json(&types));
+            return;
+        }
+
+        let mut table = Table::new();
+        table.load_preset(NOTHING);
+
+        filetypes::add_rows(&mut table, &types);
+        stop_spinner(sp);
+        println!("{table}");
+
+        let total_size = types.iter().map(|(_, s)| s.size).sum::<u64>();
+        let total_files = types.iter().map(|(_, s)| s.files).sum::<u64>();
+        let sz = format_size(total_size, DECIMAL);
+        println!("\n{} {}", "Total size:".green(), sz.green().bold());
+        println!(
+            "{} {}\n",
+            "Number of files:".green(),
+            total_files.to_string().green().bold()
+        );
+        return;
+    }
+
+    // Breakdown by age
+    if cli.age {
+        let paths = existing_paths(cli, sp, status);
+        let buckets = age::age_breakdown(&paths, cli.age_by, SystemTime::now());
+
+        if cli.json {
+            print_json_values(cli, &age::buckets_to_json(&buckets));
+            return;
+        }
+
+        let mut table = Table::new();
+        table.load_preset(NOTHING);
+
+        age::add_bucket_rows(&mut table, &buckets);
+        stop_spinner(sp);
+        println!("{table}");
+
+        let total_size = buckets.iter().map(|b| b.size).sum::<u64>();
+        let total_files = buckets.iter().map(|b| b.files).sum::<u64>();
+        let sz = format_size(total_size, DECIMAL);
+        println!("\n{} {}", "Total size:".green(), sz.green().bold());
+        println!(
+            "{} {}\n",
+            "Number of files:".green(),
+            total_files.to_string().green().bold()
+        );
+        return;
+    }
+
+    // Largest directories without recent activity
+    if let Some(days) = cli.stale {
+        let paths = existing_paths(cli, sp, status);
+        let dirs = age::stale_dirs(&paths, days, cli.age_by, SystemTime::now());
+
+        if cli.json {
+            print_json_values(cli, &age::stale_to_json(&dirs));
+            return;
+        }
+
+        let mut table = Table::new();
+        table.load_preset(NOTHING);
+
+        age::add_stale_rows(&mut table, &dirs);
+        stop_spinner(sp);
+        println!("{table}");
+
+        let total_size = dirs.iter().map(|d| d.size).sum::<u64>();
+        let sz = format_size(total_size, DECIMAL);
+        println!("\n{} {}", "Stale size:".green(), sz.green().bold());
+        println!(
+            "{} {}\n",
+            "Number of directories:".green(),
+            dirs.len().to_string().green().bold()
+        );
+        return;
+    }
+
+    // Breakdown by owner and group
+    if cli.owners {
+        if let Some(depth) = cli.depth {
+            run_dir_breakdowns(cli, depth, filter, sp, status);
+            return;
+        }
+        let paths = existing_paths(cli, sp, status);
+        let report = owners::owner_breakdown(&paths);
+
+        if cli.json {
+            match serde_json::to_string(&owners::to_json(&report)) {
+                Ok(json_output) => println!("{json_output}"),
+                Err(e) => warn(cli, format!("Failed to serialize JSON: {e}").red()),
             }
-            println!("{} {}", input_path.red().bold(), "does not exist".red());
-            if index == cli.path.len() - 1 {
-                return;
+            return;
+        }
+
+        let mut user_table = Table::new();
+        user_table.load_preset(NOTHING).set_header(vec!["User"]);
+        owners::add_rows(&mut user_table, &report.users);
+
+        let mut group_table = Table::new();
+        group_table.load_preset(NOTHING).set_header(vec!["Group"]);
+        owners::add_rows(&mut group_table, &report.groups);
+
+        stop_spinner(sp);
+        println!("{user_table}\n");
+        println!("{group_table}");
+
+        let total_size = report.users.iter().map(|(_, _, t)| t.size).sum::<u64>();
+        let total_files = report.users.iter().map(|(_, _, t)| t.files).sum::<u64>();
+        let sz = format_size(total_size, DECIMAL);
+        println!("\n{} {}", "Total size:".green(), sz.green().bold());
+        println!(
+            "{} {}\n",
+            "Number of files:".green(),
+            total_files.to_string().green().bold()
+        );
+        return;
+    }
+
+    // Side-by-side comparison of several roots
+    if cli.compare {
+        let paths = existing_paths(cli, sp, status);
+        if paths.len() < 2 {
+            stop_spinner(sp);
+            warn(cli, "--compare needs at least two existing paths".red());
+            status.raise(Status::InvalidInput);
+            return;
+        }
+        let mut comparison = compare::compare_roots(&paths);
+        if cli.sort_by_size {
+            compare::sort_by_change(&mut comparison.entries);
+        }
+
+        if cli.json {
+            match serde_json::to_string(&compare::to_json(&comparison)) {
+                Ok(json_output) => println!("{json_output}"),
+                Err(e) => warn(cli, format!("Failed to serialize JSON: {e}").red()),
             }
-            continue;
+            return;
         }
 
-        if path.is_file() {
-            match path.metadata() {
-                Ok(metadata) => {
-                    let file_size = metadata.len();
-                    if let Some(file_name) = path.file_name() {
-                        let file_name = utils::truncate_filename(Path::new(file_name));
-                        sizes.push(utils::Sizes {
-                            name: file_name.to_string(),
-                            size: file_size,
-                            is_dir: false,
-                        });
+        let mut table = Table::new();
+        table
+            .load_preset(NOTHING)
+            .set_header(compare::header(&comparison));
+        compare::add_rows(&mut table, &comparison);
+
+        stop_spinner(sp);
+        println!("{table}");
+        return;
+    }
+
+    if cli.duplicates || cli.duplicate_dirs {
+        run_duplicates(cli, sp, status);
+        return;
+    }
+
+    // Flattened listing of every directory down to the given depth
+    if let Some(depth) = cli.depth {
+        let mut listings = Vec::new();
+        for path in existing_paths(cli, sp, status) {
+            let (sizes, total_size, count) = if path.is_dir() {
+                tree::flatten_matching_dirs(path, depth, filter)
+            } else {
+                let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+                (Vec::new(), size, 0)
+            };
+            progress::root_completed(path);
+            listings.push(RootListing {
+                root: path,
+                sizes,
+                total_size,
+                count,
+            });
+        }
+
+        print_listings(cli, sp, listings, filter, "Number of directories:");
+        return;
+    }
+
+    let mut listings = Vec::new();
+    for path in existing_paths(cli, sp, status) {
+        listings.push(list_children(cli, path, filter));
+        progress::root_completed(path);
+    }
+
+    if listings.is_empty() {
+        return;
+    }
+    if listings.iter().all(|l| l.count == 0) {
+        stop_spinner(sp);
+        warn(cli, "No files or folders found".normal());
+        status.raise(Status::NothingFound);
+        return;
+    }
+
+    print_listings(cli, sp, listings, filter, "Number of files:");
+}
+
+/// List every directory down to `depth` with its breakdown by type or by owner
+fn run_dir_breakdowns(
+    cli: &Args,
+    depth: usize,
+    filter: &Filter,
+    sp: &mut Option<StatusLine>,
+    status: &mut Status,
+) {
+    let annotations = tree::Annotations {
+        by_type: cli.by_type,
+        categories: cli.categories,
+        owners: cli.owners,
+        ..tree::Annotations::default()
+    };
+    let mut listings = Vec::new();
+    for path in existing_paths(cli, sp, status) {
+        let (mut dirs, total_size) = tree::breakdown_dirs(path, depth, &annotations, filter);
+        progress::root_completed(path);
+        if cli.sort_by_size {
+            dirs.sort_by_key(|dir| std::cmp::Reverse(dir.size));
+        } else {
+            dirs.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        listings.push((path, dirs, total_size));
+    }
+
+    if cli.json {
+        let values: Vec<serde_json::Value> = listings
+            .iter()
+            .flat_map(|(root, dirs, _)| {
+                dirs.iter().map(move |dir| {
+                    let mut value = serde_json::json!({
+                        "root": root.display().to_string(),
+                        "name": dir.name,
+                        "size_bytes": dir.size,
+                        "size_human": format_size(dir.size, DECIMAL),
+                    });
+                    if cli.owners {
+                        let owners = owners::to_json(&dir.owners);
+                        value["users"] = owners["users"].clone();
+                        value["groups"] = owners["groups"].clone();
+                    } else {
+                        value["types"] = filetypes::to_json(&dir.types).into();
                     }
+                    value
+                })
+            })
+            .collect();
+        print_json_values(cli, &values);
+        return;
+    }
+
+    let details = |dir: &tree::DirBreakdown| {
+        if cli.owners {
+            format!("[{}]", owners::summary(&dir.owners))
+        } else {
+            format!("[{}]", utils::summary(&dir.types, 3))
+        }
+    };
+    stop_spinner(sp);
+    let grouped = listings.len() > 1;
+    for (root, dirs, _) in &listings {
+        let mut table = Table::new();
+        table.load_preset(NOTHING);
+        for dir in dirs {
+            table.add_row(vec![
+                format!("{}/", dir.name.blue()),
+                format_size(dir.size, DECIMAL).green().to_string(),
+                details(dir).dimmed().to_string(),
+            ]);
+        }
+        if grouped {
+            println!("{}", root.display().to_string().yellow().bold());
+        }
+        println!("{table}\n");
+    }
+
+    let total_size = listings.iter().map(|(_, _, size)| size).sum::<u64>();
+    let count = listings
+        .iter()
+        .map(|(_, dirs, _)| dirs.len())
+        .sum::<usize>();
+    let sz = format_size(total_size, DECIMAL);
+    println!("{} {}", "Total size:".green(), sz.green().bold());
+    println!(
+        "{} {}\n",
+        "Number of directories:".green(),
+        count.to_string().green().bold()
+    );
+}
+
+/// Keep the sizes of the paths up to date and answer queries until Ctrl-C is pressed
+fn run_daemon(cli: &Args, socket: &Path, sp: &mut Option<StatusLine>, status: &mut Status) {
+    let roots: Vec<PathBuf> = existing_paths(cli, sp, status)
+        .into_iter()
+        .filter(|path| path.is_dir())
+        .map(|path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf()))
+        .collect();
+    if roots.is_empty() {
+        stop_spinner(sp);
+        warn(cli, "--daemon needs at least one existing directory".red());
+        status.raise(Status::InvalidInput);
+        return;
+    }
+
+    let result = daemon::run(&roots, socket, || {
+        stop_spinner(sp);
+        println!(
+            "{} {}",
+            "Listening on".green(),
+            socket.display().to_string().green().bold()
+        );
+    });
+    stop_spinner(sp);
+    if let Err(e) = result {
+        warn(cli, format!("Daemon failed: {e}").red());
+        status.raise(Status::InvalidInput);
+    }
+}
+
+/// Print the sizes of the paths as known to a running daemon
+fn run_query(cli: &Args, socket: &Path, sp: &mut Option<StatusLine>, status: &mut Status) {
+    let paths: Vec<&Path> = cli.path.iter().map(Path::new).collect();
+    let answers = daemon::query(socket, &paths);
+    stop_spinner(sp);
+    let answers = match answers {
+        Ok(answers) => answers,
+        Err(e) => {
+            warn(
+                cli,
+                format!("Cannot reach the daemon on {}: {e}", socket.display()).red(),
+            );
+            status.raise(Status::InvalidInput);
+            return;
+        }
+    };
+
+    for answer in answers.iter().filter(|answer| answer["error"].is_string()) {
+        let path = answer["path"].as_str().unwrap_or("?");
+        let error = answer["error"].as_str().unwrap_or_default();
+        warn(cli, format!("{path}: {error}").normal());
+        status.raise(Status::Partial);
+    }
+    if cli.json {
+        print_json_values(cli, &answers);
+        return;
+    }
+
+    let mut table = Table::new();
+    table.load_preset(NOTHING);
+    for answer in answers.iter().filter(|answer| !answer["error"].is_string()) {
+        let path = answer["path"].as_str().unwrap_or_default();
+        let path = if answer["is_dir"] == true {
+            format!("{}/", path.blue())
+        } else {
+            path.green().to_string()
+        };
+        table.add_row(vec![
+            path,
+            format_size(answer["size"].as_u64().unwrap_or_default(), DECIMAL),
+            format!("{} files", answer["files"]),
+        ]);
+    }
+    println!("{table}");
+}
+
+/// Treemap options from the arguments, sized to the terminal unless --treemap-size is given
+fn treemap_options(cli: &Args) -> TreemapOptions {
+    // Leave room for the path and filesystem lines and the prompt
+    let (width, height) = cli.treemap_size.unwrap_or_else(|| {
+        let (columns, rows) = treemap::terminal_size();
+        (columns, rows.saturating_sub(3).max(4))
+    });
+    TreemapOptions {
+        width,
+        height,
+        depth: cli.depth,
+        ascii: cli.ascii,
+    }
+}
+
+/// Draw a treemap of each path
+fn run_treemap(cli: &Args, sp: &mut Option<StatusLine>, status: &mut Status) {
+    let options = treemap_options(cli);
+    let file_systems = disks::file_systems();
+    for path in existing_paths(cli, sp, status) {
+        let (map, total_size) = treemap::generate_treemap(path, &options);
+        stop_spinner(sp);
+        println!(
+            "{} {}",
+            path.display().to_string().yellow().bold(),
+            format!("({})", format_size(total_size, DECIMAL)).green()
+        );
+        print_file_system(path, total_size, &file_systems);
+        print!("{map}");
+        progress::root_completed(path);
+    }
+}
+
+/// Write an HTML report of the paths
+fn run_html(cli: &Args, output: &Path, sp: &mut Option<StatusLine>, status: &mut Status) {
+    let roots: Vec<serde_json::Value> = existing_paths(cli, sp, status)
+        .into_iter()
+        .map(|path| report::scan_tree(path, cli.depth))
+        .collect();
+    stop_spinner(sp);
+    if roots.is_empty() {
+        return;
+    }
+
+    write_output(cli, output, &report::render(&roots), "Report", status);
+}
+
+/// Write the scan of a directory as an ncdu dump
+fn run_export_ncdu(cli: &Args, output: &Path, sp: &mut Option<StatusLine>, status: &mut Status) {
+    let paths = existing_paths(cli, sp, status);
+    let [path] = paths.as_slice() else {
+        stop_spinner(sp);
+        if !paths.is_empty() {
+            warn(cli, "--export-ncdu takes a single directory".red());
+            status.raise(Status::InvalidInput);
+        }
+        return;
+    };
+    if !path.is_dir() {
+        stop_spinner(sp);
+        warn(
+            cli,
+            format!("--export-ncdu needs a directory, not {}", path.display()).red(),
+        );
+        status.raise(Status::InvalidInput);
+        return;
+    }
+
+    let snapshot = Snapshot::scan(path, None);
+    progress::root_completed(path);
+    stop_spinner(sp);
+    write_output(
+        cli,
+        output,
+        &ncdu::to_json(&snapshot).to_string(),
+        "Dump",
+        status,
+    );
+}
+
+/// Load a scan from a dump made elsewhere and show it like a scan of the paths
+fn run_import(
+    cli: &Args,
+    source: &Path,
+    filter: &Filter,
+    sp: &mut Option<StatusLine>,
+    status: &mut Status,
+) {
+    let snapshot = import::load(source);
+    stop_spinner(sp);
+    let snapshot = match snapshot {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            warn(
+                cli,
+                format!("Cannot import {}: {e}", source.display()).red(),
+            );
+            status.raise(Status::InvalidInput);
+            return;
+        }
+    };
+    if snapshot.errors > 0 {
+        warn(
+            cli,
+            format!(
+                "{} directories could not be read when the dump was made",
+                snapshot.errors
+            )
+            .yellow(),
+        );
+        status.raise(Status::Partial);
+    }
+
+    if let Some(output) = &cli.export_ncdu {
+        write_output(
+            cli,
+            output,
+            &ncdu::to_json(&snapshot).to_string(),
+            "Dump",
+            status,
+        );
+        return;
+    }
+    if let Some(output) = &cli.html {
+        let roots = [report::snapshot_tree(&snapshot, cli.depth)];
+        write_output(cli, output, &report::render(&roots), "Report", status);
+        return;
+    }
+
+    let header = || {
+        println!(
+            "{} {}",
+            snapshot.root.display().to_string().yellow().bold(),
+            format!("({})", format_size(snapshot.total_size, DECIMAL)).green()
+        );
+    };
+    if cli.treemap {
+        header();
+        print!(
+            "{}",
+            treemap::snapshot_treemap(&snapshot, &treemap_options(cli))
+        );
+        return;
+    }
+    if cli.tree {
+        header();
+        print!("{}", snapshot.render_tree(cli.depth, cli.ascii, filter));
+        return;
+    }
+
+    let ((sizes, count), count_name) = match cli.depth {
+        Some(depth) => (snapshot.dirs(depth, filter), "Number of directories:"),
+        None => (snapshot.children(filter), "Number of files:"),
+    };
+    if count == 0 {
+        warn(cli, "No files or folders found".normal());
+        status.raise(Status::NothingFound);
+        return;
+    }
+    let listing = RootListing {
+        root: &snapshot.root,
+        sizes,
+        total_size: snapshot.total_size,
+        count,
+    };
+    print_listings(cli, sp, vec![listing], filter, count_name);
+}
+
+/// Write `contents` to `output`, or to stdout for `-`, and say where it went
+fn write_output(cli: &Args, output: &Path, contents: &str, name: &str, status: &mut Status) {
+    if output == Path::new("-") {
+        print!("{contents}");
+    } else if let Err(e) = std::fs::write(output, contents) {
+        warn(cli, format!("Cannot write {}: {e}", output.display()).red());
+        status.raise(Status::Partial);
+    } else {
+        println!(
+            "{} {}",
+            format!("{name} written to").green(),
+            output.display().to_string().green().bold()
+        );
+    }
+}
+
+/// Scan the paths and serve them over HTTP until Ctrl-C is pressed
+fn run_serve(
+    cli: &Args,
+    address: &serve::Address,
+    sp: &mut Option<StatusLine>,
+    status: &mut Status,
+) {
+    // Serve absolute paths, so that API paths do not depend on the working directory
+    let roots: Vec<PathBuf> = existing_paths(cli, sp, status)
+        .into_iter()
+        .map(|path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf()))
+        .collect();
+    if roots.is_empty() {
+        return;
+    }
+
+    let rescan = cli.rescan.map(Duration::from_secs);
+    let result = serve::serve(roots, rescan, address, || {
+        stop_spinner(sp);
+        println!(
+            "{} {}",
+            "Serving on".green(),
+            address.to_string().green().bold()
+        );
+    });
+    stop_spinner(sp);
+    if let Err(e) = result {
+        warn(cli, format!("Cannot listen on {address}: {e}").red());
+        status.raise(Status::InvalidInput);
+    }
+}
+
+/// Write directory sizes and file counts as Prometheus metrics
+fn run_prometheus(cli: &Args, output: &Path, sp: &mut Option<StatusLine>, status: &mut Status) {
+    let depth = cli.depth.unwrap_or(1);
+    let roots: Vec<(&Path, Vec<prometheus::DirMetric>)> = existing_paths(cli, sp, status)
+        .into_iter()
+        .map(|root| (root, prometheus::dir_metrics(root, depth)))
+        .collect();
+    stop_spinner(sp);
+    if roots.is_empty() {
+        return;
+    }
+
+    let unreadable = ScanContext::current().snapshot().errors;
+    let text = prometheus::render(&roots, &cli.label, unreadable);
+    if output == Path::new("-") {
+        print!("{text}");
+    } else if let Err(e) = prometheus::write_atomic(output, &text) {
+        warn(cli, format!("Cannot write {}: {e}", output.display()).red());
+        status.raise(Status::Partial);
+    }
+}
+
+/// Check the paths against a budget file and report which budgets were exceeded
+fn run_budget(cli: &Args, budget_file: &Path, sp: &mut Option<StatusLine>, status: &mut Status) {
+    let budgets = match budget::load_budgets(budget_file) {
+        Ok(budgets) => budgets,
+        Err(e) => {
+            stop_spinner(sp);
+            warn(cli, format!("Invalid budget file: {e}").red());
+            status.raise(Status::InvalidInput);
+            return;
+        }
+    };
+
+    let results: Vec<budget::BudgetResult> = existing_paths(cli, sp, status)
+        .into_iter()
+        .flat_map(|root| budget::check(&budgets, root))
+        .collect();
+    stop_spinner(sp);
+    if results.iter().any(|result| !result.passed()) {
+        status.raise(Status::BudgetExceeded);
+    }
+
+    if let Some(junit) = &cli.junit
+        && let Err(e) = std::fs::write(junit, budget::to_junit(&results))
+    {
+        warn(cli, format!("Cannot write {}: {e}", junit.display()).red());
+        status.raise(Status::Partial);
+    }
+
+    if cli.json {
+        match serde_json::to_string(&budget::to_json(&results)) {
+            Ok(json_output) => println!("{json_output}"),
+            Err(e) => warn(cli, format!("Failed to serialize JSON: {e}").red()),
+        }
+        return;
+    }
+
+    let mut table = Table::new();
+    table.load_preset(NOTHING).set_header(budget::header());
+    budget::add_rows(&mut table, &results);
+    println!("{table}\n");
+
+    let failed = results.iter().filter(|result| !result.passed()).count();
+    if failed > 0 {
+        println!(
+            "{}",
+            format!("{failed} of {} budgets exceeded", results.len())
+                .red()
+                .bold()
+        );
+    } else {
+        println!(
+            "{}",
+            format!("All {} budgets passed", results.len())
+                .green()
+                .bold()
+        );
+    }
+}
+
+/// Report the usage of the mounted filesystems selected by the arguments
+fn run_disk_usage(cli: &Args, sp: &mut Option<StatusLine>, status: &mut Status) {
+    let disk_filter = disks::DiskFilter {
+        fs_types: cli.fs_type.clone(),
+        mounts: cli.mount.clone(),
+    };
+    let file_systems: Vec<disks::FileSystem> = disks::file_systems()
+        .into_iter()
+        .filter(|fs| disk_filter.matches(fs))
+        .collect();
+    stop_spinner(sp);
+
+    if file_systems.is_empty() {
+        warn(cli, "No filesystems found".normal());
+        status.raise(Status::NothingFound);
+        return;
+    }
+
+    let over = file_systems
+        .iter()
+        .filter(|fs| disks::over_threshold(fs, cli.threshold))
+        .count();
+    if over > 0 {
+        status.raise(Status::BudgetExceeded);
+    }
+
+    if cli.json {
+        match serde_json::to_string(&disks::to_json(&file_systems, cli.threshold)) {
+            Ok(json_output) => println!("{json_output}"),
+            Err(e) => warn(cli, format!("Failed to serialize JSON: {e}").red()),
+        }
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(ASCII_MARKDOWN)
+        .set_header(disks::header());
+    disks::add_rows(&mut table, &file_systems, cli.threshold);
+    println!("{table}");
+
+    if let Some(threshold) = cli.threshold
+        && over > 0
+    {
+        println!(
+            "{}",
+            format!("{over} filesystems are more than {threshold}% full").red()
+        );
+    }
+}
+
+/// Entries listed under one input path
+struct RootListing<'a> {
+    root: &'a Path,
+    /// Entries that match the filter
+    sizes: Vec<utils::Sizes>,
+    /// Size of everything under the root, including hidden entries
+    total_size: u64,
+    /// Number of entries, including hidden ones
+    count: usize,
+}
+
+/// List the children of a path, or the path itself if it is a file
+fn list_children<'a>(cli: &Args, path: &'a Path, filter: &Filter) -> RootListing<'a> {
+    let mut listing = RootListing {
+        root: path,
+        sizes: Vec::new(),
+        total_size: 0,
+        count: 0,
+    };
+
+    if path.is_file() {
+        match path.metadata() {
+            Ok(metadata) => {
+                listing.total_size = metadata.len();
+                listing.count = 1;
+                if filter.matches(&EntryInfo::from_metadata(path, &metadata))
+                    && let Some(file_name) = path.file_name()
+                {
+                    listing.sizes.push(utils::Sizes {
+                        name: utils::truncate_filename(Path::new(file_name)),
+                        size: metadata.len(),
+                        is_dir: false,
+                    });
                 }
-                Err(e) => println!("Failed to get metadata for {}: {}", path.display(), e),
             }
-            continue;
+            Err(e) => {
+                if !events_on_stderr(cli) {
+                    eprintln!("Failed to get metadata for {}: {}", path.display(), e);
+                }
+                utils::note_unreadable(Error::from_io(path, e));
+            }
         }
+        return listing;
+    }
 
-        // Process directory contents
-        if let Ok(entries) = std::fs::read_dir(path) {
-            for entry in entries.flatten() {
-                let entry_path = entry.path();
-                if let Some(file_name) = entry_path.file_name().and_then(|n| n.to_str()) {
-                    let file_name = utils::truncate_filename(Path::new(file_name));
-                    match entry.file_type() {
-                        Ok(file_type) => {
-                            if file_type.is_file() {
-                                if let Ok(metadata) = entry.metadata() {
-                                    sizes.push(utils::Sizes {
-                                        name: file_name.to_string(),
-                                        size: metadata.len(),
-                                        is_dir: false,
-                                    });
-                                }
-                            } else if file_type.is_dir() {
-                                let dir_size = utils::calculate_dir_size(&entry_path);
-                                sizes.push(utils::Sizes {
-                                    name: file_name.to_string(),
-                                    size: dir_size,
-                                    is_dir: true,
-                                });
-                            }
+    // Process directory contents
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            if !events_on_stderr(cli) {
+                eprintln!("Failed to read {}: {}", path.display(), e);
+            }
+            utils::note_unreadable(Error::from_io(path, e));
+            return listing;
+        }
+    };
+    for entry in entries.flatten() {
+        if progress::is_cancelled() {
+            break;
+        }
+        let entry_path = entry.path();
+        if let Some(file_name) = entry_path.file_name().and_then(|n| n.to_str()) {
+            let file_name = utils::truncate_filename(Path::new(file_name));
+            match entry.file_type() {
+                Ok(file_type) => {
+                    let metadata = match entry.metadata() {
+                        Ok(metadata) => metadata,
+                        Err(e) => {
+                            utils::note_unreadable(Error::from_io(&entry_path, e));
+                            continue;
                         }
-                        Err(e) => println!("Error getting file type: {}", e),
+                    };
+                    let mut info = EntryInfo::from_metadata(&entry_path, &metadata);
+                    if file_type.is_dir() {
+                        // Timestamps are only needed to evaluate a filter expression
+                        (info.size, info.times) = if filter.needs_details() {
+                            age::dir_size_and_times(&entry_path)
+                        } else {
+                            (utils::calculate_dir_size(&entry_path), info.times)
+                        };
+                    } else if file_type.is_file() {
+                        progress::file_visited(info.size);
+                    } else {
+                        continue;
+                    }
+                    listing.total_size += info.size;
+                    listing.count += 1;
+                    if filter.matches(&info) {
+                        listing.sizes.push(utils::Sizes {
+                            name: file_name.to_string(),
+                            size: info.size,
+                            is_dir: info.is_dir,
+                        });
+                    }
+                }
+                Err(e) => {
+                    if !events_on_stderr(cli) {
+                        eprintln!("Error getting file type: {}", e);
                     }
+                    utils::note_unreadable(Error::from_io(&entry_path, e));
                 }
             }
         }
     }
+    listing
+}
 
-    if sizes.is_empty() {
-        stop_spinner(&mut sp);
-        eprintln!("No files or folders found");
+/// Print listings grouped per root, each with its subtotal, followed by a grand total
+///
+/// A header and subtotal are only printed for each root when there are several.
+fn print_listings(
+    cli: &Args,
+    sp: &mut Option<StatusLine>,
+    mut listings: Vec<RootListing>,
+    filter: &Filter,
+    count_name: &str,
+) {
+    for listing in &mut listings {
+        if cli.sort_by_size {
+            utils::sort_by_size(&mut listing.sizes);
+        } else {
+            utils::sort_by_name(&mut listing.sizes);
+        }
+    }
+
+    // Imported roots are not on this machine's filesystems
+    let file_systems = if cli.import.is_some() {
+        Vec::new()
+    } else {
+        disks::file_systems()
+    };
+    if cli.json {
+        print_json(cli, &listings, &file_systems);
         return;
     }
 
-    // Sort
-    if cli.sort_by_size {
-        utils::sort_by_size(&mut sizes);
+    stop_spinner(sp);
+    let grouped = listings.len() > 1;
+    for listing in &listings {
+        let mut table = Table::new();
+        table.load_preset(NOTHING).set_width(80);
+        utils::add_row(&mut table, &listing.sizes);
+
+        if grouped {
+            println!("{}", listing.root.display().to_string().yellow().bold());
+        }
+        print_file_system(listing.root, listing.total_size, &file_systems);
+        if grouped {
+            println!("{table}");
+            println!(
+                "{} {}\n",
+                "Subtotal:".green(),
+                format_size(listing.total_size, DECIMAL).green().bold()
+            );
+        } else {
+            println!("{table}\n");
+        }
+    }
+
+    let total_size = listings.iter().map(|l| l.total_size).sum::<u64>();
+    let count = listings.iter().map(|l| l.count).sum::<usize>();
+    let shown = listings.iter().map(|l| l.sizes.len()).sum::<usize>();
+    let total_label = if grouped {
+        "Grand total size:"
     } else {
-        utils::sort_by_name(&mut sizes);
+        "Total size:"
+    };
+    let sz = format_size(total_size, DECIMAL);
+    println!("{} {}", total_label.green(), sz.green().bold());
+    println!(
+        "{} {}\n",
+        count_name.green(),
+        count_label(count, shown, filter).green().bold()
+    );
+}
+
+/// Print the filesystem a root lives on and the share of it the root takes
+fn print_file_system(root: &Path, size: u64, file_systems: &[disks::FileSystem]) {
+    if let Some(fs) = disks::containing(root, file_systems) {
+        println!(
+            "{} {}",
+            "Filesystem:".green(),
+            disks::describe(fs, size).dimmed()
+        );
     }
+}
 
-    // Output as JSON
-    if cli.json {
-        let json_entries: Vec<serde_json::Value> = sizes
-            .iter()
-            .map(|s| {
+/// Print the entries of each root as a single JSON array to stdout
+///
+/// Each entry carries its `root` and the `filesystem` the root lives on.
+fn print_json(cli: &Args, listings: &[RootListing], file_systems: &[disks::FileSystem]) {
+    let json_entries: Vec<serde_json::Value> = listings
+        .iter()
+        .flat_map(|listing| {
+            let file_system = disks::containing(listing.root, file_systems)
+                .map(|fs| disks::share_to_json(fs, listing.total_size));
+            listing.sizes.iter().map(move |s| {
                 serde_json::json!({
+                    "root": listing.root.display().to_string(),
                     "name": s.name,
                     "size_bytes": s.size,
                     "size_human": format_size(s.size, DECIMAL),
                     "is_dir": s.is_dir,
+                    "filesystem": file_system,
                 })
             })
-            .collect();
+        })
+        .collect();
 
-        match serde_json::to_string(&json_entries) {
-            Ok(json_output) => println!("{json_output}"),
-            Err(e) => eprintln!("Failed to serialize JSON: {e}"),
-        }
-        return;
+    print_json_values(cli, &json_entries);
+}
+
+/// Count of entries, noting how many are shown when a filter is active
+fn count_label(count: usize, shown: usize, filter: &Filter) -> String {
+    if filter.is_empty() {
+        count.to_string()
+    } else {
+        format!("{count} ({shown} shown)")
     }
+}
 
-    let mut table = Table::new();
-    table.load_preset(NOTHING).set_width(80);
+/// Print JSON values as a JSON array to stdout
+fn print_json_values(cli: &Args, values: &[serde_json::Value]) {
+    match serde_json::to_string(values) {
+        Ok(json_output) => println!("{json_output}"),
+        Err(e) => warn(cli, format!("Failed to serialize JSON: {e}").red()),
+    }
+}
 
-    utils::add_row(&mut table, &sizes);
-    stop_spinner(&mut sp);
-    println!("{table}");
+/// How often the status line is refreshed
+const STATUS_INTERVAL: Duration = Duration::from_millis(100);
 
-    let total_size = sizes.iter().map(|s| s.size).sum::<u64>();
-    let sz = format_size(total_size, DECIMAL);
-    println!("\n{} {}", "Total size:".green(), sz.green().bold());
-    println!(
-        "{} {}\n",
-        "Number of files:".green(),
-        sizes.len().to_string().green().bold()
+/// How often a progress event is written with --progress-json
+const EVENT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Longest directory path shown on the status line, in characters
+const STATUS_DIR_LENGTH: usize = 40;
+
+/// Spinner on stderr showing the live progress of the scan
+struct StatusLine {
+    spinner: Arc<Mutex<Spinner>>,
+    /// Set when the spinner has been stopped, so it is no longer updated
+    done: Arc<AtomicBool>,
+}
+
+impl StatusLine {
+    /// Start the spinner and a thread that keeps its text up to date with the progress of `context`
+    fn start(context: &Arc<ScanContext>) -> Self {
+        let spinner = Arc::new(Mutex::new(Spinner::new_with_stream(
+            spinners::Dots,
+            "Computing...",
+            Color::Yellow,
+            Streams::Stderr,
+        )));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let (updated, stopped) = (Arc::clone(&spinner), Arc::clone(&done));
+        let progress = context.watch(STATUS_INTERVAL);
+        thread::spawn(move || {
+            for snapshot in progress {
+                let Ok(mut spinner) = updated.lock() else {
+                    break;
+                };
+                // Checked under the lock so a stopped spinner is never redrawn
+                if stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+                spinner.update_text(status_text(&snapshot));
+            }
+        });
+
+        StatusLine { spinner, done }
+    }
+
+    /// Stop the spinner and clear the status line
+    fn stop(self) {
+        if let Ok(mut spinner) = self.spinner.lock() {
+            self.done.store(true, Ordering::Relaxed);
+            spinner.stop_with_message("");
+        }
+    }
+}
+
+/// Text of the status line, e.g. `1234 files, 5.6 MB, 2 errors in …/src/deep`
+fn status_text(snapshot: &progress::Snapshot) -> String {
+    let mut text = format!(
+        "{} files, {}",
+        snapshot.files,
+        format_size(snapshot.bytes, DECIMAL)
     );
+    if snapshot.errors > 0 {
+        text.push_str(&format!(", {} errors", snapshot.errors));
+    }
+    if let Some(dir) = &snapshot.current_dir {
+        let dir = dir.display().to_string();
+        let length = dir.chars().count();
+        if length > STATUS_DIR_LENGTH {
+            let tail: String = dir.chars().skip(length - STATUS_DIR_LENGTH).collect();
+            text.push_str(&format!(" in …{tail}"));
+        } else {
+            text.push_str(&format!(" in {dir}"));
+        }
+    }
+    text
+}
 
-    if cli.disk_usage {
-        let mut disk_table = Table::new();
-        disk_table
-            .load_preset(ASCII_MARKDOWN)
-            .set_header(vec!["Name", "Total", "Available"]);
-        let disks = Disks::new_with_refreshed_list();
-        for disk in &disks {
-            let disk_name = disk.name().to_str().unwrap_or("Unknown");
-            disk_table.add_row(vec![
-                disk_name.to_string(),
-                format_size(disk.total_space(), DECIMAL),
-                format_size(disk.available_space(), DECIMAL),
-            ]);
+/// Stop the status line if it is still running
+fn stop_spinner(sp: &mut Option<StatusLine>) {
+    if let Some(status_line) = sp.take() {
+        status_line.stop();
+    }
+}
+
+/// Keep the input paths that exist, reporting the ones that do not on stderr
+///
+/// Missing paths make the run partial, or invalid if none of them exist.
+fn existing_paths<'a>(
+    cli: &'a Args,
+    sp: &mut Option<StatusLine>,
+    status: &mut Status,
+) -> Vec<&'a Path> {
+    let mut paths = Vec::new();
+    for input_path in &cli.path {
+        let path = Path::new(input_path);
+        if !path.exists() {
+            stop_spinner(sp);
+            warn(cli, format!("{input_path} does not exist").red());
+            status.raise(Status::Partial);
+            continue;
         }
-        println!("{disk_table}");
+        paths.push(path);
+    }
+    if paths.is_empty() {
+        status.raise(Status::InvalidInput);
+    }
+    paths
+}
+
+/// Whether progress events go to stderr, which must then carry nothing but JSON lines
+fn events_on_stderr(cli: &Args) -> bool {
+    cli.progress_json == Some(Destination::Stderr)
+}
+
+/// Print a diagnostic on stderr, or pass it on as a `warning` event when progress events go there
+fn warn(cli: &Args, message: ColoredString) {
+    if events_on_stderr(cli) {
+        ScanContext::current().warn(message.input);
+    } else {
+        eprintln!("{message}");
     }
 }
+
+/// Ask the user a yes/no question on stderr, defaulting to no
+fn confirm(cli: &Args, question: &str) -> bool {
+    if events_on_stderr(cli) {
+        warn(cli, format!("{question} [y/N]").normal());
+    } else {
+        eprint!("{question} [y/N] ");
+    }
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Find duplicate files or directories and optionally replace duplicate files with links
+fn run_duplicates(cli: &Args, sp: &mut Option<StatusLine>, status: &mut Status) {
+    let paths = existing_paths(cli, sp, status);
+    let groups = if cli.duplicate_dirs {
+        dupes::find_duplicate_dirs(&paths)
+    } else {
+        dupes::find_duplicates(&paths)
+    };
+    let wasted = groups.iter().map(|g| g.wasted()).sum::<u64>();
+
+    stop_spinner(sp);
+    if cli.json {
+        print_json_values(cli, &dupes::to_json(&groups));
+    } else {
+        print!("{}", dupes::render_groups(&groups));
+        println!(
+            "{} {}",
+            "Reclaimable space:".green(),
+            format_size(wasted, DECIMAL).green().bold()
+        );
+        println!(
+            "{} {}\n",
+            "Duplicate groups:".green(),
+            groups.len().to_string().green().bold()
+        );
+    }
+
+    let Some(mode) = cli.dedupe else {
+        return;
+    };
+    if groups.is_empty() {
+        return;
+    }
+
+    let count = groups.iter().map(|g| g.paths.len() - 1).sum::<usize>();
+    let link = match mode {
+        dupes::LinkMode::Hardlink => "hard links",
+        dupes::LinkMode::Reflink => "reflinks",
+    };
+    let question = format!(
+        "Replace {count} duplicate files with {link}, reclaiming {}?",
+        format_size(wasted, DECIMAL)
+    );
+    if !cli.yes && !confirm(cli, &question) {
+        warn(cli, "Aborted, no files were changed".normal());
+        return;
+    }
+
+    let (reclaimed, errors) = dupes::replace_duplicates(&groups, mode);
+    for (path, e) in &errors {
+        warn(
+            cli,
+            format!("Failed to replace {}: {e}", path.display()).red(),
+        );
+    }
+    if !errors.is_empty() {
+        status.raise(Status::Partial);
+    }
+    warn(
+        cli,
+        format!("Reclaimed: {}", format_size(reclaimed, DECIMAL)).green(),
+    );
+}
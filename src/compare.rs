@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use colored::Colorize;
+use comfy_table::{Cell, Table};
+use humansize::{DECIMAL, format_size};
+use rayon::prelude::*;
+
+use crate::error::Error;
+use crate::progress::{self, ScanContext};
+use crate::utils;
+
+/// A child name with its size under each compared root
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComparedEntry {
+    pub name: String,
+    /// Whether the entry is a directory under any of the roots
+    pub is_dir: bool,
+    /// Size under each root, `None` where the root has no such child
+    pub sizes: Vec<Option<u64>>,
+}
+
+impl ComparedEntry {
+    /// Size difference of each root against the first, missing entries counting as empty
+    pub fn deltas(&self) -> Vec<i64> {
+        deltas(
+            &self
+                .sizes
+                .iter()
+                .map(|s| s.unwrap_or(0))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// The largest change against the first root, in either direction
+    pub fn max_change(&self) -> u64 {
+        self.deltas()
+            .iter()
+            .map(|d| d.unsigned_abs())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Children of several roots matched by name
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Comparison {
+    /// The compared roots, as given
+    pub roots: Vec<String>,
+    pub entries: Vec<ComparedEntry>,
+    /// Total size of each root
+    pub totals: Vec<u64>,
+}
+
+impl Comparison {
+    /// Total size difference of each root against the first
+    pub fn total_deltas(&self) -> Vec<i64> {
+        deltas(&self.totals)
+    }
+}
+
+/// A size as a signed number of bytes, saturating at `i64::MAX`
+fn signed(size: u64) -> i64 {
+    i64::try_from(size).unwrap_or(i64::MAX)
+}
+
+/// Difference of each size against the first one, starting with the second
+fn deltas(sizes: &[u64]) -> Vec<i64> {
+    let Some(&base) = sizes.first() else {
+        return Vec::new();
+    };
+    sizes[1..]
+        .iter()
+        .map(|&size| signed(size).saturating_sub(signed(base)))
+        .collect()
+}
+
+/// The immediate children of a path with their sizes; a file is its own only child
+fn child_sizes(path: &Path) -> Vec<utils::Sizes> {
+    if path.is_file() {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+        return vec![utils::Sizes {
+            name,
+            size,
+            is_dir: false,
+        }];
+    }
+
+    let read_dir = match std::fs::read_dir(path) {
+        Ok(read_dir) => read_dir,
+        Err(e) => {
+            utils::note_unreadable(Error::from_io(path, e));
+            return Vec::new();
+        }
+    };
+    let entries: Vec<_> = read_dir.flatten().collect();
+    let context = ScanContext::current();
+    entries
+        .par_iter()
+        .filter_map(|entry| {
+            context.run(|| {
+                let file_type = entry.file_type().ok()?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                if file_type.is_file() {
+                    let size = entry.metadata().ok()?.len();
+                    Some(utils::Sizes {
+                        name,
+                        size,
+                        is_dir: false,
+                    })
+                } else if file_type.is_dir() {
+                    let size = utils::calculate_dir_size(&entry.path());
+                    Some(utils::Sizes {
+                        name,
+                        size,
+                        is_dir: true,
+                    })
+                } else {
+                    None
+                }
+            })
+        })
+        .collect()
+}
+
+/// Compare the children of several roots side by side
+///
+/// Children are matched by name, so `prod/` and `staging/` or two release
+/// directories can be compared entry by entry.
+///
+/// # Arguments
+///
+/// * `roots` - The roots to compare; the first one is the baseline for deltas.
+///
+/// # Returns
+///
+/// * The matched children, sorted by name, and the total size of each root.
+pub fn compare_roots(roots: &[&Path]) -> Comparison {
+    let mut by_name: BTreeMap<String, ComparedEntry> = BTreeMap::new();
+    let mut totals = Vec::with_capacity(roots.len());
+
+    for (index, root) in roots.iter().enumerate() {
+        let children = child_sizes(root);
+        progress::root_completed(root);
+        totals.push(children.iter().map(|c| c.size).sum());
+        for child in children {
+            let entry = by_name
+                .entry(child.name.clone())
+                .or_insert_with(|| ComparedEntry {
+                    name: child.name,
+                    is_dir: false,
+                    sizes: vec![None; roots.len()],
+                });
+            entry.is_dir |= child.is_dir;
+            entry.sizes[index] = Some(child.size);
+        }
+    }
+
+    Comparison {
+        roots: roots.iter().map(|r| r.display().to_string()).collect(),
+        entries: by_name.into_values().collect(),
+        totals,
+    }
+}
+
+/// Sort compared entries by their largest change, biggest first
+pub fn sort_by_change(entries: &mut [ComparedEntry]) {
+    entries.sort_by(|a, b| {
+        b.max_change()
+            .cmp(&a.max_change())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+/// Format a size difference with its sign, e.g. `+1.20 MB` or `-300 B`
+pub fn format_delta(delta: i64) -> String {
+    let size = format_size(delta.unsigned_abs(), DECIMAL);
+    match delta.signum() {
+        1 => format!("+{size}"),
+        -1 => format!("-{size}"),
+        _ => size,
+    }
+}
+
+/// A colored delta cell: red for growth, green for shrinkage
+fn delta_cell(delta: i64) -> Cell {
+    let text = format_delta(delta);
+    match delta.signum() {
+        1 => Cell::new(text.red()),
+        -1 => Cell::new(text.green()),
+        _ => Cell::new(text),
+    }
+}
+
+/// Header row of a comparison table: the roots followed by a delta column for each non-baseline root
+pub fn header(comparison: &Comparison) -> Vec<String> {
+    let mut header = vec!["Name".to_string()];
+    header.extend(comparison.roots.iter().cloned());
+    header.extend(comparison.roots.iter().skip(1).map(|r| format!("Δ {r}")));
+    header
+}
+
+/// Add rows to a table from a comparison, followed by a row of totals
+pub fn add_rows(table: &mut Table, comparison: &Comparison) {
+    for entry in &comparison.entries {
+        let name = if entry.is_dir {
+            Cell::new(format!(
+                "{}/",
+                utils::truncate_filename(Path::new(&entry.name)).blue()
+            ))
+        } else {
+            Cell::new(utils::truncate_filename(Path::new(&entry.name)).green())
+        };
+        let mut row = vec![name];
+        row.extend(entry.sizes.iter().map(|size| match size {
+            Some(size) => Cell::new(format_size(*size, DECIMAL)),
+            None => Cell::new("-".dimmed()),
+        }));
+        row.extend(entry.deltas().into_iter().map(delta_cell));
+        table.add_row(row);
+    }
+
+    let mut row = vec![Cell::new("Total".bold())];
+    row.extend(
+        comparison
+            .totals
+            .iter()
+            .map(|size| Cell::new(format_size(*size, DECIMAL).bold())),
+    );
+    row.extend(comparison.total_deltas().into_iter().map(delta_cell));
+    table.add_row(row);
+}
+
+/// Convert a comparison to a JSON object with `roots`, `entries` and `totals`
+pub fn to_json(comparison: &Comparison) -> serde_json::Value {
+    let entries: Vec<serde_json::Value> = comparison
+        .entries
+        .iter()
+        .map(|entry| {
+            serde_json::json!({
+                "name": entry.name,
+                "is_dir": entry.is_dir,
+                "sizes": entry.sizes,
+                "deltas": entry.deltas(),
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "roots": comparison.roots,
+        "entries": entries,
+        "totals": comparison.totals,
+        "total_deltas": comparison.total_deltas(),
+    })
+}
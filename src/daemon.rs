@@ -0,0 +1,703 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use crate::error::Error;
+use crate::progress;
+use crate::tree;
+use crate::utils::{self, Totals};
+
+/// How often the daemon checks for cancellation while idle
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A directory in the size tree, with the totals of everything under it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct DirNode {
+    /// Sizes of the files directly in this directory
+    files: HashMap<OsString, u64>,
+    dirs: HashMap<OsString, DirNode>,
+    totals: Totals,
+}
+
+impl DirNode {
+    /// Recompute the totals from the files and the totals of the subdirectories
+    fn recompute(&mut self) {
+        let mut totals = Totals::default();
+        for size in self.files.values() {
+            totals.add(*size);
+        }
+        for dir in self.dirs.values() {
+            totals.merge(dir.totals);
+        }
+        self.totals = totals;
+    }
+
+    /// Recompute the totals of every directory in this subtree, deepest first
+    fn recompute_all(&mut self) {
+        for dir in self.dirs.values_mut() {
+            dir.recompute_all();
+        }
+        self.recompute();
+    }
+
+    /// Scan a directory into a node, using the same aggregation as `tree::scan`
+    fn scan(path: &Path) -> DirNode {
+        let (entries, _, _) = tree::scan(path, None);
+        let mut root = DirNode::default();
+        for entry in entries {
+            let relative = Path::new(&entry.path);
+            let Some(name) = relative.file_name() else {
+                continue;
+            };
+            let mut parent = &mut root;
+            if let Some(dirs) = relative.parent() {
+                for dir in dirs.components() {
+                    parent = parent
+                        .dirs
+                        .entry(dir.as_os_str().to_os_string())
+                        .or_default();
+                }
+            }
+            if entry.is_dir {
+                parent.dirs.entry(name.to_os_string()).or_default();
+            } else {
+                parent.files.insert(name.to_os_string(), entry.size);
+            }
+        }
+        root.recompute_all();
+        root
+    }
+
+    /// Scan a directory into a node one level at a time, calling `watch` on every directory before reading it
+    ///
+    /// Changes made while the directory is scanned are then seen by the
+    /// watcher too. Refreshing entries that were already scanned is harmless.
+    fn scan_watching(path: &Path, watch: &mut impl FnMut(&Path)) -> DirNode {
+        watch(path);
+        let mut node = DirNode::default();
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                utils::note_unreadable(Error::from_io(path, e));
+                return node;
+            }
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                let dir = DirNode::scan_watching(&entry.path(), watch);
+                node.dirs.insert(entry.file_name(), dir);
+            } else if file_type.is_file()
+                && let Ok(metadata) = entry.metadata()
+            {
+                node.files.insert(entry.file_name(), metadata.len());
+            }
+        }
+        node.recompute();
+        node
+    }
+
+    /// Every directory in this subtree, starting with `path` itself
+    fn collect_dirs(&self, path: &Path, dirs: &mut Vec<PathBuf>) {
+        dirs.push(path.to_path_buf());
+        for (name, dir) in &self.dirs {
+            dir.collect_dirs(&path.join(name), dirs);
+        }
+    }
+}
+
+/// The result of looking up a path in the size tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lookup {
+    pub totals: Totals,
+    pub is_dir: bool,
+}
+
+/// Sizes and file counts of everything under a set of directories, kept in memory
+///
+/// The tree is built with one scan and then kept up to date one entry at a
+/// time with `refresh`, so lookups never touch the disk.
+#[derive(Clone, Debug, Default)]
+pub struct SizeTree {
+    roots: Vec<(PathBuf, DirNode)>,
+    updated_at: Option<SystemTime>,
+}
+
+/// The names leading from `root` to `path`, or `None` if `path` is not under `root`
+fn components_below(root: &Path, path: &Path) -> Option<Vec<OsString>> {
+    let relative = path.strip_prefix(root).ok()?;
+    relative
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => Some(name.to_os_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Apply `change` to the directory at `names` below `node`, then recompute the totals on the way back up
+fn update_dir(node: &mut DirNode, names: &[OsString], change: &mut impl FnMut(&mut DirNode)) {
+    match names.split_first() {
+        None => change(node),
+        Some((name, rest)) => match node.dirs.get_mut(name) {
+            Some(child) => update_dir(child, rest, change),
+            None => return,
+        },
+    }
+    node.recompute();
+}
+
+impl SizeTree {
+    /// Scan the roots into a new size tree
+    ///
+    /// # Arguments
+    ///
+    /// * `roots` - Absolute paths of the directories to keep track of.
+    ///
+    /// # Returns
+    ///
+    /// * The tree. Roots that are not directories are left out.
+    pub fn build(roots: &[PathBuf]) -> SizeTree {
+        let roots = roots
+            .iter()
+            .filter(|root| root.is_dir())
+            .map(|root| {
+                let node = DirNode::scan(root);
+                progress::root_completed(root);
+                (root.clone(), node)
+            })
+            .collect();
+        SizeTree {
+            roots,
+            updated_at: Some(SystemTime::now()),
+        }
+    }
+
+    /// Scan the roots into a new size tree like `build`, calling `watch` on every directory before reading it
+    ///
+    /// # Arguments
+    ///
+    /// * `roots` - Absolute paths of the directories to keep track of.
+    /// * `watch` - Starts watching a directory for changes.
+    ///
+    /// # Returns
+    ///
+    /// * The tree. Roots that are not directories are left out.
+    pub fn build_watching(roots: &[PathBuf], mut watch: impl FnMut(&Path)) -> SizeTree {
+        let roots = roots
+            .iter()
+            .filter(|root| root.is_dir())
+            .map(|root| {
+                let node = DirNode::scan_watching(root, &mut watch);
+                progress::root_completed(root);
+                (root.clone(), node)
+            })
+            .collect();
+        SizeTree {
+            roots,
+            updated_at: Some(SystemTime::now()),
+        }
+    }
+
+    /// The directories being tracked
+    pub fn roots(&self) -> Vec<&Path> {
+        self.roots.iter().map(|(root, _)| root.as_path()).collect()
+    }
+
+    /// When the tree last changed
+    pub fn updated_at(&self) -> Option<SystemTime> {
+        self.updated_at
+    }
+
+    /// Every directory in the tree
+    pub fn dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+        for (root, node) in &self.roots {
+            node.collect_dirs(root, &mut dirs);
+        }
+        dirs
+    }
+
+    /// Look up the totals of a file or directory
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - An absolute path, without `.` or `..` components.
+    ///
+    /// # Returns
+    ///
+    /// * The totals, or `None` if the path is not in the tree.
+    pub fn lookup(&self, path: &Path) -> Option<Lookup> {
+        self.roots.iter().find_map(|(root, node)| {
+            let names = components_below(root, path)?;
+            let (last, parents) = match names.split_last() {
+                Some(split) => split,
+                None => {
+                    return Some(Lookup {
+                        totals: node.totals,
+                        is_dir: true,
+                    });
+                }
+            };
+            let mut parent = node;
+            for name in parents {
+                parent = parent.dirs.get(name)?;
+            }
+            if let Some(dir) = parent.dirs.get(last) {
+                return Some(Lookup {
+                    totals: dir.totals,
+                    is_dir: true,
+                });
+            }
+            parent.files.get(last).map(|size| {
+                let mut totals = Totals::default();
+                totals.add(*size);
+                Lookup {
+                    totals,
+                    is_dir: false,
+                }
+            })
+        })
+    }
+
+    /// Bring a single entry up to date after it was created, changed or removed
+    ///
+    /// New directories are scanned in full. The totals of every directory
+    /// above the entry are recomputed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The absolute path of the entry that changed.
+    ///
+    /// # Returns
+    ///
+    /// * The directories that were added, which should be watched from now on.
+    pub fn refresh(&mut self, path: &Path) -> Vec<PathBuf> {
+        let mut added = Vec::new();
+        self.refresh_watching(path, |dir| added.push(dir.to_path_buf()));
+        added
+    }
+
+    /// Bring a single entry up to date like `refresh`, calling `watch` on every new directory before reading it
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The absolute path of the entry that changed.
+    /// * `watch` - Starts watching a directory for changes.
+    pub fn refresh_watching(&mut self, path: &Path, mut watch: impl FnMut(&Path)) {
+        let scanned = self
+            .is_new_dir(path)
+            .then(|| DirNode::scan_watching(path, &mut watch));
+        self.apply_refresh(path, scanned);
+    }
+
+    /// Whether `path` is a directory that belongs in the tree but has not been scanned yet
+    fn is_new_dir(&self, path: &Path) -> bool {
+        let in_tree = |path: &Path| self.lookup(path).is_some_and(|lookup| lookup.is_dir);
+        fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir())
+            && !in_tree(path)
+            && path.parent().is_some_and(in_tree)
+    }
+
+    /// Bring the entry at `path` up to date, adding `scanned` if it is a new directory
+    ///
+    /// A new directory that was not scanned beforehand is left out; creating
+    /// it raises another change, which scans it.
+    fn apply_refresh(&mut self, path: &Path, mut scanned: Option<DirNode>) {
+        let Some(name) = path.file_name().map(OsStr::to_os_string) else {
+            return;
+        };
+        let metadata = fs::symlink_metadata(path).ok();
+
+        let mut changed = false;
+        for (root, node) in &mut self.roots {
+            let Some(names) = components_below(root, path) else {
+                continue;
+            };
+            let Some((_, parents)) = names.split_last() else {
+                continue;
+            };
+            update_dir(node, parents, &mut |dir: &mut DirNode| match &metadata {
+                Some(metadata) if metadata.is_file() => {
+                    dir.dirs.remove(&name);
+                    dir.files.insert(name.clone(), metadata.len());
+                }
+                Some(metadata) if metadata.is_dir() => {
+                    dir.files.remove(&name);
+                    if !dir.dirs.contains_key(&name)
+                        && let Some(scanned) = scanned.take()
+                    {
+                        dir.dirs.insert(name.clone(), scanned);
+                    }
+                }
+                // Removed, or replaced by a symlink or special file that scans skip
+                _ => {
+                    dir.files.remove(&name);
+                    dir.dirs.remove(&name);
+                }
+            });
+            changed = true;
+        }
+        if changed {
+            self.updated_at = Some(SystemTime::now());
+        }
+    }
+
+    /// Answer one line of the query protocol
+    ///
+    /// The requests are `size PATH`, `status` and `ping`. Every answer is a
+    /// single line of JSON, with an `error` field if the request failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fs_rs::daemon::SizeTree;
+    /// let tree = SizeTree::default();
+    /// assert_eq!(tree.answer("ping")["ok"], true);
+    /// assert!(tree.answer("size /not/watched")["error"].is_string());
+    /// ```
+    pub fn answer(&self, request: &str) -> serde_json::Value {
+        let request = request.trim_end_matches(['\r', '\n']);
+        let (command, argument) = request.split_once(' ').unwrap_or((request, ""));
+        match command {
+            "ping" => serde_json::json!({ "ok": true }),
+            "status" => serde_json::json!({
+                "roots": self.roots().iter().map(|r| r.display().to_string()).collect::<Vec<_>>(),
+                "dirs": self.dirs().len(),
+                "updated_at": utils::format_rfc3339(self.updated_at),
+            }),
+            "size" if argument.is_empty() => serde_json::json!({ "error": "missing path" }),
+            "size" => match self.lookup(Path::new(argument)) {
+                Some(lookup) => serde_json::json!({
+                    "path": argument,
+                    "size": lookup.totals.size,
+                    "files": lookup.totals.files,
+                    "is_dir": lookup.is_dir,
+                }),
+                None => serde_json::json!({
+                    "path": argument,
+                    "error": "path is not watched",
+                }),
+            },
+            _ => serde_json::json!({ "error": format!("unknown request '{command}'") }),
+        }
+    }
+}
+
+/// Minimal inotify bindings
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::collections::HashMap;
+    use std::ffi::{CString, OsString};
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
+
+    /// The changes that can affect sizes and file counts
+    const MASK: u32 = libc::IN_CREATE
+        | libc::IN_DELETE
+        | libc::IN_MODIFY
+        | libc::IN_CLOSE_WRITE
+        | libc::IN_MOVED_FROM
+        | libc::IN_MOVED_TO
+        | libc::IN_ONLYDIR
+        | libc::IN_DONT_FOLLOW;
+
+    /// A change reported by the kernel
+    pub(super) enum Change {
+        /// An entry of a watched directory changed
+        Entry(PathBuf),
+        /// Events were dropped, so everything has to be scanned again
+        Overflow,
+    }
+
+    /// An inotify instance and the directories it watches
+    pub(super) struct Inotify {
+        fd: OwnedFd,
+        watches: HashMap<i32, PathBuf>,
+    }
+
+    impl Inotify {
+        pub(super) fn new() -> io::Result<Inotify> {
+            // SAFETY: inotify_init1 takes no pointers
+            let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+            if fd == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Inotify {
+                // SAFETY: the descriptor was just created and is owned by nothing else
+                fd: unsafe { OwnedFd::from_raw_fd(fd) },
+                watches: HashMap::new(),
+            })
+        }
+
+        /// Watch a directory, replacing the path of an existing watch after a move
+        pub(super) fn watch(&mut self, dir: &Path) -> io::Result<()> {
+            let path = CString::new(dir.as_os_str().as_bytes())?;
+            // SAFETY: `path` is NUL-terminated and outlives the call
+            let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), MASK) };
+            if wd == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            self.watches.insert(wd, dir.to_path_buf());
+            Ok(())
+        }
+
+        /// Stop watching a directory and everything under it
+        pub(super) fn unwatch(&mut self, dir: &Path) {
+            let fd = self.fd.as_raw_fd();
+            self.watches.retain(|wd, path| {
+                let keep = !path.starts_with(dir);
+                if !keep {
+                    // SAFETY: removing a stale watch descriptor fails harmlessly
+                    unsafe { libc::inotify_rm_watch(fd, *wd) };
+                }
+                keep
+            });
+        }
+
+        /// Wait up to `timeout` for changes and return them
+        pub(super) fn read(&mut self, timeout: std::time::Duration) -> io::Result<Vec<Change>> {
+            let mut poll = libc::pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let timeout = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+            // SAFETY: `poll` is a single valid pollfd
+            if unsafe { libc::poll(&mut poll, 1, timeout) } <= 0 {
+                return Ok(Vec::new());
+            }
+
+            let mut buffer = vec![0u8; 64 * 1024];
+            // SAFETY: the buffer is valid for writes of its whole length
+            let read = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                )
+            };
+            if read < 0 {
+                let error = io::Error::last_os_error();
+                return match error.kind() {
+                    io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted => Ok(Vec::new()),
+                    _ => Err(error),
+                };
+            }
+
+            let mut changes = Vec::new();
+            let header = std::mem::size_of::<libc::inotify_event>();
+            let mut offset = 0;
+            while offset + header <= read as usize {
+                // SAFETY: the kernel writes whole events, and read_unaligned copes with the byte buffer
+                let event: libc::inotify_event =
+                    unsafe { std::ptr::read_unaligned(buffer[offset..].as_ptr().cast()) };
+                let name = &buffer[offset + header..offset + header + event.len as usize];
+                offset += header + event.len as usize;
+
+                if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                    changes.push(Change::Overflow);
+                } else if event.mask & libc::IN_IGNORED != 0 {
+                    self.watches.remove(&event.wd);
+                } else if let Some(dir) = self.watches.get(&event.wd) {
+                    let name: Vec<u8> = name.iter().copied().take_while(|b| *b != 0).collect();
+                    if !name.is_empty() {
+                        changes.push(Change::Entry(dir.join(OsString::from_vec(name))));
+                    }
+                }
+            }
+            Ok(changes)
+        }
+    }
+}
+
+/// Keep `tree` up to date with inotify until the process is cancelled
+#[cfg(target_os = "linux")]
+fn watch(tree: &RwLock<SizeTree>) -> io::Result<()> {
+    let mut inotify = inotify::Inotify::new()?;
+    let watch_dir = |inotify: &mut inotify::Inotify, dir: &Path| {
+        if let Err(e) = inotify.watch(dir) {
+            progress::warn(&format!("Cannot watch {}: {e}", dir.display()));
+        }
+    };
+    let dirs = tree.read().map(|tree| tree.dirs()).unwrap_or_default();
+    for dir in dirs {
+        watch_dir(&mut inotify, &dir);
+    }
+
+    // New directories are watched before they are scanned, so nothing created
+    // in between is missed; the events for what the scan already saw are harmless
+    while !progress::is_cancelled() {
+        for change in inotify.read(POLL_INTERVAL)? {
+            match change {
+                inotify::Change::Entry(path) => {
+                    // Scan a new directory without the lock, so queries are answered meanwhile
+                    let scanned = tree
+                        .read()
+                        .is_ok_and(|tree| tree.is_new_dir(&path))
+                        .then(|| {
+                            DirNode::scan_watching(&path, &mut |dir| watch_dir(&mut inotify, dir))
+                        });
+                    let Ok(mut tree) = tree.write() else {
+                        continue;
+                    };
+                    tree.apply_refresh(&path, scanned);
+                    if tree.lookup(&path).is_none() {
+                        inotify.unwatch(&path);
+                    }
+                }
+                inotify::Change::Overflow => {
+                    // Events were lost, so rescan everything while queries keep using the old tree
+                    let Ok(roots) = tree.read().map(|tree| {
+                        tree.roots()
+                            .into_iter()
+                            .map(Path::to_path_buf)
+                            .collect::<Vec<_>>()
+                    }) else {
+                        continue;
+                    };
+                    let rebuilt =
+                        SizeTree::build_watching(&roots, |dir| watch_dir(&mut inotify, dir));
+                    if let Ok(mut tree) = tree.write() {
+                        *tree = rebuilt;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Watching for changes needs inotify, which only Linux has
+#[cfg(not(target_os = "linux"))]
+fn watch(_tree: &RwLock<SizeTree>) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the daemon needs inotify, which is only available on Linux",
+    ))
+}
+
+/// Answer query lines on a connection until the client hangs up
+#[cfg(unix)]
+fn handle(stream: std::os::unix::net::UnixStream, tree: &RwLock<SizeTree>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let answer = match tree.read() {
+            Ok(tree) => tree.answer(&line?),
+            Err(_) => serde_json::json!({ "error": "the size tree is unavailable" }),
+        };
+        writeln!(writer, "{answer}")?;
+    }
+    Ok(())
+}
+
+/// Keep a size tree of the roots up to date and answer queries on a Unix socket until cancelled
+///
+/// # Arguments
+///
+/// * `roots` - Absolute paths of the directories to keep track of.
+/// * `socket` - Path of the Unix socket to listen on.
+/// * `on_ready` - Called once the tree is built and the socket is bound.
+///
+/// # Returns
+///
+/// * An error if the socket cannot be bound or changes cannot be watched.
+#[cfg(unix)]
+pub fn run(roots: &[PathBuf], socket: &Path, on_ready: impl FnOnce()) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    // Replace a socket left behind by a previous run, but never any other file
+    if let Ok(metadata) = fs::symlink_metadata(socket)
+        && metadata.file_type().is_socket()
+    {
+        fs::remove_file(socket)?;
+    }
+    // Bind before scanning, so a bad socket path is reported without waiting for the scan
+    let listener = UnixListener::bind(socket)?;
+    listener.set_nonblocking(true)?;
+
+    let tree = Arc::new(RwLock::new(SizeTree::build(roots)));
+
+    let context = progress::ScanContext::current();
+    let watcher = {
+        let (tree, context) = (Arc::clone(&tree), Arc::clone(&context));
+        std::thread::spawn(move || context.run(|| watch(&tree)))
+    };
+    on_ready();
+
+    while !context.is_cancelled() && !watcher.is_finished() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                let tree = Arc::clone(&tree);
+                std::thread::spawn(move || handle(stream, &tree));
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                progress::warn(&format!("Failed to accept a connection: {e}"));
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+
+    let _ = fs::remove_file(socket);
+    match watcher.join() {
+        Ok(result) => result,
+        Err(_) => Err(io::Error::other("the watcher thread panicked")),
+    }
+}
+
+/// Unix sockets are not available on this platform
+#[cfg(not(unix))]
+pub fn run(_roots: &[PathBuf], _socket: &Path, _on_ready: impl FnOnce()) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the daemon needs Unix sockets",
+    ))
+}
+
+/// Ask a running daemon for the sizes of some paths
+///
+/// # Arguments
+///
+/// * `socket` - The daemon's Unix socket.
+/// * `paths` - The paths to ask for. Relative paths are resolved first.
+///
+/// # Returns
+///
+/// * The daemon's answer for each path, or an error if it cannot be reached.
+#[cfg(unix)]
+pub fn query(socket: &Path, paths: &[&Path]) -> io::Result<Vec<serde_json::Value>> {
+    let stream = std::os::unix::net::UnixStream::connect(socket)?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    let mut answers = Vec::with_capacity(paths.len());
+    for path in paths {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        writeln!(writer, "size {}", path.display())?;
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let answer = serde_json::from_str(&line).map_err(io::Error::other)?;
+        answers.push(answer);
+    }
+    Ok(answers)
+}
+
+/// Unix sockets are not available on this platform
+#[cfg(not(unix))]
+pub fn query(_socket: &Path, _paths: &[&Path]) -> io::Result<Vec<serde_json::Value>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "querying the daemon needs Unix sockets",
+    ))
+}
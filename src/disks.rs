@@ -0,0 +1,355 @@
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+use comfy_table::{Cell, Table};
+use glob::Pattern;
+use humansize::{DECIMAL, format_size};
+use sysinfo::Disks;
+
+use crate::utils;
+
+/// Filesystem types that are read-only images rather than storage, e.g. snap packages
+const IMAGE_FILE_SYSTEMS: [&str; 3] = ["squashfs", "iso9660", "erofs"];
+
+/// A mounted filesystem and its usage
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileSystem {
+    /// Device or volume name, e.g. `/dev/nvme0n1p2`
+    pub name: String,
+    pub mount_point: PathBuf,
+    /// Filesystem type, e.g. `ext4` or `apfs`
+    pub file_system: String,
+    /// Size of the filesystem in bytes
+    pub total: u64,
+    /// Bytes in use
+    pub used: u64,
+    /// Bytes available to unprivileged users
+    pub available: u64,
+    /// Inode counts, if the platform reports them
+    pub inodes: Option<Inodes>,
+    pub read_only: bool,
+    pub removable: bool,
+}
+
+/// Inode counts of a filesystem
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Inodes {
+    pub total: u64,
+    pub free: u64,
+}
+
+impl Inodes {
+    /// Inodes in use
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+
+    /// Percentage of the inodes in use
+    pub fn used_percent(&self) -> f64 {
+        utils::percent(self.used(), self.total)
+    }
+}
+
+impl FileSystem {
+    /// Percentage of the space in use, as `df` computes it
+    ///
+    /// Space reserved for the superuser is left out, so a filesystem that is
+    /// full for regular users shows 100%.
+    pub fn used_percent(&self) -> f64 {
+        utils::percent(self.used, self.used + self.available)
+    }
+
+    /// Whether this is a read-only image or an empty pseudo filesystem, hidden unless asked for
+    pub fn is_pseudo(&self) -> bool {
+        self.total == 0 || IMAGE_FILE_SYSTEMS.contains(&self.file_system.as_str())
+    }
+}
+
+/// Block and inode counts from `statvfs`
+#[derive(Clone, Copy, Debug)]
+struct Usage {
+    used: u64,
+    inodes: Inodes,
+}
+
+/// Query `statvfs` for a mount point
+#[cfg(unix)]
+fn statvfs(mount_point: &Path) -> Option<Usage> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(mount_point.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs is a plain C struct for which all zeroes is a valid value
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid statvfs to write into
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    let (blocks, free, fragment) = (
+        stat.f_blocks as u64,
+        stat.f_bfree as u64,
+        stat.f_frsize as u64,
+    );
+    #[allow(clippy::unnecessary_cast)]
+    let inodes = Inodes {
+        total: stat.f_files as u64,
+        free: stat.f_ffree as u64,
+    };
+    Some(Usage {
+        used: blocks.saturating_sub(free).saturating_mul(fragment),
+        inodes,
+    })
+}
+
+/// Query `statvfs` for a mount point, not available on this platform
+#[cfg(not(unix))]
+fn statvfs(_mount_point: &Path) -> Option<Usage> {
+    None
+}
+
+/// List the mounted filesystems, sorted by mount point
+///
+/// # Returns
+///
+/// * Every filesystem reported by the OS, including pseudo filesystems.
+pub fn file_systems() -> Vec<FileSystem> {
+    let disks = Disks::new_with_refreshed_list();
+    let mut file_systems: Vec<FileSystem> = disks
+        .iter()
+        .map(|disk| {
+            let usage = statvfs(disk.mount_point());
+            FileSystem {
+                name: disk.name().to_string_lossy().into_owned(),
+                mount_point: disk.mount_point().to_path_buf(),
+                file_system: disk.file_system().to_string_lossy().into_owned(),
+                total: disk.total_space(),
+                used: usage.map_or_else(
+                    || disk.total_space().saturating_sub(disk.available_space()),
+                    |usage| usage.used,
+                ),
+                available: disk.available_space(),
+                inodes: usage.map(|usage| usage.inodes).filter(|i| i.total > 0),
+                read_only: disk.is_read_only(),
+                removable: disk.is_removable(),
+            }
+        })
+        .collect();
+    file_systems.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+    file_systems
+}
+
+/// The id of the device a path lives on
+#[cfg(unix)]
+fn device(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    path.metadata().ok().map(|metadata| metadata.dev())
+}
+
+/// The id of the device a path lives on, not available on this platform
+#[cfg(not(unix))]
+fn device(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Find the filesystem a path lives on
+///
+/// Mount points on the same device as the path are preferred, and the
+/// deepest one wins so that nested mounts resolve correctly. Filesystems
+/// that report their own device ids for subvolumes, such as btrfs, fall
+/// back to the deepest mount point containing the path.
+///
+/// # Arguments
+///
+/// * `path` - The path to resolve.
+/// * `file_systems` - The mounted filesystems, as returned by `file_systems`.
+///
+/// # Returns
+///
+/// * The containing filesystem, or `None` if the path does not exist or no mount point contains it.
+pub fn containing<'a>(path: &Path, file_systems: &'a [FileSystem]) -> Option<&'a FileSystem> {
+    let path = path.canonicalize().ok()?;
+    let path_device = device(&path);
+    let deepest = |candidates: &mut dyn Iterator<Item = &'a FileSystem>| {
+        candidates.max_by_key(|fs| fs.mount_point.components().count())
+    };
+    let mut mounts = file_systems
+        .iter()
+        .filter(|fs| path.starts_with(&fs.mount_point));
+
+    let same_device = deepest(
+        &mut mounts
+            .clone()
+            .filter(|fs| path_device.is_some() && device(&fs.mount_point) == path_device),
+    );
+    same_device.or_else(|| deepest(&mut mounts))
+}
+
+/// Describe the filesystem a scanned path lives on and the share of it the path takes
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+/// use fs_rs::disks::{self, FileSystem};
+/// let fs = FileSystem {
+///     name: "/dev/sda1".to_string(),
+///     mount_point: PathBuf::from("/"),
+///     file_system: "ext4".to_string(),
+///     total: 1_000_000_000,
+///     used: 600_000_000,
+///     available: 400_000_000,
+///     inodes: None,
+///     read_only: false,
+///     removable: false,
+/// };
+/// assert_eq!(
+///     disks::describe(&fs, 250_000_000),
+///     "/ (ext4 on /dev/sda1): 1 GB capacity, 400 MB free, 25.0% used by this path"
+/// );
+/// ```
+pub fn describe(fs: &FileSystem, size: u64) -> String {
+    format!(
+        "{} ({} on {}): {} capacity, {} free, {:.1}% used by this path",
+        fs.mount_point.display(),
+        fs.file_system,
+        fs.name,
+        format_size(fs.total, DECIMAL),
+        format_size(fs.available, DECIMAL),
+        utils::percent(size, fs.total)
+    )
+}
+
+/// Convert the filesystem a scanned path lives on to a JSON object
+///
+/// `share_percent` is the percentage of the filesystem's capacity taken by `size`.
+pub fn share_to_json(fs: &FileSystem, size: u64) -> serde_json::Value {
+    serde_json::json!({
+        "mount_point": fs.mount_point.display().to_string(),
+        "file_system": fs.file_system,
+        "name": fs.name,
+        "total": fs.total,
+        "available": fs.available,
+        "share_percent": utils::percent(size, fs.total),
+    })
+}
+
+/// Which filesystems to report
+#[derive(Clone, Debug, Default)]
+pub struct DiskFilter {
+    /// Only show these filesystem types. Pseudo filesystems are hidden unless listed here
+    pub fs_types: Vec<String>,
+    /// Only show mount points matching one of these globs
+    pub mounts: Vec<Pattern>,
+}
+
+impl DiskFilter {
+    /// Whether a filesystem should be reported
+    pub fn matches(&self, fs: &FileSystem) -> bool {
+        let type_matches = if self.fs_types.is_empty() {
+            !fs.is_pseudo()
+        } else {
+            self.fs_types
+                .iter()
+                .any(|t| t.eq_ignore_ascii_case(&fs.file_system))
+        };
+        let mount_matches = self.mounts.is_empty()
+            || self
+                .mounts
+                .iter()
+                .any(|pattern| pattern.matches_path(&fs.mount_point));
+        type_matches && mount_matches
+    }
+}
+
+/// Whether a filesystem is more than `threshold` percent full
+pub fn over_threshold(fs: &FileSystem, threshold: Option<u8>) -> bool {
+    threshold.is_some_and(|threshold| fs.used_percent() > f64::from(threshold))
+}
+
+/// Header row of a filesystem table
+pub fn header() -> Vec<&'static str> {
+    vec![
+        "Filesystem",
+        "Mounted on",
+        "Type",
+        "Size",
+        "Used",
+        "Avail",
+        "Use%",
+        "Inodes",
+        "IUse%",
+        "Flags",
+    ]
+}
+
+/// Add a row per filesystem to a table
+///
+/// # Arguments
+///
+/// * `table` - The table to add rows to.
+/// * `file_systems` - The filesystems to show.
+/// * `threshold` - Usage percentage above which a filesystem is flagged in red.
+pub fn add_rows(table: &mut Table, file_systems: &[FileSystem], threshold: Option<u8>) {
+    for fs in file_systems {
+        let percent = format!("{:.0}%", fs.used_percent());
+        let percent = if over_threshold(fs, threshold) {
+            Cell::new(format!("{percent} !").red().bold())
+        } else {
+            Cell::new(percent)
+        };
+        let (inodes, inodes_percent) = match fs.inodes {
+            Some(inodes) => (
+                inodes.total.to_string(),
+                format!("{:.0}%", inodes.used_percent()),
+            ),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        let flags: Vec<&str> = [(fs.read_only, "ro"), (fs.removable, "removable")]
+            .into_iter()
+            .filter_map(|(set, flag)| set.then_some(flag))
+            .collect();
+
+        table.add_row(vec![
+            Cell::new(&fs.name),
+            Cell::new(fs.mount_point.display().to_string().blue()),
+            Cell::new(&fs.file_system),
+            Cell::new(format_size(fs.total, DECIMAL)),
+            Cell::new(format_size(fs.used, DECIMAL).yellow()),
+            Cell::new(format_size(fs.available, DECIMAL).green()),
+            percent,
+            Cell::new(inodes),
+            Cell::new(inodes_percent),
+            Cell::new(flags.join(",")),
+        ]);
+    }
+}
+
+/// Convert filesystems to a JSON array
+///
+/// With a threshold, each object carries an `over_threshold` flag.
+pub fn to_json(file_systems: &[FileSystem], threshold: Option<u8>) -> serde_json::Value {
+    file_systems
+        .iter()
+        .map(|fs| {
+            let mut value = serde_json::json!({
+                "name": fs.name,
+                "mount_point": fs.mount_point.display().to_string(),
+                "file_system": fs.file_system,
+                "total": fs.total,
+                "used": fs.used,
+                "available": fs.available,
+                "used_percent": fs.used_percent(),
+                "inodes": fs.inodes.map(|i| i.total),
+                "inodes_used": fs.inodes.map(|i| i.used()),
+                "inodes_free": fs.inodes.map(|i| i.free),
+                "read_only": fs.read_only,
+                "removable": fs.removable,
+            });
+            if threshold.is_some() {
+                value["over_threshold"] = over_threshold(fs, threshold).into();
+            }
+            value
+        })
+        .collect()
+}
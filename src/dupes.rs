@@ -0,0 +1,755 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use colored::Colorize;
+use humansize::{DECIMAL, format_size};
+use rayon::prelude::*;
+
+use crate::error::Error;
+use crate::progress::{self, ScanContext};
+use crate::utils;
+
+/// Number of bytes hashed from each end of a file in the partial hash stage
+const PARTIAL_HASH_BYTES: u64 = 4096;
+
+/// A set of files or directories with identical content
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateGroup {
+    /// Size of each file or directory in bytes
+    pub size: u64,
+    /// Paths of the identical files (one per distinct inode) or directories, sorted
+    pub paths: Vec<PathBuf>,
+    /// Whether the group is made of directories
+    pub is_dir: bool,
+}
+
+impl DuplicateGroup {
+    /// Bytes that could be reclaimed by keeping a single copy
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64).saturating_sub(1)
+    }
+}
+
+/// How duplicates are replaced when deduplicating
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LinkMode {
+    /// Replace duplicates with hard links to the kept copy
+    Hardlink,
+    /// Replace duplicates with copy-on-write clones of the kept copy (Linux only)
+    Reflink,
+}
+
+/// A file considered by the duplicate scan
+struct Candidate {
+    path: PathBuf,
+    /// `(device, inode)` used to recognise files that are already hard linked
+    inode: Option<(u64, u64)>,
+}
+
+impl Candidate {
+    fn new(path: &Path, metadata: &fs::Metadata) -> Self {
+        Candidate {
+            path: path.to_path_buf(),
+            inode: utils::inode(metadata),
+        }
+    }
+}
+
+type SizeMap = HashMap<u64, Vec<Candidate>>;
+
+fn merge_size_maps(mut a: SizeMap, b: SizeMap) -> SizeMap {
+    for (size, mut candidates) in b {
+        a.entry(size).or_default().append(&mut candidates);
+    }
+    a
+}
+
+/// Hash the first and last `PARTIAL_HASH_BYTES` of a file
+fn partial_hash(path: &Path, size: u64) -> io::Result<blake3::Hash> {
+    let mut file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0; PARTIAL_HASH_BYTES as usize];
+
+    let n = file.by_ref().take(PARTIAL_HASH_BYTES).read(&mut buffer)?;
+    hasher.update(&buffer[..n]);
+
+    if size > PARTIAL_HASH_BYTES {
+        let tail_start = size
+            .saturating_sub(PARTIAL_HASH_BYTES)
+            .max(PARTIAL_HASH_BYTES);
+        file.seek(SeekFrom::Start(tail_start))?;
+        let n = file.take(PARTIAL_HASH_BYTES).read(&mut buffer)?;
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// Hash the whole content of a file
+fn full_hash(path: &Path) -> io::Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(File::open(path)?)?;
+    Ok(hasher.finalize())
+}
+
+/// Hash files in parallel, dropping those that cannot be read
+///
+/// Every file is dropped once the scan is cancelled.
+fn hash_all<K>(paths: Vec<PathBuf>, key: K) -> Vec<(blake3::Hash, PathBuf)>
+where
+    K: Fn(&Path) -> io::Result<blake3::Hash> + Sync,
+{
+    let context = ScanContext::current();
+    paths
+        .into_par_iter()
+        .filter(|_| !context.is_cancelled())
+        .filter_map(|path| key(&path).ok().map(|hash| (hash, path)))
+        .collect()
+}
+
+/// Group paths by their hash
+fn bucket(hashed: Vec<(blake3::Hash, PathBuf)>) -> HashMap<blake3::Hash, Vec<PathBuf>> {
+    let mut buckets: HashMap<blake3::Hash, Vec<PathBuf>> = HashMap::new();
+    for (hash, path) in hashed {
+        buckets.entry(hash).or_default().push(path);
+    }
+    buckets
+}
+
+/// Identify the content of files, reading as little of them as possible
+///
+/// Files are told apart by size first, then by a hash of their first and
+/// last few KB, and only files that still look alike are hashed in full.
+/// Two files get the same key exactly when their content is identical, and
+/// each file is read at most once per stage. Files that cannot be read get
+/// no key.
+///
+/// Returns `(path, size, key)` for every file with a key.
+fn content_keys(files: Vec<(PathBuf, u64)>) -> Vec<(PathBuf, u64, blake3::Hash)> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for (path, size) in files {
+        by_size.entry(size).or_default().push(path);
+    }
+
+    let context = ScanContext::current();
+    by_size
+        .into_par_iter()
+        .flat_map_iter(|(size, paths)| {
+            context.run(|| {
+                // The stage is part of the key, so keys of different stages never match
+                let key = |stage: u8, hash: Option<blake3::Hash>| {
+                    let mut hasher = blake3::Hasher::new();
+                    hasher.update(&size.to_le_bytes());
+                    hasher.update(&[stage]);
+                    if let Some(hash) = hash {
+                        hasher.update(hash.as_bytes());
+                    }
+                    hasher.finalize()
+                };
+
+                // A file of a size no other file has needs no reading
+                if paths.len() == 1 {
+                    return paths
+                        .into_iter()
+                        .map(|path| (path, size, key(0, None)))
+                        .collect();
+                }
+
+                let mut keys = Vec::new();
+                for (partial, paths) in bucket(hash_all(paths, |path| partial_hash(path, size))) {
+                    // The partial hash already covers the whole file for small files
+                    if paths.len() == 1 || size <= 2 * PARTIAL_HASH_BYTES {
+                        keys.extend(
+                            paths
+                                .into_iter()
+                                .map(|path| (path, size, key(1, Some(partial)))),
+                        );
+                    } else {
+                        keys.extend(
+                            hash_all(paths, full_hash)
+                                .into_iter()
+                                .map(|(full, path)| (path, size, key(2, Some(full)))),
+                        );
+                    }
+                }
+                keys
+            })
+        })
+        .collect()
+}
+
+/// Find groups of files with identical content under the given paths
+///
+/// Files are grouped by size first, then by a hash of their first and last
+/// few KB, and only the remaining candidates are hashed in full. Files that
+/// are already hard linked together count as a single copy, and empty files
+/// are ignored.
+///
+/// # Arguments
+///
+/// * `paths` - The files or directories to scan.
+///
+/// # Returns
+///
+/// * The duplicate groups, largest reclaimable space first.
+pub fn find_duplicates(paths: &[&Path]) -> Vec<DuplicateGroup> {
+    let mut by_size = SizeMap::new();
+
+    for path in paths {
+        if path.is_dir() {
+            let found = utils::fold_files(
+                path,
+                &SizeMap::new,
+                &|mut map, file_path, metadata| {
+                    if metadata.len() > 0 {
+                        map.entry(metadata.len())
+                            .or_default()
+                            .push(Candidate::new(file_path, metadata));
+                    }
+                    map
+                },
+                &merge_size_maps,
+            );
+            by_size = merge_size_maps(by_size, found);
+        } else if let Ok(metadata) = path.metadata()
+            && metadata.len() > 0
+        {
+            by_size
+                .entry(metadata.len())
+                .or_default()
+                .push(Candidate::new(path, &metadata));
+        }
+        progress::root_completed(path);
+    }
+
+    let mut files = Vec::new();
+    for (size, mut candidates) in by_size {
+        // Keep a single path for files that are already hard linked together
+        candidates.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut seen = HashSet::new();
+        candidates.retain(|c| c.inode.is_none_or(|inode| seen.insert(inode)));
+        if candidates.len() > 1 {
+            files.extend(candidates.into_iter().map(|c| (c.path, size)));
+        }
+    }
+
+    let mut by_content: HashMap<blake3::Hash, (u64, Vec<PathBuf>)> = HashMap::new();
+    for (path, size, key) in content_keys(files) {
+        by_content.entry(key).or_insert((size, vec![])).1.push(path);
+    }
+    let mut groups: Vec<DuplicateGroup> = by_content
+        .into_values()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(size, mut paths)| {
+            paths.sort();
+            DuplicateGroup {
+                size,
+                paths,
+                is_dir: false,
+            }
+        })
+        .collect();
+
+    groups.sort_by(|a, b| {
+        b.wasted()
+            .cmp(&a.wasted())
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+    groups
+}
+
+/// A directory and its fingerprint: (path, hash, size_in_bytes)
+type DirPrint = (PathBuf, blake3::Hash, u64);
+
+/// A directory read into memory for finding duplicate directories
+struct DirNode {
+    path: PathBuf,
+    /// Size of every file below the directory
+    size: u64,
+    /// Whether the whole subtree could be read
+    complete: bool,
+    /// Names and sizes of the files in the directory
+    files: Vec<(std::ffi::OsString, u64)>,
+    /// Names and contents of the subdirectories
+    dirs: Vec<(std::ffi::OsString, DirNode)>,
+}
+
+/// Read a directory and everything below it, skipping symlinks and special files
+///
+/// `ancestors` holds the inodes of the directories above `path`, including
+/// `path` itself. A directory that is one of its own ancestors, e.g. through
+/// a bind mount, is reported as `Error::CycleDetected` and left unread.
+fn read_tree(path: &Path, ancestors: &[(u64, u64)]) -> DirNode {
+    let mut node = DirNode {
+        path: path.to_path_buf(),
+        size: 0,
+        complete: false,
+        files: vec![],
+        dirs: vec![],
+    };
+    if progress::is_cancelled() {
+        return node;
+    }
+    let read_dir = match fs::read_dir(path) {
+        Ok(rd) => rd,
+        Err(e) => {
+            utils::note_unreadable(Error::from_io(path, e));
+            return node;
+        }
+    };
+    progress::entered_dir(path);
+
+    let dir_entries: Vec<_> = read_dir.filter_map(Result::ok).collect();
+
+    enum Child {
+        File(std::ffi::OsString, u64),
+        Dir(std::ffi::OsString, DirNode),
+        Unreadable,
+    }
+    let context = ScanContext::current();
+    let children: Vec<Child> = dir_entries
+        .par_iter()
+        .filter_map(|entry| {
+            context.run(|| {
+                let Ok(file_type) = entry.file_type() else {
+                    return Some(Child::Unreadable);
+                };
+                if !file_type.is_dir() && !file_type.is_file() {
+                    return None;
+                }
+                let Ok(metadata) = entry.metadata() else {
+                    return Some(Child::Unreadable);
+                };
+                if file_type.is_file() {
+                    progress::file_visited(metadata.len());
+                    return Some(Child::File(entry.file_name(), metadata.len()));
+                }
+                let entry_path = entry.path();
+                let mut below = ancestors.to_vec();
+                if let Some(id) = utils::inode(&metadata) {
+                    if ancestors.contains(&id) {
+                        utils::note_unreadable(Error::CycleDetected(entry_path));
+                        return Some(Child::Unreadable);
+                    }
+                    below.push(id);
+                }
+                Some(Child::Dir(
+                    entry.file_name(),
+                    read_tree(&entry_path, &below),
+                ))
+            })
+        })
+        .collect();
+
+    node.complete = true;
+    for child in children {
+        match child {
+            Child::File(name, size) => {
+                node.size += size;
+                node.files.push((name, size));
+            }
+            Child::Dir(name, dir) => {
+                node.size += dir.size;
+                node.complete &= dir.complete;
+                node.dirs.push((name, dir));
+            }
+            Child::Unreadable => node.complete = false,
+        }
+    }
+    node.files.sort();
+    node.dirs.sort_by(|a, b| a.0.cmp(&b.0));
+    node
+}
+
+/// Compute a Merkle-style fingerprint of a directory bottom-up from its children
+///
+/// The fingerprint covers the name, kind and fingerprint of every child, so
+/// two directories have the same fingerprint only if their whole subtrees
+/// match. Files are fingerprinted by `file_print` from their path and size.
+/// The directory's own name is not part of its fingerprint.
+///
+/// Every directory from `node` down that has a fingerprint is added to
+/// `prints`. Returns `None` if part of the subtree could not be read or a
+/// file has no fingerprint.
+fn fingerprint<F>(
+    node: &DirNode,
+    file_print: &F,
+    prints: &mut Vec<DirPrint>,
+) -> Option<blake3::Hash>
+where
+    F: Fn(&Path, u64) -> Option<blake3::Hash>,
+{
+    let mut children: Vec<(&std::ffi::OsString, u8, Option<blake3::Hash>)> = node
+        .files
+        .iter()
+        .map(|(name, size)| (name, b'f', file_print(&node.path.join(name), *size)))
+        .collect();
+    for (name, dir) in &node.dirs {
+        children.push((name, b'd', fingerprint(dir, file_print, prints)));
+    }
+    children.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut hasher = node.complete.then(blake3::Hasher::new);
+    for (name, kind, hash) in children {
+        match (hasher.as_mut(), hash) {
+            (Some(hasher), Some(hash)) => {
+                let name = name.to_string_lossy();
+                hasher.update(&(name.len() as u64).to_le_bytes());
+                hasher.update(name.as_bytes());
+                hasher.update(&[kind]);
+                hasher.update(hash.as_bytes());
+            }
+            _ => hasher = None,
+        }
+    }
+
+    let hash = hasher.map(|h| h.finalize());
+    if let Some(hash) = hash {
+        prints.push((node.path.clone(), hash, node.size));
+    }
+    hash
+}
+
+/// Add every file below `node` to `files`, starting at the directories in `wanted`
+fn collect_files(
+    node: &DirNode,
+    wanted: &HashSet<&Path>,
+    inside: bool,
+    files: &mut Vec<(PathBuf, u64)>,
+) {
+    let inside = inside || wanted.contains(node.path.as_path());
+    if inside {
+        files.extend(
+            node.files
+                .iter()
+                .map(|(name, size)| (node.path.join(name), *size)),
+        );
+    }
+    for (_, dir) in &node.dirs {
+        collect_files(dir, wanted, inside, files);
+    }
+}
+
+/// Whether every path lies inside one of the `parents`
+fn all_inside(paths: &[PathBuf], parents: &[PathBuf]) -> bool {
+    paths
+        .iter()
+        .all(|path| parents.iter().any(|parent| path.starts_with(parent)))
+}
+
+/// The directories among `paths`, without repeats or directories inside another one
+///
+/// Paths are compared canonicalized, since scanning a directory twice would
+/// report it as a duplicate of itself. The first spelling of each is kept.
+fn distinct_roots<'a>(paths: &[&'a Path]) -> Vec<&'a Path> {
+    let mut roots: Vec<(PathBuf, &Path)> = paths
+        .iter()
+        .filter_map(|p| Some((fs::canonicalize(p).ok()?, *p)))
+        .filter(|(canonical, _)| canonical.is_dir())
+        .collect();
+    // Paths sort right before everything inside them, so only the last kept root can contain the next
+    roots.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut distinct: Vec<(PathBuf, &Path)> = Vec::with_capacity(roots.len());
+    for root in roots {
+        if !distinct
+            .last()
+            .is_some_and(|kept| root.0.starts_with(&kept.0))
+        {
+            distinct.push(root);
+        }
+    }
+    distinct.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Find sets of directories with identical subtrees under the given paths
+///
+/// Directories are matched by names and sizes first. The files below the
+/// candidates are then identified in the same stages as `find_duplicates`,
+/// each file being read at most once however deeply the candidates nest,
+/// and the candidates are confirmed by fingerprints derived from those file
+/// keys. Only the top-most duplicates are reported: once two directories
+/// match, their identical children are not listed again. Empty directories
+/// are ignored. Paths given more than once, or inside another given path, are
+/// only scanned once.
+///
+/// # Arguments
+///
+/// * `paths` - The directories to scan.
+///
+/// # Returns
+///
+/// * The duplicate directory groups, largest reclaimable space first.
+pub fn find_duplicate_dirs(paths: &[&Path]) -> Vec<DuplicateGroup> {
+    let mut roots = Vec::new();
+    for path in distinct_roots(paths) {
+        let ancestors: Vec<_> = fs::metadata(path)
+            .ok()
+            .and_then(|m| utils::inode(&m))
+            .into_iter()
+            .collect();
+        roots.push(read_tree(path, &ancestors));
+        progress::root_completed(path);
+    }
+
+    let mut shapes = Vec::new();
+    let by_size = |_: &Path, size: u64| Some(blake3::hash(&size.to_le_bytes()));
+    for root in &roots {
+        fingerprint(root, &by_size, &mut shapes);
+    }
+
+    let mut by_hash: HashMap<blake3::Hash, (u64, Vec<PathBuf>)> = HashMap::new();
+    for (path, hash, size) in shapes.into_iter().filter(|d| d.2 > 0) {
+        by_hash.entry(hash).or_insert((size, vec![])).1.push(path);
+    }
+
+    // Largest first, and parents before their children for equal sizes
+    let depth = |paths: &[PathBuf]| paths.iter().map(|p| p.components().count()).min();
+    let mut candidates: Vec<(u64, Vec<PathBuf>)> = by_hash
+        .into_values()
+        .filter(|(_, paths)| paths.len() > 1)
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| depth(&a.1).cmp(&depth(&b.1)))
+            .then_with(|| a.1.cmp(&b.1))
+    });
+
+    let wanted: HashSet<&Path> = candidates
+        .iter()
+        .flat_map(|(_, paths)| paths.iter().map(PathBuf::as_path))
+        .collect();
+    let mut files = Vec::new();
+    for root in &roots {
+        collect_files(root, &wanted, false, &mut files);
+    }
+    let keys: HashMap<PathBuf, blake3::Hash> = content_keys(files)
+        .into_iter()
+        .map(|(path, _, key)| (path, key))
+        .collect();
+
+    let mut contents = Vec::new();
+    let by_content = |path: &Path, _: u64| keys.get(path).copied();
+    for root in &roots {
+        fingerprint(root, &by_content, &mut contents);
+    }
+    let contents: HashMap<PathBuf, blake3::Hash> = contents
+        .into_iter()
+        .map(|(path, hash, _)| (path, hash))
+        .collect();
+
+    let mut reported: Vec<PathBuf> = Vec::new();
+    let mut groups = Vec::new();
+    for (size, paths) in candidates {
+        if all_inside(&paths, &reported) {
+            continue;
+        }
+
+        let hashed = paths
+            .into_iter()
+            .filter_map(|path| contents.get(&path).map(|hash| (*hash, path)))
+            .collect();
+        let mut confirmed: Vec<Vec<PathBuf>> = bucket(hashed)
+            .into_values()
+            .filter(|paths| paths.len() > 1)
+            .collect();
+        confirmed.sort();
+        for mut paths in confirmed {
+            if all_inside(&paths, &reported) {
+                continue;
+            }
+            paths.sort();
+            reported.extend(paths.iter().cloned());
+            groups.push(DuplicateGroup {
+                size,
+                paths,
+                is_dir: true,
+            });
+        }
+    }
+
+    groups.sort_by(|a, b| {
+        b.wasted()
+            .cmp(&a.wasted())
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+    groups
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let src_file = File::open(src)?;
+    let dst_file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dst)?;
+    // SAFETY: both file descriptors are valid for the duration of the call
+    let ret = unsafe { libc::ioctl(dst_file.as_raw_fd(), libc::FICLONE, src_file.as_raw_fd()) };
+    if ret == -1 {
+        let err = io::Error::last_os_error();
+        drop(dst_file);
+        let _ = fs::remove_file(dst);
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_src: &Path, _dst: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reflinks are only supported on Linux",
+    ))
+}
+
+/// Read until `buffer` is full or the end of the file, returning the bytes read
+fn read_chunk(file: &mut File, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// Whether two files have exactly the same bytes
+fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let mut buffer_a = vec![0; 64 * 1024];
+    let mut buffer_b = vec![0; 64 * 1024];
+    loop {
+        let n = read_chunk(&mut a, &mut buffer_a)?;
+        if n != read_chunk(&mut b, &mut buffer_b)? || buffer_a[..n] != buffer_b[..n] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Replace a single duplicate with a link to `keep`
+///
+/// The link is created next to the duplicate and renamed over it, so the
+/// duplicate is never missing if linking fails. Right before the rename the
+/// link is compared byte for byte with the duplicate, so a file edited since
+/// it was hashed is left alone.
+fn replace_with_link(keep: &Path, duplicate: &Path, size: u64, mode: LinkMode) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(duplicate)?;
+    if !metadata.is_file() || metadata.len() != size {
+        return Err(io::Error::other("file changed since it was scanned"));
+    }
+
+    let file_name = duplicate
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(".fs_rs-dedupe");
+    let tmp_path = duplicate.with_file_name(tmp_name);
+
+    match mode {
+        LinkMode::Hardlink => fs::hard_link(keep, &tmp_path)?,
+        LinkMode::Reflink => {
+            reflink(keep, &tmp_path)?;
+            if let Err(e) = fs::set_permissions(&tmp_path, metadata.permissions()) {
+                let _ = fs::remove_file(&tmp_path);
+                return Err(e);
+            }
+        }
+    }
+
+    match same_content(&tmp_path, duplicate) {
+        Ok(true) => fs::rename(&tmp_path, duplicate).inspect_err(|_| {
+            let _ = fs::remove_file(&tmp_path);
+        }),
+        Ok(false) => {
+            let _ = fs::remove_file(&tmp_path);
+            Err(io::Error::other("file changed since it was scanned"))
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Replace every duplicate with a link to the first file of its group
+///
+/// # Arguments
+///
+/// * `groups` - The duplicate groups to deduplicate.
+/// * `mode` - Whether to use hard links or reflinks.
+///
+/// # Returns
+///
+/// * A tuple of the bytes reclaimed and the files that could not be replaced.
+pub fn replace_duplicates(
+    groups: &[DuplicateGroup],
+    mode: LinkMode,
+) -> (u64, Vec<(PathBuf, io::Error)>) {
+    let mut reclaimed = 0;
+    let mut errors = Vec::new();
+
+    for group in groups.iter().filter(|g| !g.is_dir) {
+        let Some((keep, duplicates)) = group.paths.split_first() else {
+            continue;
+        };
+        for duplicate in duplicates {
+            match replace_with_link(keep, duplicate, group.size, mode) {
+                Ok(()) => reclaimed += group.size,
+                Err(e) => errors.push((duplicate.clone(), e)),
+            }
+        }
+    }
+
+    (reclaimed, errors)
+}
+
+/// Render duplicate groups as text, one block per group
+pub fn render_groups(groups: &[DuplicateGroup]) -> String {
+    let mut output = String::new();
+
+    for group in groups {
+        let header = format!(
+            "{} wasted, {} copies of {}",
+            format_size(group.wasted(), DECIMAL),
+            group.paths.len(),
+            format_size(group.size, DECIMAL)
+        );
+        output.push_str(&format!("{}\n", header.yellow().bold()));
+        for path in &group.paths {
+            let line = if group.is_dir {
+                format!("  {}/\n", path.display().to_string().blue())
+            } else {
+                format!("  {}\n", path.display().to_string().green())
+            };
+            output.push_str(&line);
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Convert duplicate groups to JSON values
+pub fn to_json(groups: &[DuplicateGroup]) -> Vec<serde_json::Value> {
+    groups
+        .iter()
+        .map(|group| {
+            serde_json::json!({
+                "size_bytes": group.size,
+                "wasted_bytes": group.wasted(),
+                "wasted_human": format_size(group.wasted(), DECIMAL),
+                "paths": group.paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>(),
+                "is_dir": group.is_dir,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,69 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Errors reported by the Result-returning scans of fs_rs
+#[derive(Debug)]
+pub enum Error {
+    /// A path does not exist
+    NotFound(PathBuf),
+    /// A path could not be read for lack of permissions
+    PermissionDenied(PathBuf),
+    /// Any other I/O error, with the path it happened on
+    Io { path: PathBuf, source: io::Error },
+    /// A directory contains itself, e.g. through a bind mount
+    CycleDetected(PathBuf),
+    /// The scan was cancelled before it finished
+    Cancelled,
+}
+
+/// Result of a fallible fs_rs operation
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Classify an I/O error that happened on `path`
+    pub fn from_io(path: &Path, source: io::Error) -> Self {
+        match source.kind() {
+            io::ErrorKind::NotFound => Error::NotFound(path.to_path_buf()),
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied(path.to_path_buf()),
+            _ => Error::Io {
+                path: path.to_path_buf(),
+                source,
+            },
+        }
+    }
+
+    /// The path the error happened on, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::NotFound(path)
+            | Error::PermissionDenied(path)
+            | Error::Io { path, .. }
+            | Error::CycleDetected(path) => Some(path),
+            Error::Cancelled => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(path) => write!(f, "{} does not exist", path.display()),
+            Error::PermissionDenied(path) => write!(f, "permission denied: {}", path.display()),
+            Error::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Error::CycleDetected(path) => {
+                write!(f, "directory cycle detected at {}", path.display())
+            }
+            Error::Cancelled => write!(f, "scan cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
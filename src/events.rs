@@ -0,0 +1,209 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::progress::{Event, ScanContext, Snapshot};
+use crate::utils;
+
+/// Where progress events are written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Destination {
+    Stderr,
+    /// An already open file descriptor, e.g. a pipe set up by a front-end
+    Fd(i32),
+}
+
+impl Destination {
+    /// Parse `stderr` or a file descriptor number
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fs_rs::events::Destination;
+    /// assert_eq!(Destination::parse("stderr"), Ok(Destination::Stderr));
+    /// assert_eq!(Destination::parse("3"), Ok(Destination::Fd(3)));
+    /// assert!(Destination::parse("stdout").is_err());
+    /// ```
+    pub fn parse(input: &str) -> Result<Destination, String> {
+        if input.eq_ignore_ascii_case("stderr") {
+            return Ok(Destination::Stderr);
+        }
+        match input.parse::<i32>() {
+            Ok(fd) if fd > 2 => Ok(Destination::Fd(fd)),
+            Ok(_) => Err("file descriptors 0 to 2 are reserved, use 'stderr'".to_string()),
+            Err(_) => Err(format!(
+                "invalid destination '{input}', expected 'stderr' or a file descriptor number"
+            )),
+        }
+    }
+
+    /// Open the destination for writing
+    fn open(self) -> io::Result<Box<dyn Write + Send>> {
+        match self {
+            Destination::Stderr => Ok(Box::new(io::stderr())),
+            Destination::Fd(fd) => open_fd(fd).map(|f| Box::new(f) as Box<dyn Write + Send>),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn open_fd(fd: i32) -> io::Result<File> {
+    use std::os::unix::io::FromRawFd;
+    // SAFETY: fcntl only queries the descriptor flags
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: the descriptor is open, and was handed to us by the caller for our exclusive use
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+fn open_fd(_fd: i32) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "writing events to a file descriptor is only supported on Unix",
+    ))
+}
+
+/// The current time as an RFC 3339 timestamp
+fn now() -> Option<String> {
+    utils::format_rfc3339(Some(SystemTime::now()))
+}
+
+/// Convert a progress snapshot to a `progress` event
+pub fn snapshot_to_json(snapshot: &Snapshot) -> serde_json::Value {
+    serde_json::json!({
+        "event": "progress",
+        "time": now(),
+        "files": snapshot.files,
+        "bytes": snapshot.bytes,
+        "errors": snapshot.errors,
+        "current_dir": snapshot.current_dir.as_deref().map(|d| d.display().to_string()),
+    })
+}
+
+/// Convert a scan event to a `root_completed`, `error` or `warning` event
+///
+/// `snapshot` is the progress of the scan when the event happened.
+pub fn event_to_json(event: &Event, snapshot: &Snapshot) -> serde_json::Value {
+    match event {
+        Event::RootCompleted(root) => {
+            serde_json::json!({
+                "event": "root_completed",
+                "time": now(),
+                "root": root.display().to_string(),
+                "files": snapshot.files,
+                "bytes": snapshot.bytes,
+            })
+        }
+        Event::Error { path, message } => serde_json::json!({
+            "event": "error",
+            "time": now(),
+            "path": path.as_deref().map(Path::display).map(|p| p.to_string()),
+            "message": message,
+        }),
+        Event::Warning { message } => serde_json::json!({
+            "event": "warning",
+            "time": now(),
+            "message": message,
+        }),
+    }
+}
+
+/// Writes the progress of a scan as one JSON object per line
+///
+/// The events are `started`, periodic `progress` counters, `root_completed`
+/// for each root, `error` for everything that could not be read, `warning`
+/// for other problems, and `finished` with the exit code.
+pub struct EventWriter {
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+    /// Set once `finished` has been written, so no other event follows it
+    done: Arc<AtomicBool>,
+}
+
+/// Write a single event as a line of JSON, ignoring failures as progress is best effort
+fn write_event(out: &Mutex<Box<dyn Write + Send>>, done: &AtomicBool, value: &serde_json::Value) {
+    if let Ok(mut out) = out.lock()
+        && !done.load(Ordering::Relaxed)
+    {
+        let _ = writeln!(out, "{value}");
+        let _ = out.flush();
+    }
+}
+
+impl EventWriter {
+    /// Open a destination and write the `started` event
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The context of the scan to report on.
+    /// * `destination` - Where to write the events.
+    /// * `roots` - The paths about to be scanned.
+    /// * `interval` - How often to write a `progress` event.
+    ///
+    /// # Returns
+    ///
+    /// * The writer, which keeps writing events until `finish` is called.
+    pub fn start(
+        context: &Arc<ScanContext>,
+        destination: Destination,
+        roots: &[String],
+        interval: Duration,
+    ) -> io::Result<EventWriter> {
+        let writer = EventWriter {
+            out: Arc::new(Mutex::new(destination.open()?)),
+            done: Arc::new(AtomicBool::new(false)),
+        };
+        writer.write(&serde_json::json!({
+            "event": "started",
+            "time": now(),
+            "roots": roots,
+        }));
+
+        let (out, done) = (Arc::clone(&writer.out), Arc::clone(&writer.done));
+        // The context owns its observer, so the observer only holds on to it weakly
+        let observed = Arc::downgrade(context);
+        context.set_observer(move |event| {
+            if let Some(context) = observed.upgrade() {
+                write_event(&out, &done, &event_to_json(event, &context.snapshot()));
+            }
+        });
+
+        let (out, done) = (Arc::clone(&writer.out), Arc::clone(&writer.done));
+        let context = Arc::clone(context);
+        thread::spawn(move || {
+            for snapshot in context.watch(interval) {
+                if done.load(Ordering::Relaxed) {
+                    break;
+                }
+                write_event(&out, &done, &snapshot_to_json(&snapshot));
+            }
+        });
+
+        Ok(writer)
+    }
+
+    fn write(&self, value: &serde_json::Value) {
+        write_event(&self.out, &self.done, value);
+    }
+
+    /// Write the `finished` event with the final counters and stop writing events
+    pub fn finish(self, context: &ScanContext, exit_code: u8) {
+        context.clear_observer();
+        let snapshot = context.snapshot();
+        self.write(&serde_json::json!({
+            "event": "finished",
+            "time": now(),
+            "exit_code": exit_code,
+            "files": snapshot.files,
+            "bytes": snapshot.bytes,
+            "errors": snapshot.errors,
+            "cancelled": snapshot.cancelled,
+        }));
+        self.done.store(true, Ordering::Relaxed);
+    }
+}
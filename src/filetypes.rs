@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use colored::Colorize;
+use comfy_table::{Cell, Table};
+use humansize::{DECIMAL, format_size};
+
+use crate::progress;
+use crate::utils::{self, Totals};
+
+/// Key used for files without an extension when grouping by extension
+const NO_EXTENSION: &str = "(none)";
+
+/// Broad file categories used to group extensions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Category {
+    Images,
+    Video,
+    Audio,
+    Archives,
+    Source,
+    Binaries,
+    Logs,
+    Other,
+}
+
+impl Category {
+    /// Name of the category as shown in reports
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Images => "images",
+            Category::Video => "video",
+            Category::Audio => "audio",
+            Category::Archives => "archives",
+            Category::Source => "source",
+            Category::Binaries => "binaries",
+            Category::Logs => "logs",
+            Category::Other => "other",
+        }
+    }
+
+    /// Category for a lowercase file extension
+    fn from_extension(extension: &str) -> Option<Self> {
+        let category = match extension {
+            "png" | "jpg" | "jpeg" | "gif" | "bmp" | "tif" | "tiff" | "webp" | "svg" | "ico"
+            | "heic" | "heif" | "raw" | "cr2" | "nef" | "psd" => Category::Images,
+            "mp4" | "mkv" | "mov" | "avi" | "wmv" | "flv" | "webm" | "m4v" | "mpg" | "mpeg" => {
+                Category::Video
+            }
+            "mp3" | "wav" | "flac" | "aac" | "ogg" | "opus" | "m4a" | "wma" | "aiff" | "mid" => {
+                Category::Audio
+            }
+            "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "zst" | "7z" | "rar" | "lz4" | "jar"
+            | "deb" | "rpm" | "iso" | "dmg" | "crate" | "whl" => Category::Archives,
+            "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "go" | "java" | "kt" | "py" | "rb"
+            | "js" | "jsx" | "ts" | "tsx" | "mjs" | "php" | "swift" | "scala" | "sh" | "bash"
+            | "zsh" | "pl" | "lua" | "sql" | "html" | "css" | "scss" | "toml" | "yaml" | "yml"
+            | "json" | "xml" | "md" => Category::Source,
+            "exe" | "dll" | "so" | "dylib" | "a" | "lib" | "o" | "obj" | "bin" | "class"
+            | "wasm" | "rlib" | "pyc" => Category::Binaries,
+            "log" => Category::Logs,
+            _ => return None,
+        };
+        Some(category)
+    }
+
+    /// Category from the first bytes of a file
+    fn from_magic(header: &[u8]) -> Option<Self> {
+        let starts = |magic: &[u8]| header.starts_with(magic);
+        let category = if starts(b"\x7fELF")
+            || starts(b"MZ")
+            || starts(&[0xcf, 0xfa, 0xed, 0xfe])
+            || starts(&[0xce, 0xfa, 0xed, 0xfe])
+            || starts(&[0xca, 0xfe, 0xba, 0xbe])
+            || starts(b"\0asm")
+        {
+            Category::Binaries
+        } else if starts(b"\x89PNG")
+            || starts(&[0xff, 0xd8, 0xff])
+            || starts(b"GIF8")
+            || starts(b"BM")
+            || (starts(b"RIFF") && header.get(8..12) == Some(b"WEBP"))
+        {
+            Category::Images
+        } else if starts(&[0x1a, 0x45, 0xdf, 0xa3])
+            || header.get(4..8) == Some(b"ftyp")
+            || (starts(b"RIFF") && header.get(8..12) == Some(b"AVI "))
+        {
+            Category::Video
+        } else if starts(b"ID3")
+            || starts(b"OggS")
+            || starts(b"fLaC")
+            || (starts(b"RIFF") && header.get(8..12) == Some(b"WAVE"))
+        {
+            Category::Audio
+        } else if starts(&[0x1f, 0x8b])
+            || starts(b"PK\x03\x04")
+            || starts(&[0xfd, b'7', b'z', b'X', b'Z', 0x00])
+            || starts(b"BZh")
+            || starts(&[0x28, 0xb5, 0x2f, 0xfd])
+            || starts(&[b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c])
+            || starts(b"Rar!")
+            || header.get(257..262) == Some(b"ustar")
+        {
+            Category::Archives
+        } else if starts(b"#!") {
+            Category::Source
+        } else {
+            return None;
+        };
+        Some(category)
+    }
+}
+
+/// Lowercase extension of a path, if it has one
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+}
+
+/// Determine the category of a file
+///
+/// The extension is used when it is known. Otherwise, and for files without
+/// an extension, the first bytes of the file are sniffed for well-known
+/// magic numbers.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file
+///
+/// # Returns
+///
+/// * The category of the file, `Category::Other` if it is not recognised.
+pub fn category_for(path: &Path) -> Category {
+    if let Some(category) = extension(path).and_then(|e| Category::from_extension(&e)) {
+        return category;
+    }
+    if path.extension().is_some() {
+        return Category::Other;
+    }
+
+    let mut header = Vec::with_capacity(262);
+    match File::open(path).and_then(|f| f.take(262).read_to_end(&mut header)) {
+        Ok(_) => Category::from_magic(&header).unwrap_or(Category::Other),
+        Err(_) => Category::Other,
+    }
+}
+
+/// Key a file is grouped under: its category or its lowercase extension
+pub fn type_key(path: &Path, categories: bool) -> String {
+    if categories {
+        category_for(path).name().to_string()
+    } else {
+        extension(path).unwrap_or_else(|| NO_EXTENSION.to_string())
+    }
+}
+
+/// Totals per file type
+pub type TypeMap = HashMap<String, Totals>;
+
+/// Merge the totals of `b` into `a`
+pub fn merge_type_maps(mut a: TypeMap, b: TypeMap) -> TypeMap {
+    for (key, stats) in b {
+        a.entry(key).or_default().merge(stats);
+    }
+    a
+}
+
+/// Aggregate bytes and file counts by type for everything under the given paths
+///
+/// # Arguments
+///
+/// * `paths` - The files or directories to scan.
+/// * `categories` - Group by category instead of by extension.
+///
+/// # Returns
+///
+/// * The totals per type, largest first.
+pub fn breakdown(paths: &[&Path], categories: bool) -> Vec<(String, Totals)> {
+    let mut types = TypeMap::new();
+
+    for path in paths {
+        if path.is_dir() {
+            let found = utils::fold_files(
+                path,
+                &TypeMap::new,
+                &|mut map, file_path, metadata| {
+                    map.entry(type_key(file_path, categories))
+                        .or_default()
+                        .add(metadata.len());
+                    map
+                },
+                &merge_type_maps,
+            );
+            types = merge_type_maps(types, found);
+        } else if let Ok(metadata) = path.metadata() {
+            types
+                .entry(type_key(path, categories))
+                .or_default()
+                .add(metadata.len());
+        }
+        progress::root_completed(path);
+    }
+
+    utils::sorted_totals(types)
+}
+
+/// Add rows to a table from a type breakdown
+pub fn add_rows(table: &mut Table, types: &[(String, Totals)]) {
+    let total_size = types.iter().map(|(_, s)| s.size).sum::<u64>();
+
+    for (key, stats) in types {
+        table.add_row(vec![
+            Cell::new(key.yellow()),
+            Cell::new(format_size(stats.size, DECIMAL).green()),
+            Cell::new(format!("{} files", stats.files)),
+            Cell::new(format!("{:.1}%", utils::percent(stats.size, total_size))),
+        ]);
+    }
+}
+
+/// Convert a type breakdown to JSON values
+pub fn to_json(types: &[(String, Totals)]) -> Vec<serde_json::Value> {
+    let total_size = types.iter().map(|(_, s)| s.size).sum::<u64>();
+
+    types
+        .iter()
+        .map(|(key, stats)| {
+            serde_json::json!({
+                "type": key,
+                "size_bytes": stats.size,
+                "size_human": format_size(stats.size, DECIMAL),
+                "files": stats.files,
+                "percent": utils::percent(stats.size, total_size),
+            })
+        })
+        .collect()
+}
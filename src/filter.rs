@@ -0,0 +1,68 @@
+use std::fs;
+use std::path::Path;
+
+use crate::age::FileTimes;
+use crate::owners;
+use crate::query::Expression;
+
+/// The details of an entry that filters are evaluated against
+#[derive(Clone, Copy, Debug)]
+pub struct EntryInfo<'a> {
+    /// Full path of the entry
+    pub path: &'a Path,
+    /// Size of a file, or of everything below a directory
+    pub size: u64,
+    pub is_dir: bool,
+    /// Timestamps of a file, or the newest timestamps of any file below a directory
+    pub times: FileTimes,
+    /// `(uid, gid)` owning the entry, if known
+    pub owner: Option<(u32, u32)>,
+}
+
+impl<'a> EntryInfo<'a> {
+    /// Details of a file from its metadata
+    pub fn from_metadata(path: &'a Path, metadata: &fs::Metadata) -> Self {
+        EntryInfo {
+            path,
+            size: metadata.len(),
+            is_dir: metadata.is_dir(),
+            times: FileTimes::from_metadata(metadata),
+            owner: owners::owner_of(metadata),
+        }
+    }
+}
+
+/// Which entries are shown in listings, trees and JSON output
+///
+/// Filters only hide entries; totals are still computed over everything.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    /// Hide entries smaller than this many bytes
+    pub min_size: Option<u64>,
+    /// Hide entries larger than this many bytes
+    pub max_size: Option<u64>,
+    /// Hide entries that do not match this expression
+    pub expression: Option<Expression>,
+}
+
+impl Filter {
+    /// Whether the filter lets every entry through
+    pub fn is_empty(&self) -> bool {
+        self.min_size.is_none() && self.max_size.is_none() && self.expression.is_none()
+    }
+
+    /// Whether the filter looks at more than the size of an entry
+    pub fn needs_details(&self) -> bool {
+        self.expression.is_some()
+    }
+
+    /// Whether an entry of `size` bytes is shown
+    pub fn matches_size(&self, size: u64) -> bool {
+        self.min_size.is_none_or(|min| size >= min) && self.max_size.is_none_or(|max| size <= max)
+    }
+
+    /// Whether an entry is shown
+    pub fn matches(&self, entry: &EntryInfo) -> bool {
+        self.matches_size(entry.size) && self.expression.as_ref().is_none_or(|e| e.matches(entry))
+    }
+}
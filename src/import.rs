@@ -0,0 +1,253 @@
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+
+use crate::ncdu;
+use crate::tree::{Entry, Snapshot};
+
+/// The first two bytes of every gzip member
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Largest size a gzipped dump may decompress to, so a small gzip bomb cannot exhaust memory
+pub const MAX_DECOMPRESSED_BYTES: u64 = 1 << 30;
+
+/// A kind of dump that can be imported
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A JSON dump written by `ncdu -o` or `--export-ncdu`
+    Ncdu,
+    /// The output of `du -ab`: a size in bytes, a tab and a path per line
+    Du,
+    /// The output of `find -printf '%s %y %p\n'`: a size, a type letter and a path per line
+    Find,
+}
+
+/// Guess the format of a dump from its first line
+///
+/// # Examples
+///
+/// ```
+/// use fs_rs::import::{Format, detect};
+/// assert_eq!(detect("[1,2,{}"), Some(Format::Ncdu));
+/// assert_eq!(detect("4096\t./src\n"), Some(Format::Du));
+/// assert_eq!(detect("4096 d ./src\n"), Some(Format::Find));
+/// assert_eq!(detect("hello"), None);
+/// ```
+pub fn detect(text: &str) -> Option<Format> {
+    let line = text.lines().find(|line| !line.trim().is_empty())?;
+    if line.trim_start().starts_with('[') {
+        return Some(Format::Ncdu);
+    }
+    let (size, rest) = line.split_once([' ', '\t'])?;
+    if size.is_empty() || !size.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if line.as_bytes()[size.len()] == b'\t' {
+        return Some(Format::Du);
+    }
+    match rest.as_bytes() {
+        [kind, b' ', _, ..] if kind.is_ascii_alphabetic() => Some(Format::Find),
+        _ => None,
+    }
+}
+
+/// Read a dump from a file, or from stdin for `-`, and parse it
+///
+/// Gzip-compressed dumps are decompressed first, and the format is detected
+/// from the contents.
+///
+/// # Arguments
+///
+/// * `source` - The file to read, or `-` for stdin.
+///
+/// # Returns
+///
+/// * The snapshot, or why the dump could not be read.
+pub fn load(source: &Path) -> Result<Snapshot, String> {
+    let mut data = Vec::new();
+    let read = if source == Path::new("-") {
+        std::io::stdin().read_to_end(&mut data)
+    } else {
+        std::fs::File::open(source).and_then(|mut file| file.read_to_end(&mut data))
+    };
+    read.map_err(|e| e.to_string())?;
+    if data.starts_with(&GZIP_MAGIC) {
+        data = gunzip(&data, MAX_DECOMPRESSED_BYTES)?;
+    }
+    // Paths that are not valid UTF-8 keep their other characters
+    parse(&String::from_utf8_lossy(&data))
+}
+
+/// Decompress gzip data, including several concatenated members as written by `cat a.gz b.gz`
+///
+/// # Arguments
+///
+/// * `data` - The compressed data.
+/// * `limit` - The most bytes the data may decompress to.
+///
+/// # Returns
+///
+/// * The decompressed data, or why it could not be decompressed.
+pub fn gunzip(data: &[u8], limit: u64) -> Result<Vec<u8>, String> {
+    let mut decompressed = Vec::new();
+    MultiGzDecoder::new(data)
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut decompressed)
+        .map_err(|e| format!("invalid gzip data: {e}"))?;
+    if decompressed.len() as u64 > limit {
+        return Err(format!("the dump decompresses to more than {limit} bytes"));
+    }
+    Ok(decompressed)
+}
+
+/// Parse a dump in any of the supported formats
+pub fn parse(text: &str) -> Result<Snapshot, String> {
+    match detect(text) {
+        Some(Format::Ncdu) => ncdu::parse(text),
+        Some(Format::Du) => parse_du(text),
+        Some(Format::Find) => parse_find(text),
+        None => Err(
+            "unknown dump format, expected ncdu JSON, `du -ab` or `find -printf '%s %y %p\\n'` output"
+                .to_string(),
+        ),
+    }
+}
+
+/// Parse the output of `du -ab`
+///
+/// du does not say which paths are directories, so a path is taken to be a
+/// directory when other paths are below it. Empty directories therefore
+/// show up as files of the size du reports for them. Sizes must be in bytes,
+/// as printed with `-b`.
+///
+/// # Examples
+///
+/// ```
+/// use fs_rs::import::parse_du;
+/// let snapshot = parse_du("10\t/srv/a.txt\n5\t/srv/logs/b.log\n4101\t/srv/logs\n8207\t/srv\n").unwrap();
+/// assert_eq!(snapshot.root.to_str(), Some("/srv"));
+/// assert_eq!(snapshot.total_size, 15);
+/// ```
+pub fn parse_du(text: &str) -> Result<Snapshot, String> {
+    let mut lines = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            continue;
+        }
+        let (size, path) = line
+            .split_once('\t')
+            .ok_or_else(|| format!("line {}: expected SIZE<tab>PATH", index + 1))?;
+        let size = parse_count(size, index)?;
+        lines.push((PathBuf::from(path), size, None));
+    }
+    build(lines)
+}
+
+/// Parse the output of `find -printf '%s %y %p\n'`
+///
+/// Regular files (`f`) and directories (`d`) are kept; symlinks and other
+/// special files are skipped, as a scan would.
+///
+/// # Examples
+///
+/// ```
+/// use fs_rs::import::parse_find;
+/// let snapshot = parse_find("4096 d .\n10 f ./a b.txt\n7 l ./link\n4096 d ./empty\n").unwrap();
+/// assert_eq!(snapshot.root.to_str(), Some("."));
+/// assert_eq!(snapshot.total_size, 10);
+/// assert_eq!(snapshot.len(), 2);
+/// ```
+pub fn parse_find(text: &str) -> Result<Snapshot, String> {
+    let mut lines = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line.splitn(3, ' ');
+        let (Some(size), Some(kind), Some(path)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(format!("line {}: expected SIZE TYPE PATH", index + 1));
+        };
+        let size = parse_count(size, index)?;
+        match kind {
+            "f" => lines.push((PathBuf::from(path), size, Some(false))),
+            "d" => lines.push((PathBuf::from(path), size, Some(true))),
+            _ => {}
+        }
+    }
+    build(lines)
+}
+
+/// Parse the size at the start of a line
+fn parse_count(size: &str, index: usize) -> Result<u64, String> {
+    size.parse()
+        .map_err(|_| format!("line {}: invalid size '{size}'", index + 1))
+}
+
+/// The deepest directory containing every path
+fn common_root(paths: &[&Path]) -> PathBuf {
+    let mut root: Vec<Component> = match paths.first() {
+        Some(path) => path.components().collect(),
+        None => return PathBuf::new(),
+    };
+    for path in &paths[1..] {
+        let shared = root
+            .iter()
+            .zip(path.components())
+            .take_while(|(a, b)| **a == *b)
+            .count();
+        root.truncate(shared);
+    }
+    root.iter().collect()
+}
+
+/// Build a snapshot from paths with their sizes and, if known, whether they are directories
+///
+/// The snapshot is rooted at the deepest directory containing every path,
+/// which is the path given to du or find when there was only one.
+fn build(lines: Vec<(PathBuf, u64, Option<bool>)>) -> Result<Snapshot, String> {
+    let paths: Vec<&Path> = lines.iter().map(|(path, _, _)| path.as_path()).collect();
+    let mut root = common_root(&paths);
+    // A single file is listed in its directory
+    if lines.len() == 1 && lines[0].2 != Some(true) {
+        root.pop();
+    }
+
+    let relative: Vec<String> = lines
+        .iter()
+        .map(|(path, _, _)| {
+            path.strip_prefix(&root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect();
+    let mut parents = std::collections::HashSet::new();
+    for path in &relative {
+        for (index, _) in path.match_indices(std::path::MAIN_SEPARATOR) {
+            parents.insert(&path[..index]);
+        }
+    }
+
+    let entries = lines
+        .iter()
+        .zip(&relative)
+        .filter(|(_, path)| !path.is_empty())
+        .map(|((_, size, is_dir), path)| {
+            let is_dir = is_dir.unwrap_or_else(|| parents.contains(path.as_str()));
+            Entry {
+                path: path.clone(),
+                size: if is_dir { 0 } else { *size },
+                is_dir,
+                ..Entry::default()
+            }
+        })
+        .collect();
+    if root.as_os_str().is_empty() {
+        root.push(".");
+    }
+    Ok(Snapshot::from_dump(root, entries))
+}
@@ -1,3 +1,23 @@
+pub mod age;
+pub mod budget;
 pub mod cli;
+pub mod compare;
+pub mod daemon;
+pub mod disks;
+pub mod dupes;
+pub mod error;
+pub mod events;
+pub mod filetypes;
+pub mod filter;
+pub mod import;
+pub mod ncdu;
+pub mod owners;
+pub mod progress;
+pub mod prometheus;
+pub mod query;
+pub mod report;
+pub mod serve;
+pub mod top;
 pub mod tree;
+pub mod treemap;
 pub mod utils;
@@ -1,5 +1,7 @@
+use std::process::ExitCode;
+
 use fs_rs::cli;
 
-fn main() {
-    cli::run();
+fn main() -> ExitCode {
+    cli::run()
 }
@@ -0,0 +1,205 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use crate::age::FileTimes;
+use crate::tree::{Entry, Snapshot, TreeNode};
+
+/// Major and minor version of the ncdu export format
+const FORMAT_VERSION: (u64, u64) = (1, 2);
+
+/// Seconds since the epoch, or nothing for times before it
+fn epoch_seconds(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|elapsed| elapsed.as_secs())
+}
+
+/// Convert a snapshot to an ncdu JSON dump, as written by `ncdu -o`
+///
+/// fs_rs measures apparent sizes, so each file's `dsize` is its `asize`.
+/// Directories get no size of their own, so ncdu shows the same totals as
+/// fs_rs. Modification times and owners are included as ncdu's extended
+/// information when known.
+///
+/// # Arguments
+///
+/// * `snapshot` - The scan to export.
+///
+/// # Returns
+///
+/// * The dump, which ncdu can open with `ncdu -f FILE`.
+pub fn to_json(snapshot: &Snapshot) -> Value {
+    let metadata = serde_json::json!({
+        "progname": env!("CARGO_PKG_NAME"),
+        "progver": env!("CARGO_PKG_VERSION"),
+        "timestamp": epoch_seconds(SystemTime::now()).unwrap_or(0),
+    });
+    let root = dir_to_json(
+        &snapshot.root.display().to_string(),
+        &snapshot.to_tree(None),
+    );
+    serde_json::json!([FORMAT_VERSION.0, FORMAT_VERSION.1, metadata, root])
+}
+
+/// Convert a directory to an array of its own details followed by its children
+fn dir_to_json(name: &str, node: &TreeNode) -> Value {
+    let mut items = vec![info_to_json(name, node, true)];
+    for (child_name, child) in &node.children {
+        if child.is_dir {
+            items.push(dir_to_json(child_name, child));
+        } else {
+            items.push(info_to_json(child_name, child, false));
+        }
+    }
+    Value::Array(items)
+}
+
+/// The details ncdu keeps for a file or directory
+fn info_to_json(name: &str, node: &TreeNode, is_dir: bool) -> Value {
+    let mut info = serde_json::json!({ "name": name });
+    if !is_dir {
+        info["asize"] = node.size.into();
+        info["dsize"] = node.size.into();
+        if let Some(mtime) = node.times.modified.and_then(epoch_seconds) {
+            info["mtime"] = mtime.into();
+        }
+    }
+    if let Some((uid, gid)) = node.owner {
+        info["uid"] = uid.into();
+        info["gid"] = gid.into();
+    }
+    info
+}
+
+/// Parse an ncdu JSON dump into a snapshot
+///
+/// Files take their apparent size (`asize`), and directory sizes are
+/// computed from the files below them. Excluded entries and anything that is
+/// not a regular file, such as symlinks, are skipped, as a scan would. A
+/// `read_error` on an entry is counted in the snapshot's `errors`.
+///
+/// # Arguments
+///
+/// * `text` - The contents of a file written by `ncdu -o` or `to_json`.
+///
+/// # Returns
+///
+/// * The snapshot, rooted at the path ncdu scanned, or why the dump could not be read.
+///
+/// # Examples
+///
+/// ```
+/// use fs_rs::ncdu::parse;
+/// let dump = r#"[1, 2, {"progname": "ncdu"},
+///     [{"name": "/srv"}, {"name": "a.txt", "asize": 10},
+///      [{"name": "logs", "asize": 4096}, {"name": "b.log", "asize": 5}]]]"#;
+/// let snapshot = parse(dump).unwrap();
+/// assert_eq!(snapshot.root.to_str(), Some("/srv"));
+/// assert_eq!(snapshot.total_size, 15);
+/// assert_eq!(snapshot.len(), 3);
+/// assert!(parse("[2, 0, {}, []]").is_err());
+/// ```
+pub fn parse(text: &str) -> Result<Snapshot, String> {
+    let dump: Value =
+        serde_json::from_str(text).map_err(|e| format!("not an ncdu JSON dump: {e}"))?;
+    let major = dump.get(0).and_then(Value::as_u64);
+    if major.is_some_and(|major| major != FORMAT_VERSION.0) {
+        return Err(format!(
+            "unsupported ncdu dump version {}",
+            major.unwrap_or_default()
+        ));
+    }
+    let Some(root) = dump
+        .get(3)
+        .and_then(Value::as_array)
+        .filter(|_| major.is_some())
+    else {
+        return Err("not an ncdu JSON dump".to_string());
+    };
+    let info = root
+        .first()
+        .ok_or_else(|| "the root directory has no details".to_string())?;
+    let name = name_of(info)?;
+
+    let mut entries = Vec::new();
+    let mut errors = u64::from(info["read_error"] == true);
+    collect(&root[1..], "", &mut entries, &mut errors)?;
+    let mut snapshot = Snapshot::from_dump(PathBuf::from(name), entries);
+    snapshot.errors = errors;
+    Ok(snapshot)
+}
+
+/// The name of an entry, which every entry must have
+fn name_of(info: &Value) -> Result<&str, String> {
+    info["name"]
+        .as_str()
+        .ok_or_else(|| format!("entry without a name: {info}"))
+}
+
+/// Add the entries of a directory to `entries`, with paths below `prefix`
+fn collect(
+    items: &[Value],
+    prefix: &str,
+    entries: &mut Vec<Entry>,
+    errors: &mut u64,
+) -> Result<(), String> {
+    let join = |name: &str| {
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{prefix}{}{name}", std::path::MAIN_SEPARATOR)
+        }
+    };
+
+    for item in items {
+        match item {
+            Value::Array(dir) => {
+                let info = dir
+                    .first()
+                    .ok_or_else(|| format!("directory without details in {prefix:?}"))?;
+                let path = join(name_of(info)?);
+                if info["read_error"] == true {
+                    *errors += 1;
+                }
+                entries.push(Entry {
+                    path: path.clone(),
+                    is_dir: true,
+                    owner: owner_of(info),
+                    ..Entry::default()
+                });
+                collect(&dir[1..], &path, entries, errors)?;
+            }
+            Value::Object(_) => {
+                if item["read_error"] == true {
+                    *errors += 1;
+                }
+                if !item["excluded"].is_null() || item["notreg"] == true {
+                    continue;
+                }
+                let modified = item["mtime"]
+                    .as_u64()
+                    .map(|mtime| UNIX_EPOCH + Duration::from_secs(mtime));
+                entries.push(Entry {
+                    path: join(name_of(item)?),
+                    size: item["asize"].as_u64().unwrap_or(0),
+                    is_dir: false,
+                    times: FileTimes {
+                        modified,
+                        ..FileTimes::default()
+                    },
+                    owner: owner_of(item),
+                });
+            }
+            _ => return Err(format!("unexpected entry in {prefix:?}: {item}")),
+        }
+    }
+    Ok(())
+}
+
+/// The `(uid, gid)` of an entry, if ncdu recorded both
+fn owner_of(info: &Value) -> Option<(u32, u32)> {
+    let id = |field: &str| info[field].as_u64().and_then(|id| u32::try_from(id).ok());
+    id("uid").zip(id("gid"))
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use colored::Colorize;
+use comfy_table::{Cell, Table};
+use humansize::{DECIMAL, format_size};
+
+use crate::progress;
+use crate::utils::{self, Totals};
+
+/// Totals per user or group id
+pub type IdMap = HashMap<u32, Totals>;
+
+/// The `(uid, gid)` owning a file, `None` on platforms without Unix ownership
+#[cfg(unix)]
+pub fn owner_of(metadata: &fs::Metadata) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.uid(), metadata.gid()))
+}
+
+/// The `(uid, gid)` owning a file, `None` on platforms without Unix ownership
+#[cfg(not(unix))]
+pub fn owner_of(_metadata: &fs::Metadata) -> Option<(u32, u32)> {
+    None
+}
+
+/// Call a reentrant passwd/group lookup, growing the buffer until it fits
+///
+/// `extract` reads the wanted field from the entry while the buffer it
+/// points into is still alive.
+#[cfg(unix)]
+fn lookup_entry<T, R>(
+    lookup: impl Fn(*mut T, *mut libc::c_char, usize, *mut *mut T) -> libc::c_int,
+    extract: impl Fn(&T) -> R,
+) -> Option<R> {
+    let mut buffer: Vec<libc::c_char> = vec![0; 1024];
+    loop {
+        // SAFETY: passwd and group are plain C structs for which all zeroes is a valid value
+        let mut entry: T = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+        let ret = lookup(&mut entry, buffer.as_mut_ptr(), buffer.len(), &mut result);
+        if ret == libc::ERANGE && buffer.len() < 1 << 20 {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+        if ret != 0 || result.is_null() {
+            return None;
+        }
+        return Some(extract(&entry));
+    }
+}
+
+/// Copy a NUL-terminated C string returned by a passwd/group lookup
+#[cfg(unix)]
+fn c_string(ptr: *const libc::c_char) -> String {
+    // SAFETY: on success the lookups return NUL-terminated strings inside their buffer
+    let name = unsafe { std::ffi::CStr::from_ptr(ptr) };
+    name.to_string_lossy().into_owned()
+}
+
+/// Resolve a user id to a name through the local passwd database
+///
+/// Falls back to the numeric id if the user is unknown.
+pub fn user_name(uid: u32) -> String {
+    #[cfg(unix)]
+    let name = lookup_entry(
+        // SAFETY: all pointers are valid and `len` is the size of the buffer
+        |pwd, buf, len, result| unsafe { libc::getpwuid_r(uid, pwd, buf, len, result) },
+        |pwd: &libc::passwd| c_string(pwd.pw_name),
+    );
+    #[cfg(not(unix))]
+    let name = None;
+    name.unwrap_or_else(|| uid.to_string())
+}
+
+/// Resolve a group id to a name through the local group database
+///
+/// Falls back to the numeric id if the group is unknown.
+pub fn group_name(gid: u32) -> String {
+    #[cfg(unix)]
+    let name = lookup_entry(
+        // SAFETY: all pointers are valid and `len` is the size of the buffer
+        |grp, buf, len, result| unsafe { libc::getgrgid_r(gid, grp, buf, len, result) },
+        |grp: &libc::group| c_string(grp.gr_name),
+    );
+    #[cfg(not(unix))]
+    let name = None;
+    name.unwrap_or_else(|| gid.to_string())
+}
+
+/// Resolve a user name, or a numeric user id, to a user id
+pub fn user_id(name: &str) -> Option<u32> {
+    if let Ok(uid) = name.parse() {
+        return Some(uid);
+    }
+    #[cfg(unix)]
+    {
+        let name = std::ffi::CString::new(name).ok()?;
+        lookup_entry(
+            // SAFETY: all pointers are valid and `len` is the size of the buffer
+            |pwd, buf, len, result| unsafe {
+                libc::getpwnam_r(name.as_ptr(), pwd, buf, len, result)
+            },
+            |pwd: &libc::passwd| pwd.pw_uid,
+        )
+    }
+    #[cfg(not(unix))]
+    None
+}
+
+/// Resolve a group name, or a numeric group id, to a group id
+pub fn group_id(name: &str) -> Option<u32> {
+    if let Ok(gid) = name.parse() {
+        return Some(gid);
+    }
+    #[cfg(unix)]
+    {
+        let name = std::ffi::CString::new(name).ok()?;
+        lookup_entry(
+            // SAFETY: all pointers are valid and `len` is the size of the buffer
+            |grp, buf, len, result| unsafe {
+                libc::getgrnam_r(name.as_ptr(), grp, buf, len, result)
+            },
+            |grp: &libc::group| grp.gr_gid,
+        )
+    }
+    #[cfg(not(unix))]
+    None
+}
+
+/// Resolves ids to names, looking each id up only once
+#[derive(Debug, Default)]
+pub struct NameCache {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl NameCache {
+    /// Name of a user id
+    pub fn user(&mut self, uid: u32) -> String {
+        self.users
+            .entry(uid)
+            .or_insert_with(|| user_name(uid))
+            .clone()
+    }
+
+    /// Name of a group id
+    pub fn group(&mut self, gid: u32) -> String {
+        self.groups
+            .entry(gid)
+            .or_insert_with(|| group_name(gid))
+            .clone()
+    }
+}
+
+/// Merge the totals of `b` into `a`
+pub fn merge_id_maps(mut a: IdMap, b: IdMap) -> IdMap {
+    for (id, totals) in b {
+        a.entry(id).or_default().merge(totals);
+    }
+    a
+}
+
+/// Bytes and file counts per user and per group
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OwnerReport {
+    /// `(name, uid, totals)` per user, largest first
+    pub users: Vec<(String, u32, Totals)>,
+    /// `(name, gid, totals)` per group, largest first
+    pub groups: Vec<(String, u32, Totals)>,
+}
+
+/// Resolve the ids of an `IdMap` to names and sort it, largest first
+pub(crate) fn named(
+    ids: &IdMap,
+    mut name: impl FnMut(u32) -> String,
+) -> Vec<(String, u32, Totals)> {
+    let mut named: Vec<(String, u32, Totals)> = ids
+        .iter()
+        .map(|(id, totals)| (name(*id), *id, *totals))
+        .collect();
+    named.sort_by(|a, b| b.2.size.cmp(&a.2.size).then_with(|| a.0.cmp(&b.0)));
+    named
+}
+
+/// Short summary of the largest users and groups, e.g. `users: alice 12 kB; groups: staff 12 kB`
+pub fn summary(report: &OwnerReport) -> String {
+    let names = |owners: &[(String, u32, Totals)]| {
+        let totals: Vec<(String, Totals)> = owners
+            .iter()
+            .map(|(name, _, totals)| (name.clone(), *totals))
+            .collect();
+        utils::summary(&totals, 3)
+    };
+    format!(
+        "users: {}; groups: {}",
+        names(&report.users),
+        names(&report.groups)
+    )
+}
+
+/// Aggregate bytes and file counts per owner and group for everything under the given paths
+///
+/// Owners are only known on Unix; elsewhere the report is empty.
+///
+/// # Arguments
+///
+/// * `paths` - The files or directories to scan.
+///
+/// # Returns
+///
+/// * The totals per user and per group, largest first.
+pub fn owner_breakdown(paths: &[&Path]) -> OwnerReport {
+    let add = |(mut users, mut groups): (IdMap, IdMap), metadata: &fs::Metadata| {
+        if let Some((uid, gid)) = owner_of(metadata) {
+            users.entry(uid).or_default().add(metadata.len());
+            groups.entry(gid).or_default().add(metadata.len());
+        }
+        (users, groups)
+    };
+    let merge =
+        |a: (IdMap, IdMap), b: (IdMap, IdMap)| (merge_id_maps(a.0, b.0), merge_id_maps(a.1, b.1));
+
+    let mut totals = (IdMap::new(), IdMap::new());
+    for path in paths {
+        if path.is_dir() {
+            let found = utils::fold_files(
+                path,
+                &Default::default,
+                &|totals, _, metadata| add(totals, metadata),
+                &merge,
+            );
+            totals = merge(totals, found);
+        } else if let Ok(metadata) = path.metadata() {
+            totals = add(totals, &metadata);
+        }
+        progress::root_completed(path);
+    }
+
+    OwnerReport {
+        users: named(&totals.0, user_name),
+        groups: named(&totals.1, group_name),
+    }
+}
+
+/// Add rows to a table from per-user or per-group totals
+pub fn add_rows(table: &mut Table, owners: &[(String, u32, Totals)]) {
+    let total_size = owners.iter().map(|(_, _, t)| t.size).sum::<u64>();
+
+    for (name, _, totals) in owners {
+        table.add_row(vec![
+            Cell::new(name.yellow()),
+            Cell::new(format_size(totals.size, DECIMAL).green()),
+            Cell::new(format!("{} files", totals.files)),
+            Cell::new(format!("{:.1}%", utils::percent(totals.size, total_size))),
+        ]);
+    }
+}
+
+fn owners_to_json(owners: &[(String, u32, Totals)]) -> Vec<serde_json::Value> {
+    owners
+        .iter()
+        .map(|(name, id, totals)| {
+            serde_json::json!({
+                "name": name,
+                "id": id,
+                "size_bytes": totals.size,
+                "size_human": format_size(totals.size, DECIMAL),
+                "files": totals.files,
+            })
+        })
+        .collect()
+}
+
+/// Convert an owner report to a JSON object with `users` and `groups` arrays
+pub fn to_json(report: &OwnerReport) -> serde_json::Value {
+    serde_json::json!({
+        "users": owners_to_json(&report.users),
+        "groups": owners_to_json(&report.groups),
+    })
+}
@@ -0,0 +1,310 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::Error;
+
+thread_local! {
+    /// The context scans started on this thread report into, set by `ScanContext::run`
+    static CURRENT: RefCell<Option<Arc<ScanContext>>> = const { RefCell::new(None) };
+}
+
+/// A function called for every progress event
+pub type Observer = dyn Fn(&Event) + Send + Sync;
+
+/// Something that happened during a scan
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// Everything under a root passed to a scan has been visited
+    RootCompleted(PathBuf),
+    /// A file or directory could not be read and was skipped
+    Error {
+        path: Option<PathBuf>,
+        message: String,
+    },
+    /// Something the user should know about that is not tied to a single file, e.g. a missing root
+    Warning { message: String },
+}
+
+/// The progress of a scan at one point in time
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Files visited so far
+    pub files: u64,
+    /// Bytes counted so far
+    pub bytes: u64,
+    /// Files and directories that could not be read so far
+    pub errors: u64,
+    /// The directory the scan most recently entered
+    pub current_dir: Option<PathBuf>,
+    /// Whether the scan was cancelled
+    pub cancelled: bool,
+}
+
+/// The progress counters, cancellation flag and observer of one scan
+///
+/// Scanners report into the context of the thread that started them, set
+/// with `ScanContext::run`, and carry it over to the worker threads they
+/// spread the work across. Scans running under different contexts can be
+/// watched and cancelled independently.
+#[derive(Default)]
+pub struct ScanContext {
+    /// The context of the scan this one is part of, which sees its progress too
+    parent: Option<Arc<ScanContext>>,
+    /// Set once the scan should stop
+    cancelled: Arc<AtomicBool>,
+    /// Files visited so far
+    files: AtomicU64,
+    /// Bytes counted so far
+    bytes: AtomicU64,
+    /// Files and directories that could not be read so far
+    errors: AtomicU64,
+    /// The directory the scan most recently entered
+    current_dir: Mutex<Option<PathBuf>>,
+    /// Called for every event, if set
+    observer: RwLock<Option<Box<Observer>>>,
+    /// Set when warnings should only reach the observers, e.g. because stderr carries the events
+    quiet: AtomicBool,
+}
+
+/// Restores the previous context of the thread when dropped
+struct Restore(Option<Arc<ScanContext>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        CURRENT.set(self.0.take());
+    }
+}
+
+impl ScanContext {
+    /// Create a context with empty counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a context for part of this scan
+    ///
+    /// The part has its own counters, which are also added to this
+    /// context's, and stops when this scan is cancelled.
+    pub fn child(self: &Arc<Self>) -> ScanContext {
+        ScanContext {
+            parent: Some(Arc::clone(self)),
+            cancelled: Arc::clone(&self.cancelled),
+            ..Self::default()
+        }
+    }
+
+    /// Run `f` with this context, so the scans it starts report into it
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - The function starting the scans.
+    ///
+    /// # Returns
+    ///
+    /// * What `f` returned.
+    pub fn run<T>(self: &Arc<Self>, f: impl FnOnce() -> T) -> T {
+        let _restore = Restore(CURRENT.replace(Some(Arc::clone(self))));
+        f()
+    }
+
+    /// The context of the calling thread, or a new one if it has none
+    pub fn current() -> Arc<ScanContext> {
+        CURRENT.with_borrow(Clone::clone).unwrap_or_default()
+    }
+
+    /// Take a snapshot of the progress of the scan
+    ///
+    /// A front-end can poll this while the scan runs on another thread.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            files: self.files.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            current_dir: self.current_dir.lock().ok().and_then(|dir| dir.clone()),
+            cancelled: self.is_cancelled(),
+        }
+    }
+
+    /// Send a snapshot of the progress every `interval` until the receiver is dropped
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - How often to send a snapshot.
+    ///
+    /// # Returns
+    ///
+    /// * The receiving end of the progress channel.
+    pub fn watch(self: &Arc<Self>, interval: Duration) -> mpsc::Receiver<Snapshot> {
+        let (sender, receiver) = mpsc::channel();
+        let context = Arc::clone(self);
+        thread::spawn(move || {
+            while sender.send(context.snapshot()).is_ok() {
+                thread::sleep(interval);
+            }
+        });
+        receiver
+    }
+
+    /// Call `observer` for every event of the scan, replacing any previous observer
+    ///
+    /// The observer is called from the scanning threads, so it should return quickly.
+    pub fn set_observer(&self, observer: impl Fn(&Event) + Send + Sync + 'static) {
+        if let Ok(mut current) = self.observer.write() {
+            *current = Some(Box::new(observer));
+        }
+    }
+
+    /// Stop passing events to the observer
+    pub fn clear_observer(&self) {
+        if let Ok(mut current) = self.observer.write() {
+            *current = None;
+        }
+    }
+
+    /// Whether the scan should stop
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// A token that cancels the scan
+    pub fn cancel_token(&self) -> CancelToken {
+        CancelToken(Arc::clone(&self.cancelled))
+    }
+
+    /// Pass a warning to the observers of the scan
+    pub fn warn(&self, message: impl Into<String>) {
+        self.notify(&Event::Warning {
+            message: message.into(),
+        });
+    }
+
+    /// Keep warnings raised during the scan off stderr and only pass them to the observers
+    pub fn set_quiet(&self) {
+        self.quiet.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether warnings raised during this scan or the one it is part of stay off stderr
+    pub fn is_quiet(&self) -> bool {
+        self.quiet.load(Ordering::Relaxed) || self.parent.as_ref().is_some_and(|p| p.is_quiet())
+    }
+
+    /// Record a file of `size` bytes visited by the scan
+    pub(crate) fn file_visited(&self, size: u64) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(size, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.file_visited(size);
+        }
+    }
+
+    /// Record that the scan entered a directory
+    pub(crate) fn entered_dir(&self, path: &Path) {
+        // Progress is best effort, so never wait for another thread to finish updating it
+        if let Ok(mut dir) = self.current_dir.try_lock() {
+            *dir = Some(path.to_path_buf());
+        }
+        if let Some(parent) = &self.parent {
+            parent.entered_dir(path);
+        }
+    }
+
+    /// Record a file or directory that the scan could not read
+    pub(crate) fn error(&self, error: &Error) {
+        let mut context = Some(self);
+        while let Some(current) = context {
+            current.errors.fetch_add(1, Ordering::Relaxed);
+            context = current.parent.as_deref();
+        }
+        self.notify(&Event::Error {
+            path: error.path().map(Path::to_path_buf),
+            message: error.to_string(),
+        });
+    }
+
+    /// Pass an event to the observers of this context and the ones it is part of
+    fn notify(&self, event: &Event) {
+        if let Ok(observer) = self.observer.read()
+            && let Some(observer) = observer.as_ref()
+        {
+            observer(event);
+        }
+        if let Some(parent) = &self.parent {
+            parent.notify(event);
+        }
+    }
+}
+
+/// Call `f` with the context of the calling thread, if it has one
+fn with_current(f: impl FnOnce(&ScanContext)) {
+    CURRENT.with_borrow(|context| {
+        if let Some(context) = context {
+            f(context);
+        }
+    });
+}
+
+/// Record a file of `size` bytes visited by the scan of the calling thread
+pub(crate) fn file_visited(size: u64) {
+    with_current(|context| context.file_visited(size));
+}
+
+/// Record that the scan of the calling thread entered a directory
+pub(crate) fn entered_dir(path: &Path) {
+    with_current(|context| context.entered_dir(path));
+}
+
+/// Record a file or directory that the scan of the calling thread could not read
+pub(crate) fn error(error: &Error) {
+    with_current(|context| context.error(error));
+}
+
+/// Record that the scan of the calling thread has visited everything under one of its roots
+pub(crate) fn root_completed(root: &Path) {
+    with_current(|context| context.notify(&Event::RootCompleted(root.to_path_buf())));
+}
+
+/// Report a problem that does not stop the scan of the calling thread
+///
+/// It is printed on stderr, or passed on as a warning event if the scan was
+/// made quiet with `ScanContext::set_quiet`.
+pub(crate) fn warn(message: &str) {
+    let context = ScanContext::current();
+    if context.is_quiet() {
+        context.warn(message);
+    } else {
+        eprintln!("{message}");
+    }
+}
+
+/// Whether the scan of the calling thread should stop
+pub(crate) fn is_cancelled() -> bool {
+    CURRENT.with_borrow(|context| {
+        context
+            .as_ref()
+            .is_some_and(|context| context.is_cancelled())
+    })
+}
+
+/// Stops a scan
+///
+/// A cancelled scan returns what it found so far, and the Result-returning
+/// scans fail with `Error::Cancelled`. Tokens are cheap to clone and can be
+/// moved into a signal handler or another thread.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Ask the scan to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether cancellation was requested
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,268 @@
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rayon::prelude::*;
+
+use crate::error::Error;
+use crate::progress::{self, ScanContext};
+use crate::utils::{self, Totals};
+
+/// Label names set by fs_rs itself, which user labels cannot override
+const RESERVED_LABELS: [&str; 3] = ["root", "path", "depth"];
+
+/// Size and file count of a directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirMetric {
+    pub path: PathBuf,
+    /// Levels below the scanned root, 0 for the root itself
+    pub depth: usize,
+    pub totals: Totals,
+}
+
+/// Parse a `name=value` label, checking the name against the Prometheus naming rules
+///
+/// # Examples
+///
+/// ```
+/// use fs_rs::prometheus::parse_label;
+/// assert_eq!(parse_label("env=prod"), Ok(("env".to_string(), "prod".to_string())));
+/// assert!(parse_label("2env=prod").is_err());
+/// assert!(parse_label("path=/tmp").is_err());
+/// ```
+pub fn parse_label(input: &str) -> Result<(String, String), String> {
+    let (name, value) = input
+        .split_once('=')
+        .ok_or_else(|| format!("invalid label '{input}', expected NAME=VALUE"))?;
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with("__");
+    if !valid {
+        return Err(format!("invalid label name '{name}'"));
+    }
+    if RESERVED_LABELS.contains(&name) {
+        return Err(format!("label '{name}' is set by fs_rs"));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Collect the size and file count of every directory under `path`, down to `depth` levels
+///
+/// Sizes are aggregated as in `utils::calculate_dir_size`, and directories
+/// below `depth` are folded into their ancestor at `depth`.
+///
+/// # Arguments
+///
+/// * `path` - The directory to scan.
+/// * `depth` - How many levels of directories to report below `path`.
+///
+/// # Returns
+///
+/// * The root followed by every directory down to `depth`, sorted by path.
+pub fn dir_metrics(path: &Path, depth: usize) -> Vec<DirMetric> {
+    let mut metrics = Vec::new();
+    let totals = collect(path, 0, depth, &mut metrics);
+    metrics.push(DirMetric {
+        path: path.to_path_buf(),
+        depth: 0,
+        totals,
+    });
+    metrics.sort_by(|a, b| a.path.cmp(&b.path));
+    progress::root_completed(path);
+    metrics
+}
+
+/// Add the directories below `path` to `metrics` and return the totals of `path`
+fn collect(path: &Path, level: usize, depth: usize, metrics: &mut Vec<DirMetric>) -> Totals {
+    if level >= depth {
+        return utils::calculate_dir_totals(path);
+    }
+    if progress::is_cancelled() {
+        return Totals::default();
+    }
+    let read_dir = match fs::read_dir(path) {
+        Ok(read_dir) => read_dir,
+        Err(e) => {
+            utils::note_unreadable(Error::from_io(path, e));
+            return Totals::default();
+        }
+    };
+    progress::entered_dir(path);
+
+    let entries: Vec<_> = read_dir.filter_map(Result::ok).collect();
+    let context = ScanContext::current();
+    let found: Vec<(Totals, Vec<DirMetric>)> = entries
+        .par_iter()
+        .map(|entry| {
+            context.run(|| {
+                let entry_path = entry.path();
+                let mut totals = Totals::default();
+                let mut below = Vec::new();
+                match entry.file_type() {
+                    Ok(file_type) if file_type.is_dir() => {
+                        totals = collect(&entry_path, level + 1, depth, &mut below);
+                        below.push(DirMetric {
+                            path: entry_path,
+                            depth: level + 1,
+                            totals,
+                        });
+                    }
+                    Ok(file_type) if !file_type.is_symlink() => match entry.metadata() {
+                        Ok(metadata) => {
+                            progress::file_visited(metadata.len());
+                            totals.add(metadata.len());
+                        }
+                        Err(e) => utils::note_unreadable(Error::from_io(&entry_path, e)),
+                    },
+                    Ok(_) => {}
+                    Err(e) => utils::note_unreadable(Error::from_io(&entry_path, e)),
+                }
+                (totals, below)
+            })
+        })
+        .collect();
+
+    let mut totals = Totals::default();
+    for (found_totals, below) in found {
+        totals.merge(found_totals);
+        metrics.extend(below);
+    }
+    totals
+}
+
+/// Escape a label value for the exposition format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Format the label set of a directory, fs_rs labels first
+fn label_set(root: &Path, metric: &DirMetric, labels: &[(String, String)]) -> String {
+    let mut pairs = vec![
+        ("root", root.display().to_string()),
+        ("path", metric.path.display().to_string()),
+        ("depth", metric.depth.to_string()),
+    ];
+    pairs.extend(
+        labels
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone())),
+    );
+    let pairs: Vec<String> = pairs
+        .into_iter()
+        .map(|(name, value)| format!("{name}=\"{}\"", escape_label(&value)))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// Write one gauge with a series per directory
+fn write_series(
+    text: &mut String,
+    name: &str,
+    help: &str,
+    roots: &[(&Path, Vec<DirMetric>)],
+    labels: &[(String, String)],
+    value: impl Fn(&Totals) -> u64,
+) {
+    let _ = writeln!(text, "# HELP {name} {help}");
+    let _ = writeln!(text, "# TYPE {name} gauge");
+    for (root, metrics) in roots {
+        for metric in metrics {
+            let labels = label_set(root, metric, labels);
+            let _ = writeln!(text, "{name}{labels} {}", value(&metric.totals));
+        }
+    }
+}
+
+/// Render directory metrics in the Prometheus text exposition format
+///
+/// # Arguments
+///
+/// * `roots` - The metrics of each scanned root, as returned by `dir_metrics`.
+/// * `labels` - Extra labels added to every series.
+/// * `errors` - Files and directories that could not be read.
+///
+/// # Returns
+///
+/// * The metrics text, ending with a newline.
+pub fn render(
+    roots: &[(&Path, Vec<DirMetric>)],
+    labels: &[(String, String)],
+    errors: u64,
+) -> String {
+    let mut text = String::new();
+    write_series(
+        &mut text,
+        "fs_rs_directory_size_bytes",
+        "Bytes used by the files under a directory.",
+        roots,
+        labels,
+        |totals| totals.size,
+    );
+    write_series(
+        &mut text,
+        "fs_rs_directory_files",
+        "Number of files under a directory.",
+        roots,
+        labels,
+        |totals| totals.files,
+    );
+
+    let extra: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{name}=\"{}\"", escape_label(value)))
+        .collect();
+    let extra = if extra.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", extra.join(","))
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let _ = writeln!(
+        text,
+        "# HELP fs_rs_scan_errors Files and directories that could not be read."
+    );
+    let _ = writeln!(text, "# TYPE fs_rs_scan_errors gauge");
+    let _ = writeln!(text, "fs_rs_scan_errors{extra} {errors}");
+    let _ = writeln!(
+        text,
+        "# HELP fs_rs_scan_timestamp_seconds When the scan finished, in seconds since the epoch."
+    );
+    let _ = writeln!(text, "# TYPE fs_rs_scan_timestamp_seconds gauge");
+    let _ = writeln!(text, "fs_rs_scan_timestamp_seconds{extra} {timestamp}");
+    text
+}
+
+/// Write `contents` to `path` atomically, so readers never see a partial file
+///
+/// The contents go to a hidden temporary file in the same directory, which
+/// is then renamed over `path`. The temporary name does not end in `.prom`,
+/// so node_exporter's textfile collector ignores it.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let temp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = File::create(&temp).and_then(|mut file| {
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    });
+    match result.and_then(|_| fs::rename(&temp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
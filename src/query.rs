@@ -0,0 +1,699 @@
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use chrono::{Local, NaiveDate, TimeZone};
+use glob::{MatchOptions, Pattern};
+
+use crate::age::TimeField;
+use crate::filter::EntryInfo;
+use crate::owners;
+use crate::utils;
+
+/// Characters that end a bare word
+const SPECIAL_CHARS: &str = "()=,!<>~\"'";
+
+/// An error in a filter expression, with the location of the offending token
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// The whole expression that failed to parse
+    pub input: String,
+    /// Byte range of the offending token in `input`
+    pub start: usize,
+    pub end: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offset = self.input[..self.start].chars().count();
+        let width = self.input[self.start..self.end].chars().count().max(1);
+        writeln!(f, "{}", self.message)?;
+        writeln!(f, "  {}", self.input)?;
+        write!(f, "  {}{}", " ".repeat(offset), "^".repeat(width))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Str(String),
+    LParen,
+    RParen,
+    Comma,
+    Op(CmpOp),
+    Glob,
+    End,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+impl Token {
+    /// Whether the token is the given keyword, ignoring case
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.kind, TokenKind::Word(w) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn describe(&self) -> String {
+        match &self.kind {
+            TokenKind::Word(w) => format!("'{w}'"),
+            TokenKind::Str(s) => format!("\"{s}\""),
+            TokenKind::LParen => "'('".to_string(),
+            TokenKind::RParen => "')'".to_string(),
+            TokenKind::Comma => "','".to_string(),
+            TokenKind::Op(op) => format!("'{op}'"),
+            TokenKind::Glob => "'~'".to_string(),
+            TokenKind::End => "end of expression".to_string(),
+        }
+    }
+}
+
+/// Comparison operators
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn compare<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            CmpOp::Eq => left == right,
+            CmpOp::Ne => left != right,
+            CmpOp::Lt => left < right,
+            CmpOp::Le => left <= right,
+            CmpOp::Gt => left > right,
+            CmpOp::Ge => left >= right,
+        }
+    }
+}
+
+impl fmt::Display for CmpOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            CmpOp::Eq => "=",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        };
+        f.write_str(op)
+    }
+}
+
+/// Split an expression into tokens
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let error = |message: String, start: usize, end: usize| ParseError {
+        message,
+        input: input.to_string(),
+        start,
+        end,
+    };
+
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let single = |kind| (kind, start + c.len_utf8());
+        let (kind, end) = match c {
+            '(' => single(TokenKind::LParen),
+            ')' => single(TokenKind::RParen),
+            ',' => single(TokenKind::Comma),
+            '~' => single(TokenKind::Glob),
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let with_eq = chars.peek().is_some_and(|&(_, next)| next == '=');
+                let op = match (c, with_eq) {
+                    ('=', _) => CmpOp::Eq,
+                    ('!', true) => CmpOp::Ne,
+                    ('<', false) => CmpOp::Lt,
+                    ('<', true) => CmpOp::Le,
+                    ('>', false) => CmpOp::Gt,
+                    ('>', true) => CmpOp::Ge,
+                    _ => {
+                        return Err(error(
+                            "unexpected '!', use 'not' or '!='".to_string(),
+                            start,
+                            start + 1,
+                        ));
+                    }
+                };
+                if with_eq {
+                    chars.next();
+                }
+                let end = chars.peek().map_or(input.len(), |&(i, _)| i);
+                tokens.push(Token {
+                    kind: TokenKind::Op(op),
+                    start,
+                    end,
+                });
+                continue;
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut value = String::new();
+                let mut end = None;
+                for (i, next) in chars.by_ref() {
+                    if next == c {
+                        end = Some(i + 1);
+                        break;
+                    }
+                    value.push(next);
+                }
+                let Some(end) = end else {
+                    return Err(error("unterminated string".to_string(), start, input.len()));
+                };
+                tokens.push(Token {
+                    kind: TokenKind::Str(value),
+                    start,
+                    end,
+                });
+                continue;
+            }
+            _ => {
+                let mut end = start;
+                while let Some(&(i, next)) = chars.peek() {
+                    if next.is_whitespace() || SPECIAL_CHARS.contains(next) {
+                        break;
+                    }
+                    end = i + next.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Word(input[start..end].to_string()),
+                    start,
+                    end,
+                });
+                continue;
+            }
+        };
+
+        chars.next();
+        tokens.push(Token { kind, start, end });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::End,
+        start: input.len(),
+        end: input.len(),
+    });
+    Ok(tokens)
+}
+
+/// Fields of an entry that text conditions can test
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TextField {
+    Name,
+    Path,
+    Ext,
+}
+
+/// How a text field is matched
+#[derive(Clone, Debug, PartialEq)]
+enum TextMatch {
+    /// Equal to any of the values
+    AnyOf(Vec<String>),
+    /// Matches the glob pattern
+    Glob(Pattern),
+}
+
+/// A point in time or an age that timestamps are compared with
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TimeValue {
+    /// That long before the expression was parsed, e.g. `30d`
+    Age(Duration),
+    /// A fixed point in time, e.g. `2024-01-01`
+    At(SystemTime),
+}
+
+/// A single condition on an entry
+#[derive(Clone, Debug, PartialEq)]
+enum Condition {
+    Size(CmpOp, u64),
+    Time(TimeField, CmpOp, TimeValue),
+    Text(TextField, TextMatch, bool),
+    IsDir(bool),
+    Owner(Vec<u32>, bool),
+    Group(Vec<u32>, bool),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Condition(Condition),
+}
+
+/// A parsed filter expression over entry metadata
+///
+/// Expressions combine conditions with `and`, `or`, `not` and parentheses:
+///
+/// * `size > 100MB` compares the size, with human units
+/// * `mtime < 2024-01-01` compares a timestamp with a date, and `mtime < 30d`,
+///   `atime > 1y`, `ctime >= 12h` with that long ago. Either way `<` means
+///   older: `mtime < 30d` holds for files last modified more than 30 days ago
+/// * `name`, `path` and `ext` compare text with `=`, `!=`, `in (a, b)` and glob
+///   patterns with `~`, e.g. `path ~ "*/keep/*"`; extensions ignore case
+/// * `type = dir` or `type = file`
+/// * `owner` and `group` compare with a name or id, e.g. `owner in (alice, 1001)`
+///
+/// For directories, sizes and timestamps cover everything below them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    root: Node,
+    /// The time ages are measured from
+    now: SystemTime,
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        if token.kind != TokenKind::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: String, token: &Token) -> ParseError {
+        ParseError {
+            message,
+            input: self.input.to_string(),
+            start: token.start,
+            end: token.end,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Node, ParseError> {
+        let mut node = self.parse_and()?;
+        while self.peek().is_keyword("or") {
+            self.next();
+            node = Node::Or(Box::new(node), Box::new(self.parse_and()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_and(&mut self) -> Result<Node, ParseError> {
+        let mut node = self.parse_unary()?;
+        while self.peek().is_keyword("and") {
+            self.next();
+            node = Node::And(Box::new(node), Box::new(self.parse_unary()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, ParseError> {
+        if self.peek().is_keyword("not") {
+            self.next();
+            return Ok(Node::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.peek().kind == TokenKind::LParen {
+            self.next();
+            let node = self.parse_or()?;
+            let token = self.next();
+            if token.kind != TokenKind::RParen {
+                return Err(self.error(format!("expected ')', found {}", token.describe()), &token));
+            }
+            return Ok(node);
+        }
+        self.parse_condition().map(Node::Condition)
+    }
+
+    /// A single value: a bare word or a quoted string
+    fn parse_value(&mut self) -> Result<(String, Token), ParseError> {
+        let token = self.next();
+        match &token.kind {
+            TokenKind::Word(value) | TokenKind::Str(value) => Ok((value.clone(), token)),
+            _ => Err(self.error(
+                format!("expected a value, found {}", token.describe()),
+                &token,
+            )),
+        }
+    }
+
+    /// A parenthesised, comma-separated list of values after `in`
+    fn parse_list(&mut self) -> Result<Vec<(String, Token)>, ParseError> {
+        let token = self.next();
+        if token.kind != TokenKind::LParen {
+            return Err(self.error(
+                format!("expected '(' after 'in', found {}", token.describe()),
+                &token,
+            ));
+        }
+        let mut values = vec![self.parse_value()?];
+        loop {
+            let token = self.next();
+            match token.kind {
+                TokenKind::Comma => values.push(self.parse_value()?),
+                TokenKind::RParen => return Ok(values),
+                _ => {
+                    return Err(self.error(
+                        format!("expected ',' or ')', found {}", token.describe()),
+                        &token,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn parse_condition(&mut self) -> Result<Condition, ParseError> {
+        let field_token = self.next();
+        let field = match &field_token.kind {
+            TokenKind::Word(word) => word.to_lowercase(),
+            _ => {
+                return Err(self.error(
+                    format!("expected a field name, found {}", field_token.describe()),
+                    &field_token,
+                ));
+            }
+        };
+
+        match field.as_str() {
+            "size" => {
+                let op = self.parse_op(&field)?;
+                let (value, token) = self.parse_value()?;
+                let size = utils::parse_size(&value).map_err(|e| self.error(e, &token))?;
+                Ok(Condition::Size(op, size))
+            }
+            "mtime" | "atime" | "ctime" => {
+                let time_field = match field.as_str() {
+                    "mtime" => TimeField::Modified,
+                    "atime" => TimeField::Accessed,
+                    _ => TimeField::Changed,
+                };
+                let op = self.parse_op(&field)?;
+                let (value, token) = self.parse_value()?;
+                let time = parse_time_value(&value).ok_or_else(|| {
+                    self.error(
+                        format!("invalid time '{value}', expected an age such as 30d or a date such as 2024-01-31"),
+                        &token,
+                    )
+                })?;
+                Ok(Condition::Time(time_field, op, time))
+            }
+            "name" | "path" | "ext" => {
+                let text_field = match field.as_str() {
+                    "name" => TextField::Name,
+                    "path" => TextField::Path,
+                    _ => TextField::Ext,
+                };
+                let normalize = |value: String| {
+                    if text_field == TextField::Ext {
+                        value.trim_start_matches('.').to_lowercase()
+                    } else {
+                        value
+                    }
+                };
+                let (matcher, negate) = self.parse_text_match(&field)?;
+                let matcher = match matcher {
+                    TextMatch::AnyOf(values) => {
+                        TextMatch::AnyOf(values.into_iter().map(normalize).collect())
+                    }
+                    glob => glob,
+                };
+                Ok(Condition::Text(text_field, matcher, negate))
+            }
+            "type" => {
+                let op_token = self.peek().clone();
+                let negate = match self.next().kind {
+                    TokenKind::Op(CmpOp::Eq) => false,
+                    TokenKind::Op(CmpOp::Ne) => true,
+                    _ => {
+                        return Err(self.error(
+                            format!(
+                                "expected '=' or '!=' after 'type', found {}",
+                                op_token.describe()
+                            ),
+                            &op_token,
+                        ));
+                    }
+                };
+                let (value, token) = self.parse_value()?;
+                let is_dir = match value.to_lowercase().as_str() {
+                    "dir" | "directory" | "d" => true,
+                    "file" | "f" => false,
+                    _ => {
+                        return Err(self.error(
+                            format!("unknown type '{value}', expected 'file' or 'dir'"),
+                            &token,
+                        ));
+                    }
+                };
+                Ok(Condition::IsDir(is_dir != negate))
+            }
+            "owner" | "user" | "group" => {
+                let is_group = field == "group";
+                let op_token = self.peek().clone();
+                let (values, negate) = match self.next().kind {
+                    TokenKind::Op(CmpOp::Eq) => (vec![self.parse_value()?], false),
+                    TokenKind::Op(CmpOp::Ne) => (vec![self.parse_value()?], true),
+                    TokenKind::Word(w) if w.eq_ignore_ascii_case("in") => {
+                        (self.parse_list()?, false)
+                    }
+                    _ => {
+                        return Err(self.error(
+                            format!(
+                                "expected '=', '!=' or 'in' after '{field}', found {}",
+                                op_token.describe()
+                            ),
+                            &op_token,
+                        ));
+                    }
+                };
+                let mut ids = Vec::new();
+                for (value, token) in values {
+                    let id = if is_group {
+                        owners::group_id(&value)
+                    } else {
+                        owners::user_id(&value)
+                    };
+                    let kind = if is_group { "group" } else { "user" };
+                    ids.push(id.ok_or_else(|| {
+                        self.error(format!("unknown {kind} '{value}'"), &token)
+                    })?);
+                }
+                if is_group {
+                    Ok(Condition::Group(ids, negate))
+                } else {
+                    Ok(Condition::Owner(ids, negate))
+                }
+            }
+            _ => Err(self.error(
+                format!(
+                    "unknown field '{field}', expected one of size, mtime, atime, ctime, name, path, ext, type, owner, group"
+                ),
+                &field_token,
+            )),
+        }
+    }
+
+    /// A comparison operator for a numeric field
+    fn parse_op(&mut self, field: &str) -> Result<CmpOp, ParseError> {
+        let token = self.next();
+        match token.kind {
+            TokenKind::Op(op) => Ok(op),
+            _ => Err(self.error(
+                format!(
+                    "expected a comparison such as '>' after '{field}', found {}",
+                    token.describe()
+                ),
+                &token,
+            )),
+        }
+    }
+
+    /// The operator and value(s) of a text condition, and whether it is negated
+    fn parse_text_match(&mut self, field: &str) -> Result<(TextMatch, bool), ParseError> {
+        let token = self.next();
+        match &token.kind {
+            TokenKind::Op(CmpOp::Eq) => Ok((TextMatch::AnyOf(vec![self.parse_value()?.0]), false)),
+            TokenKind::Op(CmpOp::Ne) => Ok((TextMatch::AnyOf(vec![self.parse_value()?.0]), true)),
+            TokenKind::Word(w) if w.eq_ignore_ascii_case("in") => {
+                let values = self.parse_list()?.into_iter().map(|(v, _)| v).collect();
+                Ok((TextMatch::AnyOf(values), false))
+            }
+            TokenKind::Glob => {
+                let (value, value_token) = self.parse_value()?;
+                let pattern = Pattern::new(&value).map_err(|e| {
+                    self.error(
+                        format!("invalid pattern '{value}': {}", e.msg),
+                        &value_token,
+                    )
+                })?;
+                Ok((TextMatch::Glob(pattern), false))
+            }
+            _ => Err(self.error(
+                format!(
+                    "expected '=', '!=', 'in' or '~' after '{field}', found {}",
+                    token.describe()
+                ),
+                &token,
+            )),
+        }
+    }
+}
+
+/// Parse an age such as `30d` or a date such as `2024-01-31`
+fn parse_time_value(value: &str) -> Option<TimeValue> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let midnight = Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .earliest()?;
+        return Some(TimeValue::At(midnight.into()));
+    }
+
+    let split = value.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let seconds = match unit.to_lowercase().as_str() {
+        "s" => 1.0,
+        "m" | "min" => 60.0,
+        "h" => 60.0 * 60.0,
+        "d" => 24.0 * 60.0 * 60.0,
+        "w" => 7.0 * 24.0 * 60.0 * 60.0,
+        "y" => 365.0 * 24.0 * 60.0 * 60.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(number * seconds)
+        .ok()
+        .map(TimeValue::Age)
+}
+
+impl Expression {
+    /// Parse a filter expression
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The expression, e.g. `size > 100MB and ext in (log, gz)`
+    ///
+    /// # Returns
+    ///
+    /// * The parsed expression, or an error pointing at the offending token.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fs_rs::query::Expression;
+    /// assert!(Expression::parse("size > 100MB and not path ~ \"*/keep/*\"").is_ok());
+    /// assert!(Expression::parse("size >").is_err());
+    /// ```
+    pub fn parse(input: &str) -> Result<Expression, ParseError> {
+        let mut parser = Parser {
+            input,
+            tokens: tokenize(input)?,
+            position: 0,
+        };
+        let root = parser.parse_or()?;
+        let token = parser.next();
+        if token.kind != TokenKind::End {
+            let message = format!(
+                "expected 'and', 'or' or end of expression, found {}",
+                token.describe()
+            );
+            return Err(parser.error(message, &token));
+        }
+        Ok(Expression {
+            root,
+            now: SystemTime::now(),
+        })
+    }
+
+    /// Whether an entry matches the expression
+    pub fn matches(&self, entry: &EntryInfo) -> bool {
+        self.eval(&self.root, entry)
+    }
+
+    fn eval(&self, node: &Node, entry: &EntryInfo) -> bool {
+        match node {
+            Node::And(a, b) => self.eval(a, entry) && self.eval(b, entry),
+            Node::Or(a, b) => self.eval(a, entry) || self.eval(b, entry),
+            Node::Not(a) => !self.eval(a, entry),
+            Node::Condition(condition) => self.eval_condition(condition, entry),
+        }
+    }
+
+    fn eval_condition(&self, condition: &Condition, entry: &EntryInfo) -> bool {
+        match condition {
+            Condition::Size(op, size) => op.compare(entry.size, *size),
+            Condition::Time(field, op, value) => {
+                let Some(time) = entry.times.get(*field) else {
+                    return false;
+                };
+                // Both forms compare timestamps, so `<` always means older
+                let at = match value {
+                    TimeValue::Age(age) => {
+                        self.now.checked_sub(*age).unwrap_or(SystemTime::UNIX_EPOCH)
+                    }
+                    TimeValue::At(at) => *at,
+                };
+                op.compare(time, at)
+            }
+            Condition::Text(field, matcher, negate) => {
+                let text = match field {
+                    TextField::Name => entry
+                        .path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string()),
+                    TextField::Path => Some(entry.path.to_string_lossy().to_string()),
+                    TextField::Ext => Some(
+                        entry
+                            .path
+                            .extension()
+                            .map(|e| e.to_string_lossy().to_lowercase())
+                            .unwrap_or_default(),
+                    ),
+                };
+                let Some(text) = text else {
+                    return *negate;
+                };
+                let matched = match matcher {
+                    TextMatch::AnyOf(values) => values.contains(&text),
+                    TextMatch::Glob(pattern) => pattern.matches_with(
+                        &text,
+                        MatchOptions {
+                            case_sensitive: *field != TextField::Ext,
+                            require_literal_separator: false,
+                            require_literal_leading_dot: false,
+                        },
+                    ),
+                };
+                matched != *negate
+            }
+            Condition::IsDir(is_dir) => entry.is_dir == *is_dir,
+            Condition::Owner(uids, negate) => match entry.owner {
+                Some((uid, _)) => uids.contains(&uid) != *negate,
+                None => false,
+            },
+            Condition::Group(gids, negate) => match entry.owner {
+                Some((_, gid)) => gids.contains(&gid) != *negate,
+                None => false,
+            },
+        }
+    }
+}
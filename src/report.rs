@@ -0,0 +1,107 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::progress;
+use crate::tree::{self, Snapshot, TreeNode};
+use crate::utils;
+
+const REPORT_HTML: &str = include_str!("report.html");
+
+/// Share of a root below which entries are folded into one entry per directory, to keep reports small
+const FOLD_SHARE: f64 = 1.0 / 20_000.0;
+
+/// Scan a path into the nested JSON used by HTML reports
+///
+/// Every entry has a `name` and a `size`, and directories also have
+/// `children`, largest first. Entries smaller than 1/20000th of the whole
+/// scan are folded into a single `(N smaller entries)` child with a `folded`
+/// count, so reports of large trees stay small enough to open in a browser.
+///
+/// # Arguments
+///
+/// * `path` - The path to scan. Its full path is the name of the root.
+/// * `depth` - An optional depth limit. Directories at the limit have no children.
+///
+/// # Returns
+///
+/// * The root of the tree as a JSON object.
+pub fn scan_tree(path: &Path, depth: Option<usize>) -> serde_json::Value {
+    let (entries, total_size, _) = tree::scan(path, depth);
+    progress::root_completed(path);
+    tree_to_json(path, &tree::build_tree(&entries), total_size)
+}
+
+/// Convert a snapshot into the nested JSON used by HTML reports, as `scan_tree` does for a path
+pub fn snapshot_tree(snapshot: &Snapshot, depth: Option<usize>) -> serde_json::Value {
+    tree_to_json(
+        &snapshot.root,
+        &snapshot.to_tree(depth),
+        snapshot.total_size,
+    )
+}
+
+/// Convert the tree of a root to JSON
+fn tree_to_json(path: &Path, root: &TreeNode, total_size: u64) -> serde_json::Value {
+    let min_size = (total_size as f64 * FOLD_SHARE) as u64;
+    serde_json::json!({
+        "name": path.display().to_string(),
+        "size": total_size,
+        "children": children_to_json(root, min_size),
+    })
+}
+
+/// Convert the children of a directory to JSON, folding those smaller than `min_size`
+fn children_to_json(node: &TreeNode, min_size: u64) -> Vec<serde_json::Value> {
+    let mut children: Vec<(&String, &TreeNode)> = node.children.iter().collect();
+    children.sort_by(|a, b| b.1.size.cmp(&a.1.size).then_with(|| a.0.cmp(b.0)));
+
+    let mut values = Vec::new();
+    let (mut folded, mut folded_size) = (0u64, 0u64);
+    for (name, child) in children {
+        if child.size == 0 || child.size < min_size {
+            folded += 1;
+            folded_size += child.size;
+        } else if child.is_dir {
+            values.push(serde_json::json!({
+                "name": name,
+                "size": child.size,
+                "children": children_to_json(child, min_size),
+            }));
+        } else {
+            values.push(serde_json::json!({ "name": name, "size": child.size }));
+        }
+    }
+    if folded > 0 {
+        values.push(serde_json::json!({
+            "name": format!("({folded} smaller entries)"),
+            "size": folded_size,
+            "folded": folded,
+        }));
+        // Keep the list largest first, as treemaps lay it out in order
+        values.sort_by_key(|value| std::cmp::Reverse(value["size"].as_u64()));
+    }
+    values
+}
+
+/// Render a self-contained HTML report with an interactive treemap and sunburst
+///
+/// The report needs no network access: the scan is embedded as JSON and
+/// drawn as SVG by an inline script, with zooming by click, tooltips and a
+/// search box that highlights matching paths.
+///
+/// # Arguments
+///
+/// * `roots` - The scanned roots, as returned by `scan_tree`.
+///
+/// # Returns
+///
+/// * The HTML document.
+pub fn render(roots: &[serde_json::Value]) -> String {
+    let data = serde_json::json!({
+        "generated": utils::format_time(Some(SystemTime::now())),
+        "roots": roots,
+    });
+    // A name containing `</script>` must not end the inline script early
+    let data = data.to_string().replace("</", "<\\/");
+    REPORT_HTML.replace("/*DATA*/", &data)
+}
@@ -0,0 +1,561 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use humansize::{DECIMAL, format_size};
+
+use crate::progress::{self, ScanContext};
+use crate::top::{self, LargeFile};
+use crate::tree;
+use crate::utils;
+
+/// The web UI, served at `/`
+const INDEX_HTML: &str = include_str!("serve.html");
+
+/// Longest request head accepted, in bytes
+const MAX_REQUEST_HEAD: u64 = 16 * 1024;
+
+/// How often the accept loop checks for cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a connection may stay idle before its thread gives up on it
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where the server listens
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Address {
+    /// A TCP address such as `127.0.0.1:7878`
+    Tcp(String),
+    /// A Unix domain socket path, written as `unix:/path/to/socket`
+    Unix(PathBuf),
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.strip_prefix("unix:") {
+            Some("") => Err("missing socket path after 'unix:'".to_string()),
+            Some(path) => Ok(Address::Unix(PathBuf::from(path))),
+            None if input.contains(':') => Ok(Address::Tcp(input.to_string())),
+            None => Err(format!(
+                "invalid address '{input}', expected HOST:PORT or unix:PATH"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Address::Tcp(address) => write!(f, "http://{address}"),
+            Address::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// A scanned file or directory
+///
+/// Nodes default to an empty directory, as parents are created before their own entry is seen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    pub size: u64,
+    pub is_dir: bool,
+    /// Paths of the children of a directory, largest first
+    pub children: Vec<PathBuf>,
+}
+
+/// Everything found by one scan of the roots, looked up by absolute path
+#[derive(Clone, Debug, Default)]
+pub struct Index {
+    pub roots: Vec<PathBuf>,
+    pub nodes: HashMap<PathBuf, Node>,
+    /// Every file, largest first
+    pub files: Vec<LargeFile>,
+    pub scanned_at: Option<SystemTime>,
+    pub duration: Duration,
+    /// Files and directories that could not be read during the scan
+    pub errors: u64,
+}
+
+impl Index {
+    /// Scan the roots and index every file and directory under them
+    ///
+    /// # Arguments
+    ///
+    /// * `roots` - The files or directories to scan, ideally absolute.
+    ///
+    /// # Returns
+    ///
+    /// * The index, with children sorted largest first.
+    pub fn scan(roots: &[PathBuf]) -> Index {
+        let started = Instant::now();
+        let mut index = Index {
+            roots: roots.to_vec(),
+            ..Default::default()
+        };
+
+        // Count the errors of this scan apart from those of earlier scans
+        let context = Arc::new(ScanContext::current().child());
+        context.run(|| {
+            for root in roots {
+                if root.is_dir() {
+                    let (entries, total_size, _) = tree::scan(root, None);
+                    for entry in entries {
+                        let path = root.join(&entry.path);
+                        if !entry.is_dir {
+                            index.files.push(LargeFile {
+                                size: entry.size,
+                                path: path.clone(),
+                                modified: entry.times.modified,
+                            });
+                        }
+                        index.insert(path, entry.size, entry.is_dir);
+                    }
+                    index.nodes.entry(root.clone()).or_default().size = total_size;
+                } else if let Ok(metadata) = root.metadata() {
+                    index.files.push(LargeFile {
+                        size: metadata.len(),
+                        path: root.clone(),
+                        modified: metadata.modified().ok(),
+                    });
+                    index.nodes.insert(
+                        root.clone(),
+                        Node {
+                            size: metadata.len(),
+                            is_dir: false,
+                            children: Vec::new(),
+                        },
+                    );
+                }
+                progress::root_completed(root);
+            }
+        });
+
+        let sizes: HashMap<PathBuf, u64> = index
+            .nodes
+            .iter()
+            .map(|(path, node)| (path.clone(), node.size))
+            .collect();
+        for node in index.nodes.values_mut() {
+            node.children
+                .sort_by(|a, b| sizes[b].cmp(&sizes[a]).then_with(|| a.cmp(b)));
+        }
+        index.files.sort_by(|a, b| b.cmp(a));
+        index.scanned_at = Some(SystemTime::now());
+        index.duration = started.elapsed();
+        index.errors = context.snapshot().errors;
+        index
+    }
+
+    /// Add a node and link it to its parent
+    fn insert(&mut self, path: PathBuf, size: u64, is_dir: bool) {
+        if let Some(parent) = path.parent() {
+            self.nodes
+                .entry(parent.to_path_buf())
+                .or_default()
+                .children
+                .push(path.clone());
+        }
+        let node = self.nodes.entry(path).or_default();
+        node.size = size;
+        node.is_dir = is_dir;
+    }
+
+    /// Total size of the roots
+    pub fn total_size(&self) -> u64 {
+        self.roots
+            .iter()
+            .filter_map(|root| self.nodes.get(root))
+            .map(|node| node.size)
+            .sum()
+    }
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Node {
+            size: 0,
+            is_dir: true,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// The index being served, replaced by every rescan
+pub struct Server {
+    index: RwLock<Arc<Index>>,
+    scanning: AtomicBool,
+    scans: AtomicU64,
+    rescan: Option<Duration>,
+}
+
+impl Server {
+    /// Scan the roots once and prepare to serve them
+    pub fn new(roots: Vec<PathBuf>, rescan: Option<Duration>) -> Server {
+        let server = Server {
+            index: RwLock::new(Arc::new(Index {
+                roots,
+                ..Default::default()
+            })),
+            scanning: AtomicBool::new(false),
+            scans: AtomicU64::new(0),
+            rescan,
+        };
+        server.rescan();
+        server
+    }
+
+    /// The most recent complete index
+    pub fn index(&self) -> Arc<Index> {
+        match self.index.read() {
+            Ok(index) => Arc::clone(&index),
+            Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+        }
+    }
+
+    /// Scan the roots again and swap in the new index once it is complete
+    pub fn rescan(&self) {
+        self.scanning.store(true, Ordering::Relaxed);
+        let index = Index::scan(&self.index().roots);
+        if let Ok(mut current) = self.index.write() {
+            *current = Arc::new(index);
+        }
+        self.scans.fetch_add(1, Ordering::Relaxed);
+        self.scanning.store(false, Ordering::Relaxed);
+    }
+
+    /// Answer a request for `target`, e.g. `/api/children?path=/var`
+    pub fn respond(&self, target: &str) -> Response {
+        let (route, query) = target.split_once('?').unwrap_or((target, ""));
+        let params = parse_query(query);
+        let index = self.index();
+        let path = |index: &Index| -> Result<PathBuf, Response> {
+            let path = match params.get("path") {
+                Some(path) => PathBuf::from(path),
+                None if index.roots.len() == 1 => index.roots[0].clone(),
+                None => return Err(Response::error(400, "missing 'path' parameter")),
+            };
+            if index.nodes.contains_key(&path) {
+                Ok(path)
+            } else {
+                Err(Response::error(404, "path is not part of the scan"))
+            }
+        };
+
+        match route {
+            "/" | "/index.html" => Response {
+                status: 200,
+                content_type: "text/html; charset=utf-8",
+                body: INDEX_HTML.to_string(),
+            },
+            "/api/status" => Response::json(&self.status_json(&index)),
+            "/api/children" => match path(&index) {
+                Ok(path) => Response::json(&children_json(&index, &path)),
+                Err(response) => response,
+            },
+            "/api/size" => match path(&index) {
+                Ok(path) => Response::json(&node_json(&index, &path)),
+                Err(response) => response,
+            },
+            "/api/top" => {
+                let count = match params.get("n").map(|n| n.parse::<usize>()) {
+                    None => 10,
+                    Some(Ok(count)) => count,
+                    Some(Err(_)) => return Response::error(400, "'n' must be a number"),
+                };
+                let files = &index.files[..count.min(index.files.len())];
+                Response::json(&serde_json::Value::from(top::to_json(files)))
+            }
+            _ => Response::error(404, "not found"),
+        }
+    }
+
+    /// The scan status as JSON
+    fn status_json(&self, index: &Index) -> serde_json::Value {
+        serde_json::json!({
+            "roots": index.roots.iter().map(|r| r.display().to_string()).collect::<Vec<_>>(),
+            "scanning": self.scanning.load(Ordering::Relaxed),
+            "scans": self.scans.load(Ordering::Relaxed),
+            "scanned_at": utils::format_rfc3339(index.scanned_at),
+            "duration_ms": index.duration.as_millis() as u64,
+            "rescan_seconds": self.rescan.map(|r| r.as_secs()),
+            "files": index.files.len(),
+            "size_bytes": index.total_size(),
+            "size_human": format_size(index.total_size(), DECIMAL),
+            "errors": index.errors,
+        })
+    }
+}
+
+/// A file or directory as JSON, without its children
+fn node_json(index: &Index, path: &Path) -> serde_json::Value {
+    let node = &index.nodes[path];
+    serde_json::json!({
+        "path": path.display().to_string(),
+        "name": path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned()),
+        "size_bytes": node.size,
+        "size_human": format_size(node.size, DECIMAL),
+        "is_dir": node.is_dir,
+        "children": node.children.len(),
+    })
+}
+
+/// A directory and its children as JSON, largest first
+fn children_json(index: &Index, path: &Path) -> serde_json::Value {
+    let mut value = node_json(index, path);
+    value["parent"] = if index.roots.iter().any(|root| root == path) {
+        serde_json::Value::Null
+    } else {
+        path.parent().map(|p| p.display().to_string()).into()
+    };
+    value["entries"] = index.nodes[path]
+        .children
+        .iter()
+        .map(|child| node_json(index, child))
+        .collect();
+    value
+}
+
+/// Decode a `%XX`-escaped query component, with `+` as a space
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Parse the query string of a request
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+/// An HTTP response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    fn json(value: &serde_json::Value) -> Response {
+        Response {
+            status: 200,
+            content_type: "application/json",
+            body: value.to_string(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            body: serde_json::json!({ "error": message }).to_string(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+
+    /// Write the response with its headers, leaving out the body for HEAD requests
+    fn write_to(&self, out: &mut impl Write, head_only: bool) -> io::Result<()> {
+        write!(
+            out,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.reason(),
+            self.content_type,
+            self.body.len()
+        )?;
+        if !head_only {
+            out.write_all(self.body.as_bytes())?;
+        }
+        out.flush()
+    }
+}
+
+/// Read one request from a connection and answer it
+fn handle(stream: impl Read + Write, server: &Server) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    (&mut reader)
+        .take(MAX_REQUEST_HEAD)
+        .read_line(&mut request_line)?;
+    // Skip the headers, nothing in them changes the response
+    let mut header = String::new();
+    while (&mut reader)
+        .take(MAX_REQUEST_HEAD)
+        .read_line(&mut header)?
+        > 2
+    {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or("/");
+    let response = match method {
+        "GET" | "HEAD" => server.respond(target),
+        _ => Response::error(405, "only GET and HEAD are supported"),
+    };
+    response.write_to(reader.get_mut(), method == "HEAD")
+}
+
+/// A bound TCP or Unix socket
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+impl Listener {
+    fn bind(address: &Address) -> io::Result<Listener> {
+        let listener = match address {
+            Address::Tcp(address) => Listener::Tcp(TcpListener::bind(address)?),
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                use std::os::unix::fs::FileTypeExt;
+                // Replace a socket left behind by a previous run, but never any other file
+                if let Ok(metadata) = std::fs::symlink_metadata(path)
+                    && metadata.file_type().is_socket()
+                {
+                    std::fs::remove_file(path)?;
+                }
+                Listener::Unix(std::os::unix::net::UnixListener::bind(path)?)
+            }
+            #[cfg(not(unix))]
+            Address::Unix(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Unix sockets are only supported on Unix",
+                ));
+            }
+        };
+        match &listener {
+            Listener::Tcp(listener) => listener.set_nonblocking(true)?,
+            #[cfg(unix)]
+            Listener::Unix(listener) => listener.set_nonblocking(true)?,
+        }
+        Ok(listener)
+    }
+
+    /// Accept a connection and answer it on its own thread
+    fn accept(&self, server: &Arc<Server>) -> io::Result<()> {
+        let server = Arc::clone(server);
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(READ_TIMEOUT))?;
+                thread::spawn(move || handle(stream, &server));
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(READ_TIMEOUT))?;
+                thread::spawn(move || handle(stream, &server));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Scan the roots and serve them until the process is cancelled
+///
+/// The address is bound before the first scan, so a bad address is reported
+/// without waiting for the scan.
+///
+/// # Arguments
+///
+/// * `roots` - The paths to scan and serve.
+/// * `rescan` - How often to scan the roots again, if at all.
+/// * `address` - Where to listen.
+/// * `on_ready` - Called once the first scan is done, e.g. to print the address.
+///
+/// # Returns
+///
+/// * An error if the address cannot be bound, otherwise `Ok` after cancellation.
+pub fn serve(
+    roots: Vec<PathBuf>,
+    rescan: Option<Duration>,
+    address: &Address,
+    on_ready: impl FnOnce(),
+) -> io::Result<()> {
+    let listener = Listener::bind(address)?;
+    let server = Arc::new(Server::new(roots, rescan));
+    on_ready();
+
+    let context = ScanContext::current();
+    if let Some(interval) = server.rescan {
+        let (server, context) = (Arc::clone(&server), Arc::clone(&context));
+        thread::spawn(move || {
+            context.run(|| {
+                loop {
+                    thread::sleep(interval);
+                    if context.is_cancelled() {
+                        break;
+                    }
+                    server.rescan();
+                }
+            })
+        });
+    }
+
+    while !context.is_cancelled() {
+        match listener.accept(&server) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                progress::warn(&format!("Failed to accept a connection: {e}"));
+                // Errors such as running out of file descriptors persist for a while
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+
+    if let Address::Unix(path) = address {
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use colored::Colorize;
+use comfy_table::{Cell, Table};
+use humansize::{DECIMAL, format_size};
+
+use crate::progress;
+use crate::utils;
+
+/// The most files `--top` will list
+pub const MAX_COUNT: usize = 100_000;
+
+/// A single file found by the top-N scan
+///
+/// Ordering is by size first so a heap of these keeps the largest files.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LargeFile {
+    pub size: u64,
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+}
+
+/// Bounded min-heap that only ever holds the `count` largest files
+struct TopHeap {
+    count: usize,
+    heap: BinaryHeap<Reverse<LargeFile>>,
+}
+
+impl TopHeap {
+    fn new(count: usize) -> Self {
+        TopHeap {
+            count,
+            // Grow on demand rather than reserving room for a huge `count` up front
+            heap: BinaryHeap::with_capacity(count.min(1024)),
+        }
+    }
+
+    fn push(&mut self, file: LargeFile) {
+        if self.count == 0 {
+            return;
+        }
+        if self.heap.len() == self.count {
+            match self.heap.peek() {
+                Some(Reverse(smallest)) if file <= *smallest => return,
+                _ => {}
+            }
+        }
+        self.heap.push(Reverse(file));
+        if self.heap.len() > self.count {
+            self.heap.pop();
+        }
+    }
+
+    fn merge(mut self, mut other: TopHeap) -> Self {
+        if self.heap.len() < other.heap.len() {
+            std::mem::swap(&mut self, &mut other);
+        }
+        for Reverse(file) in other.heap {
+            self.push(file);
+        }
+        self
+    }
+
+    fn into_sorted_vec(self) -> Vec<LargeFile> {
+        // Sorting `Reverse` ascending yields the files largest first
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|r| r.0)
+            .collect()
+    }
+}
+
+/// Find the largest files anywhere under the given paths
+///
+/// Only `count` files are held in memory at a time, regardless of how many
+/// files the paths contain. Paths that are files are considered themselves.
+///
+/// # Arguments
+///
+/// * `paths` - The files or directories to scan.
+/// * `count` - How many files to return.
+///
+/// # Returns
+///
+/// * The largest files, largest first.
+pub fn largest_files(paths: &[&Path], count: usize) -> Vec<LargeFile> {
+    let mut top = TopHeap::new(count);
+
+    for path in paths {
+        if path.is_dir() {
+            let found = utils::fold_files(
+                path,
+                &|| TopHeap::new(count),
+                &|mut heap, file_path, metadata| {
+                    heap.push(LargeFile {
+                        size: metadata.len(),
+                        path: file_path.to_path_buf(),
+                        modified: metadata.modified().ok(),
+                    });
+                    heap
+                },
+                &TopHeap::merge,
+            );
+            top = top.merge(found);
+        } else if let Ok(metadata) = path.metadata() {
+            top.push(LargeFile {
+                size: metadata.len(),
+                path: path.to_path_buf(),
+                modified: metadata.modified().ok(),
+            });
+        }
+        progress::root_completed(path);
+    }
+
+    top.into_sorted_vec()
+}
+
+/// Add rows to a table from a slice of LargeFile
+pub fn add_rows(table: &mut Table, files: &[LargeFile]) {
+    for file in files {
+        table.add_row(vec![
+            Cell::new(file.path.display().to_string().green()),
+            Cell::new(format_size(file.size, DECIMAL).green()),
+            Cell::new(utils::format_time(file.modified)),
+        ]);
+    }
+}
+
+/// Convert a slice of LargeFile to JSON values
+pub fn to_json(files: &[LargeFile]) -> Vec<serde_json::Value> {
+    files
+        .iter()
+        .map(|file| {
+            serde_json::json!({
+                "path": file.path.display().to_string(),
+                "size_bytes": file.size,
+                "size_human": format_size(file.size, DECIMAL),
+                "modified": utils::format_rfc3339(file.modified),
+            })
+        })
+        .collect()
+}
@@ -1,112 +1,413 @@
 use std::collections::BTreeMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use colored::Colorize;
 use humansize::{DECIMAL, format_size};
 use rayon::prelude::*;
 
+use crate::age::{self, FileTimes, TimeField};
+use crate::error::{self, Error};
+use crate::filetypes;
+use crate::filter::{EntryInfo, Filter};
+use crate::owners;
+use crate::progress::{self, ScanContext};
 use crate::utils;
 
-/// A flat entry: (relative_path, size_in_bytes, is_directory)
-type Entry = (String, u64, bool);
+/// A flat entry collected by the scanner
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Entry {
+    /// Path relative to the scanned root
+    pub(crate) path: String,
+    pub(crate) size: u64,
+    pub(crate) is_dir: bool,
+    /// Timestamps of a file, or the newest timestamps of any file below a directory
+    pub(crate) times: FileTimes,
+    /// `(uid, gid)` owning the entry, if known
+    pub(crate) owner: Option<(u32, u32)>,
+}
 
 /// A node in the tree structure
 #[derive(Debug, Default)]
-struct TreeNode {
-    size: u64,
-    is_dir: bool,
-    children: BTreeMap<String, TreeNode>,
+pub(crate) struct TreeNode {
+    pub(crate) size: u64,
+    pub(crate) is_dir: bool,
+    pub(crate) times: FileTimes,
+    pub(crate) owner: Option<(u32, u32)>,
+    pub(crate) children: BTreeMap<String, TreeNode>,
+    /// Totals per file type, largest first. Only filled in for type breakdowns.
+    types: Vec<(String, utils::Totals)>,
+    /// Newest timestamp of any file below a directory. Only filled in when requested.
+    newest: Option<SystemTime>,
+    /// Totals per user and per group, largest first. Only filled in for owner breakdowns.
+    ownership: owners::OwnerReport,
 }
 
+type Scanned = (Vec<Entry>, u64, FileTimes);
+
 /// Recursively collect all files and directories with their sizes.
 ///
-/// Returns `(entries, total_size)` where entries is a flat list of
-/// `(relative_path, size, is_dir)` tuples and total_size is the sum of
-/// all content under `path`. This avoids double-traversal by computing
-/// directory sizes from the recursive results rather than calling
-/// `calculate_dir_size` separately.
+/// Returns `(entries, total_size, newest)` where entries is a flat list of
+/// every file and directory, total_size is the sum of all content under
+/// `path` and newest holds the newest timestamps of any file below it. This
+/// avoids double-traversal by computing directory sizes from the recursive
+/// results rather than calling `calculate_dir_size` separately.
+///
+/// `ancestors` holds the inodes of the directories above `path`, to detect
+/// cycles. When `strict` is set the first unreadable entry or cycle is
+/// returned as an error; otherwise it is skipped.
 fn collect_entries(
     path: &Path,
     base_path: &Path,
     depth: usize,
     max_depth: usize,
-) -> (Vec<Entry>, u64) {
+    ancestors: &[(u64, u64)],
+    strict: bool,
+) -> error::Result<Scanned> {
+    let skip = |err: Error| {
+        if strict {
+            Err(err)
+        } else {
+            utils::note_unreadable(err);
+            Ok((vec![], 0, FileTimes::default()))
+        }
+    };
+
     if depth > max_depth {
-        return (vec![], 0);
+        return Ok((vec![], 0, FileTimes::default()));
+    }
+    if progress::is_cancelled() {
+        return if strict {
+            Err(Error::Cancelled)
+        } else {
+            Ok((vec![], 0, FileTimes::default()))
+        };
     }
 
     let read_dir = match std::fs::read_dir(path) {
         Ok(rd) => rd,
-        Err(_) => return (vec![], 0),
+        Err(e) => return skip(Error::from_io(path, e)),
     };
+    progress::entered_dir(path);
 
     let dir_entries: Vec<_> = read_dir.filter_map(Result::ok).collect();
 
-    let results: Vec<(Vec<Entry>, u64)> = dir_entries
+    let context = ScanContext::current();
+    let results: Vec<Scanned> = dir_entries
         .par_iter()
         .map(|entry| {
-            let file_type = match entry.file_type() {
-                Ok(ft) => ft,
-                Err(_) => return (vec![], 0),
-            };
+            context.run(|| {
+                let entry_path = entry.path();
+                let file_type = match entry.file_type() {
+                    Ok(ft) => ft,
+                    Err(e) => return skip(Error::from_io(&entry_path, e)),
+                };
 
-            if file_type.is_symlink() {
-                return (vec![], 0);
-            }
+                if file_type.is_symlink() {
+                    return Ok((vec![], 0, FileTimes::default()));
+                }
+
+                let relative_path = entry_path
+                    .strip_prefix(base_path)
+                    .unwrap_or(&entry_path)
+                    .to_string_lossy()
+                    .to_string();
+                let metadata = match entry.metadata() {
+                    Ok(metadata) => metadata,
+                    Err(e) => return skip(Error::from_io(&entry_path, e)),
+                };
 
-            let entry_path = entry.path();
-            let relative_path = entry_path
-                .strip_prefix(base_path)
-                .unwrap_or(&entry_path)
-                .to_string_lossy()
-                .to_string();
-
-            if file_type.is_file() {
-                let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
-                (vec![(relative_path, size, false)], size)
-            } else if file_type.is_dir() {
-                if depth < max_depth {
-                    // Recurse: collect children and derive size from them
-                    let (sub_entries, dir_size) =
-                        collect_entries(&entry_path, base_path, depth + 1, max_depth);
-                    let mut entries = vec![(relative_path, dir_size, true)];
+                if file_type.is_file() {
+                    let size = metadata.len();
+                    progress::file_visited(size);
+                    let times = FileTimes::from_metadata(&metadata);
+                    let file = Entry {
+                        path: relative_path,
+                        size,
+                        is_dir: false,
+                        times,
+                        owner: owners::owner_of(&metadata),
+                    };
+                    Ok((vec![file], size, times))
+                } else if file_type.is_dir() {
+                    let mut below = ancestors.to_vec();
+                    if let Some(id) = utils::inode(&metadata) {
+                        if ancestors.contains(&id) {
+                            return skip(Error::CycleDetected(entry_path));
+                        }
+                        below.push(id);
+                    }
+                    let (sub_entries, dir_size, times) = if depth < max_depth {
+                        // Recurse: collect children and derive size from them
+                        collect_entries(
+                            &entry_path,
+                            base_path,
+                            depth + 1,
+                            max_depth,
+                            &below,
+                            strict,
+                        )?
+                    } else if strict {
+                        // At max depth: compute size without expanding children
+                        let (dir_size, times) = utils::try_fold_files(
+                            &entry_path,
+                            &|| (0, FileTimes::default()),
+                            &|(size, times), _, metadata| {
+                                (
+                                    size + metadata.len(),
+                                    times.newest(FileTimes::from_metadata(metadata)),
+                                )
+                            },
+                            &|a, b| (a.0 + b.0, a.1.newest(b.1)),
+                        )?;
+                        (vec![], dir_size, times)
+                    } else {
+                        let (dir_size, times) = age::dir_size_and_times(&entry_path);
+                        (vec![], dir_size, times)
+                    };
+                    let dir = Entry {
+                        path: relative_path,
+                        size: dir_size,
+                        is_dir: true,
+                        times,
+                        owner: owners::owner_of(&metadata),
+                    };
+                    let mut entries = vec![dir];
                     entries.extend(sub_entries);
-                    (entries, dir_size)
+                    Ok((entries, dir_size, times))
                 } else {
-                    // At max depth: compute size without expanding children
-                    let dir_size = utils::calculate_dir_size(&entry_path);
-                    (vec![(relative_path, dir_size, true)], dir_size)
+                    Ok((vec![], 0, FileTimes::default()))
                 }
-            } else {
-                (vec![], 0)
-            }
+            })
         })
-        .collect();
+        .collect::<error::Result<_>>()?;
 
     let mut all_entries = Vec::new();
     let mut total_size = 0u64;
-    for (entries, size) in results {
+    let mut newest = FileTimes::default();
+    for (entries, size, times) in results {
         all_entries.extend(entries);
         total_size += size;
+        newest = newest.newest(times);
+    }
+
+    Ok((all_entries, total_size, newest))
+}
+
+/// Scan a directory down to an optional depth, skipping what cannot be read.
+///
+/// Returns `(entries, total_size, newest)` as described for `collect_entries`.
+pub(crate) fn scan(path: &Path, depth: Option<usize>) -> Scanned {
+    let ancestors = root_inode(path);
+    collect_entries(
+        path,
+        path,
+        1,
+        depth.unwrap_or(usize::MAX),
+        &ancestors,
+        false,
+    )
+    .unwrap_or_default()
+}
+
+/// Scan a directory down to an optional depth, failing on the first unreadable entry.
+pub(crate) fn try_scan(path: &Path, depth: Option<usize>) -> error::Result<Scanned> {
+    let metadata = std::fs::metadata(path).map_err(|e| Error::from_io(path, e))?;
+    let ancestors: Vec<_> = utils::inode(&metadata).into_iter().collect();
+    collect_entries(path, path, 1, depth.unwrap_or(usize::MAX), &ancestors, true)
+}
+
+/// The inode of a scanned root, as the first ancestor for cycle detection
+fn root_inode(path: &Path) -> Vec<(u64, u64)> {
+    std::fs::metadata(path)
+        .ok()
+        .and_then(|m| utils::inode(&m))
+        .into_iter()
+        .collect()
+}
+
+/// A scan held in memory, e.g. one loaded from a dump made on another machine
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    /// The path that was scanned, as recorded where it was scanned
+    pub root: PathBuf,
+    /// Size of everything under the root
+    pub total_size: u64,
+    /// Directories that could not be read when the dump was made
+    pub errors: u64,
+    pub(crate) entries: Vec<Entry>,
+}
+
+impl Snapshot {
+    /// Scan a directory into a snapshot
+    pub fn scan(path: &Path, depth: Option<usize>) -> Self {
+        let (entries, total_size, _) = scan(path, depth);
+        Snapshot {
+            root: path.to_path_buf(),
+            total_size,
+            errors: 0,
+            entries,
+        }
+    }
+
+    /// Build a snapshot from the files and directories listed in a dump
+    ///
+    /// Directory sizes and timestamps are computed from the files below
+    /// them, as a scan would, so whatever the dump says about directories
+    /// themselves is ignored. Directories missing from the dump are added for
+    /// the files they hold, and the last of several entries for a path wins.
+    pub(crate) fn from_dump(root: PathBuf, dump: Vec<Entry>) -> Self {
+        let mut files = BTreeMap::new();
+        let mut dirs = BTreeMap::new();
+        for entry in dump.into_iter().filter(|entry| !entry.path.is_empty()) {
+            if entry.is_dir {
+                dirs.insert(entry.path.clone(), entry);
+            } else {
+                files.insert(entry.path.clone(), entry);
+            }
+        }
+
+        // Every ancestor of a directory or file is a directory
+        let paths: Vec<String> = dirs.keys().chain(files.keys()).cloned().collect();
+        for path in paths {
+            for (index, _) in path.match_indices(std::path::MAIN_SEPARATOR) {
+                let parent = &path[..index];
+                if !parent.is_empty() && !dirs.contains_key(parent) {
+                    let dir = Entry {
+                        path: parent.to_string(),
+                        is_dir: true,
+                        ..Entry::default()
+                    };
+                    dirs.insert(parent.to_string(), dir);
+                }
+            }
+        }
+
+        for dir in dirs.values_mut() {
+            dir.size = 0;
+            dir.times = FileTimes::default();
+        }
+        let mut total_size = 0;
+        for file in files.values() {
+            total_size += file.size;
+            for (index, _) in file.path.match_indices(std::path::MAIN_SEPARATOR) {
+                if let Some(dir) = dirs.get_mut(&file.path[..index]) {
+                    dir.size += file.size;
+                    dir.times = dir.times.newest(file.times);
+                }
+            }
+        }
+
+        Snapshot {
+            root,
+            total_size,
+            errors: 0,
+            entries: dirs.into_values().chain(files.into_values()).collect(),
+        }
+    }
+
+    /// Number of files and directories in the snapshot
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the snapshot holds no files or directories
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Build the tree of the snapshot, down to an optional depth
+    pub(crate) fn to_tree(&self, depth: Option<usize>) -> TreeNode {
+        let mut tree = build_tree(&self.entries);
+        if let Some(depth) = depth {
+            prune(&mut tree, depth);
+        }
+        tree
+    }
+
+    /// Generates a tree representation of the snapshot, as `generate_annotated_tree` does for a path.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - An optional depth limit for the tree representation.
+    /// * `ascii` - Whether to use ASCII characters instead of Unicode.
+    /// * `filter` - Which entries to show. Directories containing a shown entry are always kept.
+    ///
+    /// # Returns
+    ///
+    /// * A String representing the tree structure.
+    pub fn render_tree(&self, depth: Option<usize>, ascii: bool, filter: &Filter) -> String {
+        let mut tree = self.to_tree(depth);
+        if !filter.is_empty() {
+            retain_matching(&mut tree, &self.root, filter);
+        }
+        render_tree(&tree, "", ascii)
+    }
+
+    /// Lists the children of the root that match a filter.
+    ///
+    /// # Returns
+    ///
+    /// * A tuple of the matching children and the number of children before filtering.
+    pub fn children(&self, filter: &Filter) -> (Vec<utils::Sizes>, usize) {
+        let children: Vec<&Entry> = self
+            .entries
+            .iter()
+            .filter(|entry| !entry.path.contains(std::path::MAIN_SEPARATOR))
+            .collect();
+        let count = children.len();
+        let matching = children
+            .into_iter()
+            .filter(|entry| filter.matches(&self.info(entry, &self.root.join(&entry.path))))
+            .map(|entry| utils::Sizes {
+                name: utils::truncate_filename(Path::new(&entry.path)),
+                size: entry.size,
+                is_dir: entry.is_dir,
+            })
+            .collect();
+        (matching, count)
+    }
+
+    /// Lists the directories of the snapshot down to `depth` levels that match a filter, like `flatten_matching_dirs`.
+    ///
+    /// # Returns
+    ///
+    /// * A tuple of the matching directories and the number of directories before filtering.
+    pub fn dirs(&self, depth: usize, filter: &Filter) -> (Vec<utils::Sizes>, usize) {
+        let dirs = self.entries.iter().filter(|entry| {
+            entry.is_dir && entry.path.matches(std::path::MAIN_SEPARATOR).count() < depth
+        });
+        matching_dirs(&self.root, dirs, filter)
     }
 
-    (all_entries, total_size)
+    /// Details of an entry for evaluating filters
+    fn info<'a>(&self, entry: &Entry, path: &'a Path) -> EntryInfo<'a> {
+        EntryInfo {
+            path,
+            size: entry.size,
+            is_dir: entry.is_dir,
+            times: entry.times,
+            owner: entry.owner,
+        }
+    }
 }
 
 /// Build a tree structure from flat paths
-fn build_tree(entries: &[Entry]) -> TreeNode {
+pub(crate) fn build_tree(entries: &[Entry]) -> TreeNode {
     let mut root = TreeNode::default();
 
-    for (path, size, is_dir) in entries {
-        let parts: Vec<&str> = path.split(std::path::MAIN_SEPARATOR).collect();
+    for entry in entries {
+        let parts: Vec<&str> = entry.path.split(std::path::MAIN_SEPARATOR).collect();
         let mut current = &mut root;
 
         for (i, part) in parts.iter().enumerate() {
             current = current.children.entry(part.to_string()).or_default();
 
             if i == parts.len() - 1 {
-                current.size = *size;
-                current.is_dir = *is_dir;
+                current.size = entry.size;
+                current.is_dir = entry.is_dir;
+                current.times = entry.times;
+                current.owner = entry.owner;
             }
         }
     }
@@ -114,6 +415,24 @@ fn build_tree(entries: &[Entry]) -> TreeNode {
     root
 }
 
+/// Extra details shown after a directory's size, if any were filled in
+fn annotations(node: &TreeNode) -> String {
+    let mut output = String::new();
+    if let Some(newest) = node.newest {
+        let newest = format!("newest {}", utils::format_time(Some(newest)));
+        output.push_str(&format!("  {}", newest.dimmed()));
+    }
+    if !node.ownership.users.is_empty() {
+        let owners = format!("[{}]", owners::summary(&node.ownership));
+        output.push_str(&format!("  {}", owners.dimmed()));
+    }
+    if !node.types.is_empty() {
+        let types = format!("[{}]", utils::summary(&node.types, 3));
+        output.push_str(&format!("  {}", types.dimmed()));
+    }
+    output
+}
+
 /// Render a tree node recursively
 fn render_tree(node: &TreeNode, prefix: &str, ascii: bool) -> String {
     let mut output = String::new();
@@ -138,11 +457,12 @@ fn render_tree(node: &TreeNode, prefix: &str, ascii: bool) -> String {
 
         let formatted_line = if child.is_dir {
             format!(
-                "{}{}{}/  ({})\n",
+                "{}{}{}/  ({}){}\n",
                 prefix,
                 branch,
                 name.blue(),
-                size_str.blue()
+                size_str.blue(),
+                annotations(child)
             )
         } else {
             format!(
@@ -170,6 +490,197 @@ fn render_tree(node: &TreeNode, prefix: &str, ascii: bool) -> String {
     output
 }
 
+/// Fill in the per-type totals of every directory below `node`.
+///
+/// `path` is the on-disk path of `node`, needed to sniff the content of
+/// files without an extension. Returns the totals for `node` itself.
+fn annotate_types(node: &mut TreeNode, path: &Path, categories: bool) -> filetypes::TypeMap {
+    let mut types = filetypes::TypeMap::new();
+
+    for (name, child) in node.children.iter_mut() {
+        let child_path = path.join(name);
+        if child.is_dir {
+            let child_types = annotate_types(child, &child_path, categories);
+            child.types = utils::sorted_totals(child_types.clone());
+            types = filetypes::merge_type_maps(types, child_types);
+        } else {
+            types
+                .entry(filetypes::type_key(&child_path, categories))
+                .or_default()
+                .add(child.size);
+        }
+    }
+
+    types
+}
+
+/// Fill in the newest timestamp of every directory below `node`
+fn annotate_times(node: &mut TreeNode, field: TimeField) {
+    for child in node.children.values_mut().filter(|c| c.is_dir) {
+        child.newest = child.times.get(field);
+        annotate_times(child, field);
+    }
+}
+
+/// Fill in the per-user and per-group totals of every directory below `node`.
+///
+/// Returns the totals by `(uid, gid)` for `node` itself.
+fn annotate_owners(
+    node: &mut TreeNode,
+    names: &mut owners::NameCache,
+) -> (owners::IdMap, owners::IdMap) {
+    let mut users = owners::IdMap::new();
+    let mut groups = owners::IdMap::new();
+
+    for child in node.children.values_mut() {
+        if child.is_dir {
+            let (child_users, child_groups) = annotate_owners(child, names);
+            child.ownership = owners::OwnerReport {
+                users: owners::named(&child_users, |id| names.user(id)),
+                groups: owners::named(&child_groups, |id| names.group(id)),
+            };
+            users = owners::merge_id_maps(users, child_users);
+            groups = owners::merge_id_maps(groups, child_groups);
+        } else if let Some((uid, gid)) = child.owner {
+            users.entry(uid).or_default().add(child.size);
+            groups.entry(gid).or_default().add(child.size);
+        }
+    }
+
+    (users, groups)
+}
+
+/// Remove the children of `node` that do not match `filter`, keeping the ancestors of matching entries.
+///
+/// `path` is the full path of `node`. Returns whether anything below `node` is still shown.
+fn retain_matching(node: &mut TreeNode, path: &Path, filter: &Filter) -> bool {
+    node.children.retain(|name, child| {
+        let child_path = path.join(name);
+        let has_matching_children = retain_matching(child, &child_path, filter);
+        has_matching_children
+            || filter.matches(&EntryInfo {
+                path: &child_path,
+                size: child.size,
+                is_dir: child.is_dir,
+                times: child.times,
+                owner: child.owner,
+            })
+    });
+    !node.children.is_empty()
+}
+
+/// Drop everything deeper than `depth` levels below `node`
+fn prune(node: &mut TreeNode, depth: usize) {
+    for child in node.children.values_mut() {
+        if depth <= 1 {
+            child.children.clear();
+        } else {
+            prune(child, depth - 1);
+        }
+    }
+}
+
+/// Details shown next to each directory in an annotated tree
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Annotations {
+    /// Show the largest file types below each directory
+    pub by_type: bool,
+    /// Group file types by category instead of by extension
+    pub categories: bool,
+    /// Show the newest timestamp of any file below each directory
+    pub newest: Option<TimeField>,
+    /// Show the largest users and groups below each directory
+    pub owners: bool,
+}
+
+/// The breakdowns of a directory, as shown next to it in an annotated tree
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DirBreakdown {
+    /// Path relative to the scanned root
+    pub name: String,
+    /// Size of everything under the directory
+    pub size: u64,
+    /// Totals per file type, largest first. Empty unless a breakdown by type was requested.
+    pub types: Vec<(String, utils::Totals)>,
+    /// Totals per user and per group, largest first. Empty unless a breakdown by owner was requested.
+    pub owners: owners::OwnerReport,
+}
+
+/// Lists the directories under a given path down to `depth` levels with their breakdowns.
+///
+/// The whole tree is scanned, so each breakdown covers everything under
+/// its directory, as in `generate_annotated_tree`.
+///
+/// # Arguments
+///
+/// * `path` - The path to list directories for.
+/// * `depth` - How many levels of directories to report.
+/// * `annotations` - Which breakdowns to fill in.
+/// * `filter` - Which directories to report.
+///
+/// # Returns
+///
+/// * A tuple of the matching directories and the total size of `path`.
+pub fn breakdown_dirs(
+    path: &Path,
+    depth: usize,
+    annotations: &Annotations,
+    filter: &Filter,
+) -> (Vec<DirBreakdown>, u64) {
+    let (entries, total_size, _) = scan(path, None);
+    let mut tree = build_tree(&entries);
+    if annotations.by_type {
+        annotate_types(&mut tree, path, annotations.categories);
+    }
+    if annotations.owners {
+        annotate_owners(&mut tree, &mut owners::NameCache::default());
+    }
+
+    let mut dirs = Vec::new();
+    collect_breakdowns(&tree, path, "", depth, filter, &mut dirs);
+    (dirs, total_size)
+}
+
+/// Add the directories below `node` down to `depth` levels that match a filter to `dirs`
+///
+/// `path` is the full path of `node` and `prefix` its path relative to the scanned root.
+fn collect_breakdowns(
+    node: &TreeNode,
+    path: &Path,
+    prefix: &str,
+    depth: usize,
+    filter: &Filter,
+    dirs: &mut Vec<DirBreakdown>,
+) {
+    if depth == 0 {
+        return;
+    }
+    for (name, child) in node.children.iter().filter(|(_, child)| child.is_dir) {
+        let child_path = path.join(name);
+        let relative = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{prefix}{}{name}", std::path::MAIN_SEPARATOR)
+        };
+        let info = EntryInfo {
+            path: &child_path,
+            size: child.size,
+            is_dir: true,
+            times: child.times,
+            owner: child.owner,
+        };
+        if filter.matches(&info) {
+            dirs.push(DirBreakdown {
+                name: relative.clone(),
+                size: child.size,
+                types: child.types.clone(),
+                owners: child.ownership.clone(),
+            });
+        }
+        collect_breakdowns(child, &child_path, &relative, depth - 1, filter, dirs);
+    }
+}
+
 /// Generates a tree representation of a given path.
 ///
 /// # Arguments
@@ -182,8 +693,165 @@ fn render_tree(node: &TreeNode, prefix: &str, ascii: bool) -> String {
 ///
 /// * A String representing the tree structure.
 pub fn generate_tree(path: &Path, depth: Option<usize>, ascii: bool) -> String {
-    let max_depth = depth.unwrap_or(usize::MAX);
-    let (entries, _) = collect_entries(path, path, 1, max_depth);
-    let tree = build_tree(&entries);
-    render_tree(&tree, "", ascii)
+    generate_annotated_tree(
+        path,
+        depth,
+        ascii,
+        &Annotations::default(),
+        &Filter::default(),
+    )
+}
+
+/// Generates a tree representation of a given path, failing on the first unreadable entry.
+///
+/// Unlike `generate_tree`, nothing is silently skipped, so the sizes shown
+/// are never undercounted.
+///
+/// # Arguments
+///
+/// * `path` - The path to generate tree for.
+/// * `depth` - An optional depth limit for the tree representation.
+/// * `ascii` - Whether to use ASCII characters instead of Unicode.
+///
+/// # Returns
+///
+/// * A String representing the tree structure, or why it could not be generated.
+pub fn try_generate_tree(path: &Path, depth: Option<usize>, ascii: bool) -> error::Result<String> {
+    let (entries, _, _) = try_scan(path, depth)?;
+    Ok(render_tree(&build_tree(&entries), "", ascii))
+}
+
+/// Generates a tree representation of a given path with extra details for each directory.
+///
+/// For a breakdown by type or owner the whole tree is scanned, so it covers
+/// everything under each directory even when `depth` limits how much of the
+/// tree is shown.
+///
+/// # Arguments
+///
+/// * `path` - The path to generate tree for.
+/// * `depth` - An optional depth limit for the tree representation.
+/// * `ascii` - Whether to use ASCII characters instead of Unicode.
+/// * `annotations` - Which details to show next to each directory.
+/// * `filter` - Which entries to show. Directories containing a shown entry are always kept.
+///
+/// # Returns
+///
+/// * A String representing the tree structure.
+pub fn generate_annotated_tree(
+    path: &Path,
+    depth: Option<usize>,
+    ascii: bool,
+    annotations: &Annotations,
+    filter: &Filter,
+) -> String {
+    generate_sized_tree(path, depth, ascii, annotations, filter).0
+}
+
+/// Generates an annotated tree representation of a given path together with its total size.
+///
+/// Takes the same arguments as `generate_annotated_tree`.
+///
+/// # Returns
+///
+/// * A tuple of the tree structure and the total size of everything under
+///   `path`, including entries hidden by the filter.
+pub fn generate_sized_tree(
+    path: &Path,
+    depth: Option<usize>,
+    ascii: bool,
+    annotations: &Annotations,
+    filter: &Filter,
+) -> (String, u64) {
+    let full_scan = annotations.by_type || annotations.owners;
+    let scan_depth = if full_scan { None } else { depth };
+    let (entries, total_size, _) = scan(path, scan_depth);
+    let mut tree = build_tree(&entries);
+
+    if annotations.by_type {
+        annotate_types(&mut tree, path, annotations.categories);
+    }
+    if annotations.owners {
+        annotate_owners(&mut tree, &mut owners::NameCache::default());
+    }
+    if let Some(field) = annotations.newest {
+        annotate_times(&mut tree, field);
+    }
+    if let (Some(depth), None) = (depth, scan_depth) {
+        prune(&mut tree, depth);
+    }
+    if !filter.is_empty() {
+        retain_matching(&mut tree, path, filter);
+    }
+
+    (render_tree(&tree, "", ascii), total_size)
+}
+
+/// Lists every directory under a given path down to `depth` levels, `du -d N` style.
+///
+/// Each directory is reported with its path relative to `path` and its
+/// aggregated size, so the result can be sorted to find the biggest
+/// directories anywhere in the tree.
+///
+/// # Arguments
+///
+/// * `path` - The path to list directories for.
+/// * `depth` - How many levels of directories to report.
+///
+/// # Returns
+///
+/// * A tuple of the directories found and the total size of `path`.
+pub fn flatten_dirs(path: &Path, depth: usize) -> (Vec<utils::Sizes>, u64) {
+    let (dirs, total_size, _) = flatten_matching_dirs(path, depth, &Filter::default());
+    (dirs, total_size)
+}
+
+/// Lists the directories under a given path down to `depth` levels that match a filter.
+///
+/// # Arguments
+///
+/// * `path` - The path to list directories for.
+/// * `depth` - How many levels of directories to report.
+/// * `filter` - Which directories to report.
+///
+/// # Returns
+///
+/// * A tuple of the matching directories, the total size of `path` and the
+///   number of directories found before filtering.
+pub fn flatten_matching_dirs(
+    path: &Path,
+    depth: usize,
+    filter: &Filter,
+) -> (Vec<utils::Sizes>, u64, usize) {
+    let (entries, total_size, _) = scan(path, Some(depth));
+    let (matching, count) =
+        matching_dirs(path, entries.iter().filter(|entry| entry.is_dir), filter);
+    (matching, total_size, count)
+}
+
+/// Keep the directories that match a filter, returning them with the number of directories before filtering
+fn matching_dirs<'a>(
+    path: &Path,
+    dirs: impl Iterator<Item = &'a Entry>,
+    filter: &Filter,
+) -> (Vec<utils::Sizes>, usize) {
+    let mut count = 0;
+    let matching = dirs
+        .inspect(|_| count += 1)
+        .filter(|entry| {
+            filter.matches(&EntryInfo {
+                path: &path.join(&entry.path),
+                size: entry.size,
+                is_dir: true,
+                times: entry.times,
+                owner: entry.owner,
+            })
+        })
+        .map(|entry| utils::Sizes {
+            name: entry.path.clone(),
+            size: entry.size,
+            is_dir: true,
+        })
+        .collect();
+    (matching, count)
 }
@@ -0,0 +1,487 @@
+use std::path::Path;
+
+use colored::{Color, Colorize};
+use humansize::{DECIMAL, format_size};
+
+use crate::tree::{self, Snapshot, TreeNode};
+
+/// Height of a terminal cell relative to its width, so rectangles look square on screen
+const CELL_ASPECT: f64 = 2.0;
+
+/// Background colors given to siblings in turn
+const PALETTE: [Color; 6] = [
+    Color::Blue,
+    Color::Green,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Red,
+    Color::Yellow,
+];
+
+/// Width and height used when the terminal size is unknown
+const DEFAULT_SIZE: (usize, usize) = (80, 24);
+
+/// Largest width or height of a treemap, which keeps the canvas a sensible size
+pub const MAX_SIDE: usize = 10_000;
+
+/// A rectangle in layout coordinates
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+
+/// Lay out values as rectangles with areas proportional to them, using the squarified algorithm
+///
+/// Values are placed largest first in rows along the shorter side of the
+/// remaining space, and a row is closed as soon as adding the next value
+/// would make its rectangles less square.
+///
+/// # Arguments
+///
+/// * `values` - The sizes to lay out.
+/// * `area` - The rectangle to fill.
+///
+/// # Returns
+///
+/// * A rectangle per value, in the order of `values`. Zero values get an empty rectangle.
+///
+/// # Examples
+///
+/// ```
+/// use fs_rs::treemap::{Rect, squarify};
+/// let rects = squarify(&[1, 3], Rect { x: 0.0, y: 0.0, w: 4.0, h: 1.0 });
+/// assert_eq!(rects[1], Rect { x: 0.0, y: 0.0, w: 3.0, h: 1.0 });
+/// assert_eq!(rects[0], Rect { x: 3.0, y: 0.0, w: 1.0, h: 1.0 });
+/// ```
+pub fn squarify(values: &[u64], area: Rect) -> Vec<Rect> {
+    let mut rects = vec![Rect::default(); values.len()];
+    let total: u64 = values.iter().sum();
+    if total == 0 || area.w <= 0.0 || area.h <= 0.0 {
+        return rects;
+    }
+
+    let mut order: Vec<usize> = (0..values.len()).filter(|&i| values[i] > 0).collect();
+    order.sort_by(|&a, &b| values[b].cmp(&values[a]));
+    let scale = area.w * area.h / total as f64;
+    let areas: Vec<f64> = order.iter().map(|&i| values[i] as f64 * scale).collect();
+
+    let mut free = area;
+    let mut start = 0;
+    while start < order.len() {
+        let side = free.w.min(free.h);
+        let mut end = start + 1;
+        let mut best = worst_ratio(&areas[start..end], side);
+        while end < order.len() {
+            let ratio = worst_ratio(&areas[start..=end], side);
+            if ratio > best {
+                break;
+            }
+            best = ratio;
+            end += 1;
+        }
+
+        // The row runs along the shorter side and is as thick as its area requires
+        let thickness = areas[start..end].iter().sum::<f64>() / side;
+        let mut offset = 0.0;
+        for (&index, &cell_area) in order[start..end].iter().zip(&areas[start..end]) {
+            let length = cell_area / thickness;
+            rects[index] = if free.w >= free.h {
+                Rect {
+                    x: free.x,
+                    y: free.y + offset,
+                    w: thickness,
+                    h: length,
+                }
+            } else {
+                Rect {
+                    x: free.x + offset,
+                    y: free.y,
+                    w: length,
+                    h: thickness,
+                }
+            };
+            offset += length;
+        }
+        if free.w >= free.h {
+            free.x += thickness;
+            free.w -= thickness;
+        } else {
+            free.y += thickness;
+            free.h -= thickness;
+        }
+        start = end;
+    }
+
+    rects
+}
+
+/// The largest aspect ratio of a row of areas laid out along `side`
+fn worst_ratio(row: &[f64], side: f64) -> f64 {
+    let sum: f64 = row.iter().sum();
+    let max = row.iter().copied().fold(f64::MIN, f64::max);
+    let min = row.iter().copied().fold(f64::MAX, f64::min);
+    let side = side * side;
+    f64::max(side * max / (sum * sum), sum * sum / (side * min))
+}
+
+/// Characters used to draw directory boxes
+struct Glyphs {
+    top_left: char,
+    top_right: char,
+    bottom_left: char,
+    bottom_right: char,
+    horizontal: char,
+    vertical: char,
+    ellipsis: char,
+}
+
+const UNICODE_GLYPHS: Glyphs = Glyphs {
+    top_left: '┌',
+    top_right: '┐',
+    bottom_left: '└',
+    bottom_right: '┘',
+    horizontal: '─',
+    vertical: '│',
+    ellipsis: '…',
+};
+
+const ASCII_GLYPHS: Glyphs = Glyphs {
+    top_left: '+',
+    top_right: '+',
+    bottom_left: '+',
+    bottom_right: '+',
+    horizontal: '-',
+    vertical: '|',
+    ellipsis: '~',
+};
+
+/// A character on the canvas with its colors
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    ch: char,
+    fg: Option<Color>,
+    bg: Option<Color>,
+}
+
+/// A grid of cells that rectangles are drawn on
+struct Canvas {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    glyphs: &'static Glyphs,
+}
+
+/// Cell bounds of a rectangle, end exclusive
+#[derive(Clone, Copy)]
+struct Bounds {
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+}
+
+impl Bounds {
+    fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize, ascii: bool) -> Self {
+        let blank = Cell {
+            ch: ' ',
+            fg: None,
+            bg: None,
+        };
+        Canvas {
+            width,
+            height,
+            cells: vec![blank; width * height],
+            glyphs: if ascii {
+                &ASCII_GLYPHS
+            } else {
+                &UNICODE_GLYPHS
+            },
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, ch: char, fg: Color, bg: Color) {
+        self.cells[y * self.width + x] = Cell {
+            ch,
+            fg: Some(fg),
+            bg: Some(bg),
+        };
+    }
+
+    fn fill(&mut self, bounds: Bounds, fg: Color, bg: Color) {
+        for y in bounds.y0..bounds.y1 {
+            for x in bounds.x0..bounds.x1 {
+                self.set(x, y, ' ', fg, bg);
+            }
+        }
+    }
+
+    /// Write `text` from `(x, y)`, cut to `width` characters with an ellipsis
+    fn text(&mut self, x: usize, y: usize, width: usize, text: &str, fg: Color, bg: Color) {
+        let count = text.chars().count();
+        let shown: Vec<char> = if count <= width {
+            text.chars().collect()
+        } else if width > 1 {
+            let mut cut: Vec<char> = text.chars().take(width - 1).collect();
+            cut.push(self.glyphs.ellipsis);
+            cut
+        } else {
+            return;
+        };
+        for (i, ch) in shown.into_iter().enumerate() {
+            // Control characters in names would break the grid
+            let ch = if ch.is_control() { '?' } else { ch };
+            self.set(x + i, y, ch, fg, bg);
+        }
+    }
+
+    /// Draw a box around `bounds` with `title` on its top edge
+    fn frame(&mut self, bounds: Bounds, title: &str, fg: Color, bg: Color) {
+        let Bounds { x0, y0, x1, y1 } = bounds;
+        let glyphs = self.glyphs;
+        for x in x0 + 1..x1 - 1 {
+            self.set(x, y0, glyphs.horizontal, fg, bg);
+            self.set(x, y1 - 1, glyphs.horizontal, fg, bg);
+        }
+        for y in y0 + 1..y1 - 1 {
+            self.set(x0, y, glyphs.vertical, fg, bg);
+            self.set(x1 - 1, y, glyphs.vertical, fg, bg);
+        }
+        self.set(x0, y0, glyphs.top_left, fg, bg);
+        self.set(x1 - 1, y0, glyphs.top_right, fg, bg);
+        self.set(x0, y1 - 1, glyphs.bottom_left, fg, bg);
+        self.set(x1 - 1, y1 - 1, glyphs.bottom_right, fg, bg);
+        self.text(x0 + 1, y0, bounds.width() - 2, title, fg, bg);
+    }
+
+    /// Render the canvas as lines of colored text
+    fn render(&self) -> String {
+        let mut output = String::new();
+        for row in self.cells.chunks(self.width.max(1)).take(self.height) {
+            let mut run = String::new();
+            let mut style = None;
+            for cell in row {
+                let cell_style = (cell.fg, cell.bg);
+                if style != Some(cell_style) {
+                    output.push_str(&paint(&run, style));
+                    run.clear();
+                    style = Some(cell_style);
+                }
+                run.push(cell.ch);
+            }
+            output.push_str(&paint(&run, style));
+            output.push('\n');
+        }
+        output
+    }
+}
+
+/// Apply a foreground and background color to a run of text
+fn paint(text: &str, style: Option<(Option<Color>, Option<Color>)>) -> String {
+    match style {
+        Some((Some(fg), Some(bg))) => text.color(fg).on_color(bg).to_string(),
+        _ => text.to_string(),
+    }
+}
+
+/// The bright variant of a palette color, used for files
+fn bright(color: Color) -> Color {
+    match color {
+        Color::Blue => Color::BrightBlue,
+        Color::Green => Color::BrightGreen,
+        Color::Magenta => Color::BrightMagenta,
+        Color::Cyan => Color::BrightCyan,
+        Color::Red => Color::BrightRed,
+        Color::Yellow => Color::BrightYellow,
+        other => other,
+    }
+}
+
+/// Lay out the children of `node` inside `bounds` and draw them
+fn draw_children(canvas: &mut Canvas, node: &TreeNode, bounds: Bounds, level: usize, depth: usize) {
+    let children: Vec<(&String, &TreeNode)> = node.children.iter().collect();
+    let sizes: Vec<u64> = children.iter().map(|(_, child)| child.size).collect();
+    let area = Rect {
+        x: 0.0,
+        y: 0.0,
+        w: bounds.width() as f64,
+        h: bounds.height() as f64 * CELL_ASPECT,
+    };
+    let rects = squarify(&sizes, area);
+
+    for (index, ((name, child), rect)) in children.iter().zip(rects).enumerate() {
+        let cell = Bounds {
+            x0: bounds.x0 + rect.x.round() as usize,
+            y0: bounds.y0 + (rect.y / CELL_ASPECT).round() as usize,
+            x1: bounds.x0 + (rect.x + rect.w).round() as usize,
+            y1: bounds.y0 + ((rect.y + rect.h) / CELL_ASPECT).round() as usize,
+        };
+        if cell.x1 <= cell.x0 || cell.y1 <= cell.y0 {
+            continue;
+        }
+        // Shift the palette per level, so a directory and its largest child differ
+        let color = PALETTE[(index + level) % PALETTE.len()];
+        let size = format_size(child.size, DECIMAL);
+
+        if child.is_dir && cell.width() >= 3 && cell.height() >= 2 {
+            canvas.fill(cell, Color::White, color);
+            canvas.frame(cell, &format!("{name}/ {size}"), Color::BrightWhite, color);
+            let inner = Bounds {
+                x0: cell.x0 + 1,
+                y0: cell.y0 + 1,
+                x1: cell.x1 - 1,
+                y1: cell.y1 - 1,
+            };
+            if level < depth && inner.width() > 0 && inner.height() > 0 {
+                draw_children(canvas, child, inner, level + 1, depth);
+            }
+        } else {
+            let (fg, bg) = if child.is_dir {
+                (Color::BrightWhite, color)
+            } else {
+                (Color::Black, bright(color))
+            };
+            let name = if child.is_dir {
+                format!("{name}/")
+            } else {
+                name.to_string()
+            };
+            canvas.fill(cell, fg, bg);
+            if cell.height() >= 2 {
+                canvas.text(cell.x0, cell.y0, cell.width(), &name, fg, bg);
+                canvas.text(cell.x0, cell.y0 + 1, cell.width(), &size, fg, bg);
+            } else {
+                canvas.text(
+                    cell.x0,
+                    cell.y0,
+                    cell.width(),
+                    &format!("{name} {size}"),
+                    fg,
+                    bg,
+                );
+            }
+        }
+    }
+}
+
+/// Options for drawing a treemap
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TreemapOptions {
+    /// Width of the map in columns, at most `MAX_SIDE`
+    pub width: usize,
+    /// Height of the map in rows, at most `MAX_SIDE`
+    pub height: usize,
+    /// How many levels of directories to nest. Defaults to as many as fit.
+    pub depth: Option<usize>,
+    /// Whether to use ASCII characters instead of Unicode box drawing
+    pub ascii: bool,
+}
+
+/// Generates a squarified treemap of a given path for the terminal.
+///
+/// The children of `path` are laid out as rectangles with areas proportional
+/// to their sizes. Directories are drawn as boxes with their name and size on
+/// the top edge and their own children nested inside, files as colored
+/// blocks labelled where the label fits.
+///
+/// # Arguments
+///
+/// * `path` - The path to draw a treemap of.
+/// * `options` - The size of the map, nesting depth and characters to use.
+///
+/// # Returns
+///
+/// * A tuple of the treemap, one line per row, and the total size of `path`.
+pub fn generate_treemap(path: &Path, options: &TreemapOptions) -> (String, u64) {
+    let (entries, total_size, _) = tree::scan(path, options.depth);
+    (draw(&tree::build_tree(&entries), options), total_size)
+}
+
+/// Generates a squarified treemap of a snapshot, as `generate_treemap` does for a path.
+pub fn snapshot_treemap(snapshot: &Snapshot, options: &TreemapOptions) -> String {
+    draw(&snapshot.to_tree(options.depth), options)
+}
+
+/// Draw the children of `root` over the whole canvas
+fn draw(root: &TreeNode, options: &TreemapOptions) -> String {
+    let (width, height) = (options.width.min(MAX_SIDE), options.height.min(MAX_SIDE));
+    let mut canvas = Canvas::new(width, height, options.ascii);
+    let bounds = Bounds {
+        x0: 0,
+        y0: 0,
+        x1: width,
+        y1: height,
+    };
+    draw_children(
+        &mut canvas,
+        root,
+        bounds,
+        1,
+        options.depth.unwrap_or(usize::MAX),
+    );
+    canvas.render()
+}
+
+/// Parse a treemap size such as `120x40`, with each side at most `MAX_SIDE`
+///
+/// # Examples
+///
+/// ```
+/// use fs_rs::treemap::parse_dimensions;
+/// assert_eq!(parse_dimensions("120x40"), Ok((120, 40)));
+/// assert_eq!(parse_dimensions("10000x10000"), Ok((10000, 10000)));
+/// assert!(parse_dimensions("120").is_err());
+/// assert!(parse_dimensions("0x40").is_err());
+/// assert!(parse_dimensions("100000x100000").is_err());
+/// assert!(parse_dimensions("99999999999x99999999999").is_err());
+/// ```
+pub fn parse_dimensions(input: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("invalid size '{input}', expected COLUMNSxROWS, e.g. 120x40");
+    let (width, height) = input.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let width: usize = width.trim().parse().map_err(|_| invalid())?;
+    let height: usize = height.trim().parse().map_err(|_| invalid())?;
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+    if width > MAX_SIDE || height > MAX_SIDE || width.checked_mul(height).is_none() {
+        return Err(format!(
+            "invalid size '{input}', each side must be at most {MAX_SIDE}"
+        ));
+    }
+    Ok((width, height))
+}
+
+/// The size of the terminal in columns and rows, falling back to `COLUMNS`, `LINES` and 80x24
+pub fn terminal_size() -> (usize, usize) {
+    #[cfg(unix)]
+    {
+        // SAFETY: TIOCGWINSZ only writes a winsize into the struct passed to it
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        let ret = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+        if ret == 0 && size.ws_col > 0 && size.ws_row > 0 {
+            return (size.ws_col as usize, size.ws_row as usize);
+        }
+    }
+    let variable = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|&value| value > 0 && value <= MAX_SIDE)
+    };
+    (
+        variable("COLUMNS").unwrap_or(DEFAULT_SIZE.0),
+        variable("LINES").unwrap_or(DEFAULT_SIZE.1),
+    )
+}
@@ -1,12 +1,23 @@
+use chrono::{DateTime, Local};
 use colored::Colorize;
 use comfy_table::{Cell, Table};
 use humansize::{DECIMAL, format_size};
 use rayon::prelude::*;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::time::SystemTime;
+
+use crate::error::{self, Error};
+use crate::progress::{self, ScanContext};
 
 const MAX_FILENAME_LENGTH: usize = 25;
 
+/// Record that a file or directory could not be read during a scan
+pub(crate) fn note_unreadable(error: Error) {
+    progress::error(&error);
+}
+
 /// Struct to hold sizes of files/directories
 #[derive(Clone, Debug, PartialEq)]
 pub struct Sizes {
@@ -15,6 +26,212 @@ pub struct Sizes {
     pub is_dir: bool,
 }
 
+/// Bytes and file count of a group of files
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub size: u64,
+    pub files: u64,
+}
+
+impl Totals {
+    /// Add a single file of `size` bytes
+    pub fn add(&mut self, size: u64) {
+        self.size += size;
+        self.files += 1;
+    }
+
+    /// Add the totals of another `Totals`
+    pub fn merge(&mut self, other: Totals) {
+        self.size += other.size;
+        self.files += other.files;
+    }
+}
+
+/// Walk every file under a directory in parallel, skipping symlinks, and fold them into a value
+///
+/// Each file is passed to `fold` together with its path and metadata. The
+/// per-thread accumulators created by `identity` are merged with `reduce`,
+/// so callers never need to collect the whole file list in memory.
+///
+/// # Arguments
+///
+/// * `dir_path`: Path to the directory
+/// * `identity`: Creates an empty accumulator
+/// * `fold`: Adds a single file to an accumulator
+/// * `reduce`: Merges two accumulators
+///
+/// returns: T - The accumulated value for all files under `dir_path`
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// let dir_path = Path::new("/some/directory");
+/// let count = fs_rs::utils::fold_files(dir_path, &|| 0u64, &|n, _, _| n + 1, &|a, b| a + b);
+/// ```
+pub fn fold_files<T, ID, F, R>(dir_path: &Path, identity: &ID, fold: &F, reduce: &R) -> T
+where
+    T: Send,
+    ID: Fn() -> T + Sync,
+    F: Fn(T, &Path, &fs::Metadata) -> T + Sync,
+    R: Fn(T, T) -> T + Sync,
+{
+    if progress::is_cancelled() {
+        return identity();
+    }
+    let entries = match fs::read_dir(dir_path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            note_unreadable(Error::from_io(dir_path, e));
+            return identity();
+        }
+    };
+    progress::entered_dir(dir_path);
+
+    let context = ScanContext::current();
+    entries
+        .filter_map(Result::ok)
+        .par_bridge()
+        .fold(identity, |acc, entry| {
+            context.run(|| {
+                if context.is_cancelled() {
+                    return acc;
+                }
+                let entry_path = entry.path();
+                let file_type = match entry.file_type() {
+                    Ok(ft) => ft,
+                    Err(e) => {
+                        note_unreadable(Error::from_io(&entry_path, e));
+                        return acc;
+                    }
+                };
+                if file_type.is_symlink() {
+                    return acc;
+                }
+                if file_type.is_dir() {
+                    let sub = fold_files(&entry_path, identity, fold, reduce);
+                    reduce(acc, sub)
+                } else {
+                    match entry.metadata() {
+                        Ok(metadata) => {
+                            progress::file_visited(metadata.len());
+                            fold(acc, &entry_path, &metadata)
+                        }
+                        Err(e) => {
+                            note_unreadable(Error::from_io(&entry_path, e));
+                            acc
+                        }
+                    }
+                }
+            })
+        })
+        .reduce(identity, reduce)
+}
+
+/// The `(device, inode)` pair identifying a file, `None` on platforms without inodes
+#[cfg(unix)]
+pub(crate) fn inode(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// The `(device, inode)` pair identifying a file, `None` on platforms without inodes
+#[cfg(not(unix))]
+pub(crate) fn inode(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Walk every file under a directory in parallel like `fold_files`, stopping at the first error
+///
+/// Unlike `fold_files`, unreadable entries are not skipped: the first one
+/// found is returned as an error. A directory that contains itself, e.g.
+/// through a bind mount, is reported as `Error::CycleDetected`.
+///
+/// # Arguments
+///
+/// * `dir_path`: Path to the directory
+/// * `identity`: Creates an empty accumulator
+/// * `fold`: Adds a single file to an accumulator
+/// * `reduce`: Merges two accumulators
+///
+/// returns: Result<T> - The accumulated value for all files under `dir_path`, or the first error
+pub fn try_fold_files<T, ID, F, R>(
+    dir_path: &Path,
+    identity: &ID,
+    fold: &F,
+    reduce: &R,
+) -> error::Result<T>
+where
+    T: Send,
+    ID: Fn() -> T + Sync,
+    F: Fn(T, &Path, &fs::Metadata) -> T + Sync,
+    R: Fn(T, T) -> T + Sync,
+{
+    let metadata = fs::metadata(dir_path).map_err(|e| Error::from_io(dir_path, e))?;
+    let ancestors: Vec<(u64, u64)> = inode(&metadata).into_iter().collect();
+    try_fold_dir(dir_path, &ancestors, identity, fold, reduce)
+}
+
+/// Recursive part of `try_fold_files`, `ancestors` holding the inodes of the directories above
+fn try_fold_dir<T, ID, F, R>(
+    dir_path: &Path,
+    ancestors: &[(u64, u64)],
+    identity: &ID,
+    fold: &F,
+    reduce: &R,
+) -> error::Result<T>
+where
+    T: Send,
+    ID: Fn() -> T + Sync,
+    F: Fn(T, &Path, &fs::Metadata) -> T + Sync,
+    R: Fn(T, T) -> T + Sync,
+{
+    if progress::is_cancelled() {
+        return Err(Error::Cancelled);
+    }
+    let entries = fs::read_dir(dir_path).map_err(|e| Error::from_io(dir_path, e))?;
+    let entries = entries
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(|e| Error::from_io(dir_path, e))?;
+    progress::entered_dir(dir_path);
+
+    let context = ScanContext::current();
+    entries
+        .par_iter()
+        .try_fold(identity, |acc, entry| {
+            context.run(|| {
+                if context.is_cancelled() {
+                    return Err(Error::Cancelled);
+                }
+                let entry_path = entry.path();
+                let file_type = entry
+                    .file_type()
+                    .map_err(|e| Error::from_io(&entry_path, e))?;
+                if file_type.is_symlink() {
+                    return Ok(acc);
+                }
+                let metadata = entry
+                    .metadata()
+                    .map_err(|e| Error::from_io(&entry_path, e))?;
+                if file_type.is_dir() {
+                    let mut below = ancestors.to_vec();
+                    if let Some(id) = inode(&metadata) {
+                        if ancestors.contains(&id) {
+                            return Err(Error::CycleDetected(entry_path));
+                        }
+                        below.push(id);
+                    }
+                    let sub = try_fold_dir(&entry_path, &below, identity, fold, reduce)?;
+                    Ok(reduce(acc, sub))
+                } else {
+                    progress::file_visited(metadata.len());
+                    Ok(fold(acc, &entry_path, &metadata))
+                }
+            })
+        })
+        .try_reduce(identity, |a, b| Ok(reduce(a, b)))
+}
+
 /// Calculate directory size in parallel, skipping symlinks
 ///
 /// # Arguments
@@ -31,28 +248,107 @@ pub struct Sizes {
 /// let size = fs_rs::utils::calculate_dir_size(dir_path);
 /// ```
 pub fn calculate_dir_size(dir_path: &Path) -> u64 {
-    fs::read_dir(dir_path)
-        .map(|entries| {
-            entries
-                .filter_map(Result::ok)
-                .par_bridge()
-                .map(|entry| {
-                    let file_type = match entry.file_type() {
-                        Ok(ft) => ft,
-                        Err(_) => return 0,
-                    };
-                    if file_type.is_symlink() {
-                        return 0;
-                    }
-                    if file_type.is_dir() {
-                        calculate_dir_size(&entry.path())
-                    } else {
-                        entry.metadata().map(|m| m.len()).unwrap_or(0)
-                    }
-                })
-                .sum()
-        })
-        .unwrap_or(0)
+    fold_files(
+        dir_path,
+        &|| 0,
+        &|size, _, metadata| size + metadata.len(),
+        &|a, b| a + b,
+    )
+}
+
+/// Calculate the size and file count of a directory in parallel, skipping symlinks
+///
+/// Files are aggregated exactly as in `calculate_dir_size`.
+///
+/// # Arguments
+///
+/// * `dir_path`: Path to the directory
+///
+/// returns: Totals - The size in bytes and the number of files
+pub fn calculate_dir_totals(dir_path: &Path) -> Totals {
+    fold_files(
+        dir_path,
+        &Totals::default,
+        &|mut totals, _, metadata| {
+            totals.add(metadata.len());
+            totals
+        },
+        &|mut a, b| {
+            a.merge(b);
+            a
+        },
+    )
+}
+
+/// Calculate directory size in parallel, skipping symlinks and failing on the first unreadable entry
+///
+/// # Arguments
+///
+/// * `dir_path`: Path to the directory
+///
+/// returns: Result<u64> - The size of the directory in bytes, or why it could not be computed
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use fs_rs::error::Error;
+/// let result = fs_rs::utils::try_calculate_dir_size(Path::new("/some/directory"));
+/// assert!(matches!(result, Err(Error::NotFound(_))));
+/// ```
+pub fn try_calculate_dir_size(dir_path: &Path) -> error::Result<u64> {
+    try_fold_files(
+        dir_path,
+        &|| 0,
+        &|size, _, metadata| size + metadata.len(),
+        &|a, b| a + b,
+    )
+}
+
+/// Parse a human readable size such as `10MB`, `1.5GiB` or `512` into bytes
+///
+/// Decimal units (`k`, `KB`, `MB`, ...) are powers of 1000 and binary units
+/// (`KiB`, `MiB`, ...) are powers of 1024. Units are case-insensitive and a
+/// number without a unit is in bytes.
+///
+/// # Arguments
+///
+/// * `input`: The size to parse
+///
+/// returns: Result<u64, String> - The size in bytes, or a message describing the problem
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(fs_rs::utils::parse_size("10MB"), Ok(10_000_000));
+/// assert_eq!(fs_rs::utils::parse_size("1.5KiB"), Ok(1536));
+/// ```
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{input}', expected a number such as 10MB"))?;
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "m" | "mb" => 1000u64.pow(2),
+        "g" | "gb" => 1000u64.pow(3),
+        "t" | "tb" => 1000u64.pow(4),
+        "p" | "pb" => 1000u64.pow(5),
+        "ki" | "kib" => 1 << 10,
+        "mi" | "mib" => 1 << 20,
+        "gi" | "gib" => 1 << 30,
+        "ti" | "tib" => 1 << 40,
+        "pi" | "pib" => 1 << 50,
+        other => return Err(format!("unknown size unit '{other}' in '{input}'")),
+    };
+
+    Ok((number * multiplier as f64).round() as u64)
 }
 
 /// Sort sizes by size in descending order (in-place)
@@ -73,7 +369,7 @@ pub fn calculate_dir_size(dir_path: &Path) -> u64 {
 /// assert_eq!(sizes[0].size, 200);
 /// ```
 pub fn sort_by_size(sizes: &mut [Sizes]) {
-    sizes.sort_by(|a, b| b.size.cmp(&a.size));
+    sizes.sort_by_key(|s| std::cmp::Reverse(s.size));
 }
 
 /// Sort sizes by name in ascending order (in-place)
@@ -152,3 +448,72 @@ pub fn add_row(table: &mut Table, values: &[Sizes]) {
         table.add_row(vec![name_cell, size_cell]);
     }
 }
+
+/// Format a timestamp as a local date and time for display
+///
+/// # Arguments
+///
+/// * `time`: The timestamp to format
+///
+/// returns: String - The formatted time, or `-` if it is unknown
+pub fn format_time(time: Option<SystemTime>) -> String {
+    match time {
+        Some(time) => DateTime::<Local>::from(time)
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+        None => "-".to_string(),
+    }
+}
+
+/// Format a timestamp as an RFC 3339 local date and time for JSON output
+///
+/// # Arguments
+///
+/// * `time`: The timestamp to format
+///
+/// returns: Option<String> - The formatted time, or `None` if it is unknown
+pub fn format_rfc3339(time: Option<SystemTime>) -> Option<String> {
+    time.map(|time| DateTime::<Local>::from(time).to_rfc3339())
+}
+
+/// Sort named totals into a list, largest first
+///
+/// # Arguments
+///
+/// * `totals`: Totals keyed by name
+///
+/// returns: Vec<(String, Totals)> - The totals, largest first and then by name
+pub fn sorted_totals(totals: HashMap<String, Totals>) -> Vec<(String, Totals)> {
+    let mut totals: Vec<(String, Totals)> = totals.into_iter().collect();
+    totals.sort_by(|a, b| b.1.size.cmp(&a.1.size).then_with(|| a.0.cmp(&b.0)));
+    totals
+}
+
+/// Short summary of the largest named totals, e.g. `rs 12 kB, toml 1 kB, +2 more`
+///
+/// # Arguments
+///
+/// * `totals`: Named totals, largest first
+/// * `limit`: How many names to show
+///
+/// returns: String
+pub fn summary(totals: &[(String, Totals)], limit: usize) -> String {
+    let mut parts: Vec<String> = totals
+        .iter()
+        .take(limit)
+        .map(|(name, t)| format!("{} {}", name, format_size(t.size, DECIMAL)))
+        .collect();
+    if totals.len() > limit {
+        parts.push(format!("+{} more", totals.len() - limit));
+    }
+    parts.join(", ")
+}
+
+/// Percentage of `part` in `total`, 0 if `total` is 0
+pub fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
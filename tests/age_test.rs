@@ -0,0 +1,109 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tempfile::tempdir;
+
+use fs_rs::age::{FileTimes, TimeField, age_breakdown, stale_dirs};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn write_file(path: &Path, size: usize, age: Duration) {
+    let mut file = File::create(path).expect("Failed to create file");
+    file.write_all(&vec![b'x'; size])
+        .expect("Failed to write to file");
+    file.set_modified(SystemTime::now() - age)
+        .expect("Failed to set modified time");
+}
+
+#[test]
+fn test_age_breakdown() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    write_file(&dir.path().join("new.txt"), 10, Duration::ZERO);
+    write_file(&dir.path().join("week.txt"), 20, DAY * 3);
+    write_file(&dir.path().join("month.txt"), 30, DAY * 20);
+    write_file(&dir.path().join("ancient.txt"), 40, DAY * 800);
+
+    let buckets = age_breakdown(&[dir.path()], TimeField::Modified, SystemTime::now());
+    let sizes: Vec<(&str, u64)> = buckets.iter().map(|b| (b.label, b.size)).collect();
+
+    assert_eq!(
+        sizes,
+        vec![
+            ("< 1 day", 10),
+            ("< 7 days", 20),
+            ("< 30 days", 30),
+            ("< 1 year", 0),
+            ("older", 40),
+            ("unknown", 0),
+        ]
+    );
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_stale_dirs_reports_top_most_directory() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let archive = dir.path().join("archive");
+    let nested = archive.join("2019");
+    let active = dir.path().join("active");
+    fs::create_dir_all(&nested).expect("Failed to create nested dir");
+    fs::create_dir(&active).expect("Failed to create active dir");
+    write_file(&archive.join("old.tar"), 500, DAY * 400);
+    write_file(&nested.join("older.tar"), 300, DAY * 900);
+    write_file(&active.join("current.txt"), 10, Duration::ZERO);
+    write_file(&active.join("forgotten.txt"), 1000, DAY * 400);
+
+    let stale = stale_dirs(&[dir.path()], 90, TimeField::Modified, SystemTime::now());
+
+    assert_eq!(stale.len(), 1);
+    assert_eq!(stale[0].path, archive);
+    assert_eq!(stale[0].size, 800);
+    assert!(stale[0].newest.is_some());
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_unknown_timestamps_are_never_stale() {
+    let now = SystemTime::now();
+    let old = FileTimes {
+        modified: Some(now - DAY * 400),
+        ..FileTimes::default()
+    };
+
+    assert!(old.older_than(TimeField::Modified, now - DAY * 90));
+    assert!(!old.older_than(TimeField::Modified, now - DAY * 500));
+    assert!(!old.older_than(TimeField::Changed, now - DAY * 90));
+    assert!(!FileTimes::default().older_than(TimeField::Modified, now));
+}
+
+#[test]
+fn test_stale_dirs_after_a_stale_subtree_are_still_found() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let archive = dir.path().join("archive");
+    let sibling = dir.path().join("archive-copy");
+    let cache = dir.path().join("zz").join("cache");
+    for path in [archive.join("2019"), sibling.clone(), cache.clone()] {
+        fs::create_dir_all(&path).expect("Failed to create dir");
+    }
+    write_file(&archive.join("2019").join("old.tar"), 500, DAY * 400);
+    write_file(&sibling.join("old.tar"), 200, DAY * 400);
+    write_file(&cache.join("blob"), 100, DAY * 400);
+    write_file(
+        &dir.path().join("zz").join("current.txt"),
+        10,
+        Duration::ZERO,
+    );
+
+    let stale = stale_dirs(&[dir.path()], 90, TimeField::Modified, SystemTime::now());
+    let paths: Vec<_> = stale.iter().map(|s| s.path.clone()).collect();
+
+    assert_eq!(paths, vec![archive, sibling, cache]);
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
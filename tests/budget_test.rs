@@ -0,0 +1,55 @@
+use std::fs::{self, File};
+use std::io::Write;
+
+use fs_rs::budget::{self, parse_budgets};
+use tempfile::tempdir;
+
+#[test]
+fn test_budget_check() {
+    let dir = tempdir().unwrap();
+    let dist = dir.path().join("dist");
+    fs::create_dir_all(dist.join("assets")).unwrap();
+    File::create(dist.join("app.js"))
+        .unwrap()
+        .write_all(&[0; 3000])
+        .unwrap();
+    File::create(dist.join("assets").join("logo.svg"))
+        .unwrap()
+        .write_all(&[0; 500])
+        .unwrap();
+
+    let budgets = parse_budgets(
+        "dist size=3kB files=2\n\
+         dist/**/* size=10kB\n\
+         dist/*.js files=1\n\
+         missing size=1kB\n",
+    )
+    .unwrap();
+    let results = budget::check(&budgets, dir.path());
+    assert_eq!(results.len(), 4);
+
+    assert_eq!(results[0].totals.size, 3500);
+    assert_eq!(results[0].totals.files, 2);
+    assert_eq!(results[0].size_overage(), Some(500));
+    assert_eq!(results[0].files_overage(), None);
+    assert!(!results[0].passed());
+
+    // Nested matches of a recursive glob are only counted once
+    assert_eq!(results[1].totals.size, 3500);
+    assert!(results[1].passed());
+
+    assert_eq!(results[2].totals.files, 1);
+    assert!(results[2].passed());
+
+    assert!(results[3].matched.is_empty());
+    assert!(results[3].passed());
+
+    let json = budget::to_json(&results);
+    assert_eq!(json["passed"], false);
+    assert_eq!(json["results"][0]["size_overage"], 500);
+
+    let junit = budget::to_junit(&results);
+    assert!(junit.contains("tests=\"4\" failures=\"1\""));
+    assert!(junit.contains("<failure message=\"size 3.50 kB exceeds 3 kB by 500 B\""));
+    assert!(junit.contains("name=\"dist/*.js\""));
+}
@@ -11,16 +11,9 @@ fn fs_rs() -> Command {
 fn test_default_output_contains_filename_and_total() {
     let dir = tempdir().unwrap();
     let file = dir.path().join("test.txt");
-    File::create(&file)
-        .unwrap()
-        .write_all(b"hello")
-        .unwrap();
+    File::create(&file).unwrap().write_all(b"hello").unwrap();
 
-    let output = fs_rs()
-        .arg(dir.path())
-        .arg("--no-color")
-        .output()
-        .unwrap();
+    let output = fs_rs().arg(dir.path()).arg("--no-color").output().unwrap();
 
     assert!(output.status.success());
     let stdout = String::from_utf8(output.stdout).unwrap();
@@ -120,17 +113,15 @@ fn test_nonexistent_path_shows_error() {
         .output()
         .unwrap();
 
-    let stdout = String::from_utf8(output.stdout).unwrap();
-    assert!(stdout.contains("does not exist"));
+    assert_eq!(output.status.code(), Some(2), "no path exists");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("does not exist"));
+    assert!(output.stdout.is_empty(), "errors must not go to stdout");
 }
 
 #[test]
 fn test_tree_conflicts_with_json() {
-    let output = fs_rs()
-        .arg("--tree")
-        .arg("--json")
-        .output()
-        .unwrap();
+    let output = fs_rs().arg("--tree").arg("--json").output().unwrap();
 
     assert!(
         !output.status.success(),
@@ -178,3 +169,1074 @@ fn test_json_respects_sort_by_size() {
     assert_eq!(parsed[0]["name"], "large.txt", "largest should be first");
     assert_eq!(parsed[1]["name"], "small.txt", "smallest should be last");
 }
+
+#[test]
+fn test_depth_without_tree_lists_nested_dirs_by_size() {
+    let dir = tempdir().unwrap();
+    let small = dir.path().join("small");
+    let large = dir.path().join("outer").join("large");
+    fs::create_dir_all(&small).unwrap();
+    fs::create_dir_all(&large).unwrap();
+    File::create(small.join("a.txt"))
+        .unwrap()
+        .write_all(b"a")
+        .unwrap();
+    File::create(large.join("b.txt"))
+        .unwrap()
+        .write_all(&[b'x'; 1000])
+        .unwrap();
+    File::create(dir.path().join("outer").join("c.txt"))
+        .unwrap()
+        .write_all(b"c")
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("-d")
+        .arg("2")
+        .arg("-s")
+        .arg("--json")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let parsed: Vec<serde_json::Value> = serde_json::from_str(stdout.trim()).unwrap();
+    let names: Vec<&str> = parsed.iter().map(|e| e["name"].as_str().unwrap()).collect();
+    let large_name = format!("outer{}large", std::path::MAIN_SEPARATOR);
+    assert_eq!(names.len(), 3);
+    assert_eq!(names[0], "outer", "parent includes its children");
+    assert_eq!(names[1], large_name);
+    assert_eq!(names[2], "small");
+}
+
+#[test]
+fn test_top_json_lists_largest_files_across_dirs() {
+    let dir = tempdir().unwrap();
+    let nested = dir.path().join("a").join("b");
+    fs::create_dir_all(&nested).unwrap();
+    File::create(dir.path().join("small.txt"))
+        .unwrap()
+        .write_all(b"a")
+        .unwrap();
+    File::create(nested.join("large.txt"))
+        .unwrap()
+        .write_all(&[b'x'; 1000])
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--top")
+        .arg("1")
+        .arg("--json")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let parsed: Vec<serde_json::Value> = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(parsed.len(), 1);
+    assert!(parsed[0]["path"].as_str().unwrap().ends_with("large.txt"));
+    assert_eq!(parsed[0]["size_bytes"], 1000);
+    assert!(parsed[0]["modified"].is_string());
+}
+
+#[test]
+fn test_duplicates_json_reports_wasted_bytes() {
+    let dir = tempdir().unwrap();
+    File::create(dir.path().join("a.txt"))
+        .unwrap()
+        .write_all(b"duplicate")
+        .unwrap();
+    File::create(dir.path().join("b.txt"))
+        .unwrap()
+        .write_all(b"duplicate")
+        .unwrap();
+    File::create(dir.path().join("c.txt"))
+        .unwrap()
+        .write_all(b"unique!!!")
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--duplicates")
+        .arg("--json")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let parsed: Vec<serde_json::Value> = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(parsed[0]["wasted_bytes"], 9);
+    assert_eq!(parsed[0]["paths"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn test_by_type_json_groups_by_extension() {
+    let dir = tempdir().unwrap();
+    File::create(dir.path().join("a.txt"))
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+    File::create(dir.path().join("b.TXT"))
+        .unwrap()
+        .write_all(b"world")
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--by-type")
+        .arg("--json")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let parsed: Vec<serde_json::Value> = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(parsed[0]["type"], "txt");
+    assert_eq!(parsed[0]["size_bytes"], 10);
+    assert_eq!(parsed[0]["files"], 2);
+}
+
+#[test]
+fn test_age_json_lists_every_bucket() {
+    let dir = tempdir().unwrap();
+    File::create(dir.path().join("fresh.txt"))
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--age")
+        .arg("--json")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let parsed: Vec<serde_json::Value> = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(parsed.len(), 6);
+    assert_eq!(parsed[0]["age"], "< 1 day");
+    assert_eq!(parsed[0]["size_bytes"], 5);
+    assert_eq!(parsed[0]["files"], 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_owners_json_has_users_and_groups() {
+    let dir = tempdir().unwrap();
+    File::create(dir.path().join("a.txt"))
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--owners")
+        .arg("--json")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let parsed: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(parsed["users"][0]["size_bytes"], 5);
+    assert_eq!(parsed["users"][0]["files"], 1);
+    assert_eq!(parsed["groups"][0]["size_bytes"], 5);
+}
+
+#[test]
+fn test_min_size_hides_small_entries_but_keeps_totals() {
+    let dir = tempdir().unwrap();
+    File::create(dir.path().join("small.txt"))
+        .unwrap()
+        .write_all(b"a")
+        .unwrap();
+    File::create(dir.path().join("large.txt"))
+        .unwrap()
+        .write_all(&[b'x'; 2000])
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--min-size")
+        .arg("1kB")
+        .arg("--no-color")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("large.txt"));
+    assert!(!stdout.contains("small.txt"), "small file should be hidden");
+    assert!(
+        stdout.contains("2.00 kB"),
+        "total should include hidden files"
+    );
+    assert!(stdout.contains("2 (1 shown)"));
+}
+
+#[test]
+fn test_filter_expression() {
+    let dir = tempdir().unwrap();
+    File::create(dir.path().join("notes.txt"))
+        .unwrap()
+        .write_all(&[b'x'; 2000])
+        .unwrap();
+    File::create(dir.path().join("app.log"))
+        .unwrap()
+        .write_all(&[b'x'; 2000])
+        .unwrap();
+    File::create(dir.path().join("small.log"))
+        .unwrap()
+        .write_all(b"a")
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--filter")
+        .arg("ext = log and size > 1kB")
+        .arg("--no-color")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("app.log"));
+    assert!(!stdout.contains("notes.txt"));
+    assert!(!stdout.contains("small.log"));
+    assert!(stdout.contains("3 (1 shown)"));
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--filter")
+        .arg("size >> 1kB")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("^"),
+        "error should point at the token: {stderr}"
+    );
+}
+
+#[test]
+fn test_compare_roots_side_by_side() {
+    let dir = tempdir().unwrap();
+    let old = dir.path().join("old");
+    let new = dir.path().join("new");
+    fs::create_dir_all(&old).unwrap();
+    fs::create_dir_all(&new).unwrap();
+    File::create(old.join("file.txt"))
+        .unwrap()
+        .write_all(&[b'x'; 1000])
+        .unwrap();
+    File::create(new.join("file.txt"))
+        .unwrap()
+        .write_all(&[b'x'; 3000])
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(&old)
+        .arg(&new)
+        .arg("--compare")
+        .arg("--no-color")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("file.txt"));
+    assert!(stdout.contains("1 kB"));
+    assert!(stdout.contains("3 kB"));
+    assert!(stdout.contains("+2 kB"));
+    assert!(stdout.contains("Total"));
+
+    let output = fs_rs()
+        .arg(&old)
+        .arg(&new)
+        .arg("--compare")
+        .arg("--json")
+        .output()
+        .unwrap();
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["entries"][0]["sizes"], serde_json::json!([1000, 3000]));
+    assert_eq!(json["total_deltas"], serde_json::json!([2000]));
+}
+
+#[test]
+fn test_multiple_paths_are_grouped_per_root() {
+    let dir = tempdir().unwrap();
+    let first = dir.path().join("first");
+    let second = dir.path().join("second");
+    fs::create_dir_all(&first).unwrap();
+    fs::create_dir_all(&second).unwrap();
+    File::create(first.join("same.txt"))
+        .unwrap()
+        .write_all(&[b'x'; 1000])
+        .unwrap();
+    File::create(second.join("same.txt"))
+        .unwrap()
+        .write_all(&[b'x'; 3000])
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(&first)
+        .arg(&second)
+        .arg("--no-color")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let first_pos = stdout.find(&first.display().to_string()).unwrap();
+    let second_pos = stdout.find(&second.display().to_string()).unwrap();
+    assert!(first_pos < second_pos, "each root gets its own header");
+    assert!(stdout.contains("Subtotal: 1 kB"));
+    assert!(stdout.contains("Subtotal: 3 kB"));
+    assert!(stdout.contains("Grand total size: 4 kB"));
+
+    let output = fs_rs()
+        .arg(&first)
+        .arg(&second)
+        .arg("--json")
+        .output()
+        .unwrap();
+
+    let parsed: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(parsed.len(), 2);
+    assert_eq!(parsed[0]["root"], first.display().to_string());
+    assert_eq!(parsed[1]["root"], second.display().to_string());
+}
+
+#[test]
+fn test_tree_prints_header_per_root() {
+    let dir = tempdir().unwrap();
+    File::create(dir.path().join("file.txt"))
+        .unwrap()
+        .write_all(&[b'x'; 1000])
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--tree")
+        .arg("--no-color")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("{} (1 kB)", dir.path().display())));
+}
+
+#[test]
+fn test_exit_codes_and_clean_json_output() {
+    let dir = tempdir().unwrap();
+    File::create(dir.path().join("data.txt"))
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+
+    let output = fs_rs().arg(dir.path()).arg("--json").output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("/nonexistent/path/xyz_abc_123")
+        .arg("--json")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "one path is missing");
+    let parsed: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(parsed.len(), 1);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("does not exist"));
+
+    let empty = dir.path().join("empty");
+    fs::create_dir(&empty).unwrap();
+    let output = fs_rs().arg(&empty).output().unwrap();
+    assert_eq!(output.status.code(), Some(3), "nothing found");
+
+    let output = fs_rs().arg("--min-size").arg("lots").output().unwrap();
+    assert_eq!(output.status.code(), Some(2), "invalid argument");
+}
+
+#[test]
+fn test_progress_json_events() {
+    let dir = tempdir().unwrap();
+    File::create(dir.path().join("data.txt"))
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("/nonexistent/path/xyz_abc_123")
+        .arg("--progress-json")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+
+    // Diagnostics become events too, so every line on stderr is JSON
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let events: Vec<serde_json::Value> = stderr
+        .lines()
+        .map(|line| serde_json::from_str(line).expect(line))
+        .collect();
+    let kinds: Vec<&str> = events
+        .iter()
+        .map(|e| e["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds.first(), Some(&"started"));
+    assert_eq!(kinds.last(), Some(&"finished"));
+    assert!(kinds.contains(&"root_completed"));
+    assert!(events.iter().any(|e| {
+        e["event"] == "warning"
+            && e["message"]
+                .as_str()
+                .unwrap()
+                .ends_with("xyz_abc_123 does not exist")
+    }));
+
+    let finished = events.last().unwrap();
+    assert_eq!(finished["exit_code"], 1);
+    assert_eq!(finished["files"], 1);
+    assert_eq!(finished["bytes"], 5);
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--progress-json")
+        .arg("stdout")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2), "invalid destination");
+}
+
+#[test]
+fn test_disk_usage_json() {
+    let output = fs_rs()
+        .arg("--disk-usage")
+        .arg("--json")
+        .arg("--threshold")
+        .arg("101")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2), "threshold is a percentage");
+
+    let output = fs_rs()
+        .arg("--disk-usage")
+        .arg("--json")
+        .arg("--mount")
+        .arg("/*")
+        .arg("--threshold")
+        .arg("0")
+        .output()
+        .unwrap();
+    // Some sandboxes have no filesystem that sysinfo reports
+    if output.status.code() == Some(3) {
+        return;
+    }
+    let parsed: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(!parsed.is_empty());
+    for fs in &parsed {
+        assert!(fs["mount_point"].as_str().unwrap().starts_with('/'));
+        let used = fs["used_percent"].as_f64().unwrap();
+        assert_eq!(fs["over_threshold"], used > 0.0);
+    }
+    let over = parsed.iter().any(|fs| fs["over_threshold"] == true);
+    assert_eq!(output.status.code(), Some(if over { 4 } else { 0 }));
+}
+
+#[test]
+fn test_json_entries_carry_file_system() {
+    let dir = tempdir().unwrap();
+    File::create(dir.path().join("data.txt"))
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+
+    let output = fs_rs().arg(dir.path()).arg("--json").output().unwrap();
+    let parsed: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let file_system = &parsed[0]["filesystem"];
+    // Some sandboxes have no filesystem that sysinfo reports
+    if file_system.is_null() {
+        return;
+    }
+    let canonical = dir.path().canonicalize().unwrap();
+    let mount_point = file_system["mount_point"].as_str().unwrap();
+    assert!(canonical.starts_with(mount_point));
+    assert!(file_system["total"].as_u64().unwrap() > 0);
+    assert!(file_system["share_percent"].as_f64().unwrap() >= 0.0);
+}
+
+#[test]
+fn test_budget_exit_codes() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("dist")).unwrap();
+    File::create(dir.path().join("dist").join("app.js"))
+        .unwrap()
+        .write_all(&[0; 2000])
+        .unwrap();
+    let budgets = dir.path().join("budgets.txt");
+    let junit = dir.path().join("junit.xml");
+
+    fs::write(&budgets, "dist size=1kB\n").unwrap();
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--budget")
+        .arg(&budgets)
+        .arg("--junit")
+        .arg(&junit)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4), "budget exceeded");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("FAIL"));
+    assert!(stdout.contains("1 of 1 budgets exceeded"));
+    assert!(fs::read_to_string(&junit).unwrap().contains("<failure"));
+
+    fs::write(&budgets, "dist size=1MB files=10\n").unwrap();
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--budget")
+        .arg(&budgets)
+        .arg("--json")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let parsed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(parsed["passed"], true);
+    assert_eq!(parsed["results"][0]["size"], 2000);
+
+    fs::write(&budgets, "dist 1MB\n").unwrap();
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--budget")
+        .arg(&budgets)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2), "invalid budget file");
+}
+
+#[test]
+fn test_prometheus_textfile() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("logs")).unwrap();
+    File::create(dir.path().join("logs").join("app.log"))
+        .unwrap()
+        .write_all(&[0; 1234])
+        .unwrap();
+    let output = dir.path().join("fs_rs.prom");
+
+    let result = fs_rs()
+        .arg(dir.path().join("logs"))
+        .arg("--prometheus")
+        .arg(&output)
+        .arg("--label")
+        .arg("host=test")
+        .output()
+        .unwrap();
+    assert_eq!(result.status.code(), Some(0));
+    assert!(result.stdout.is_empty());
+    let text = fs::read_to_string(&output).unwrap();
+    assert!(text.contains("fs_rs_directory_files{"));
+    assert!(text.contains("host=\"test\"} 1234\n"));
+
+    let result = fs_rs()
+        .arg("--prometheus")
+        .arg("-")
+        .arg("--label")
+        .arg("path=x")
+        .output()
+        .unwrap();
+    assert_eq!(result.status.code(), Some(2), "reserved label");
+
+    let result = fs_rs()
+        .arg(dir.path().join("logs"))
+        .arg("--prometheus")
+        .arg(dir.path().join("missing").join("fs_rs.prom"))
+        .output()
+        .unwrap();
+    assert_eq!(
+        result.status.code(),
+        Some(1),
+        "write failures are not invalid input"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_serve_on_unix_socket() {
+    use std::io::Read;
+    use std::os::unix::net::UnixStream;
+    use std::time::{Duration, Instant};
+
+    let dir = tempdir().unwrap();
+    File::create(dir.path().join("data.txt"))
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+    let socket = dir.path().join("fs_rs.sock");
+
+    let mut child = fs_rs()
+        .arg(dir.path())
+        .arg("--serve")
+        .arg(format!("unix:{}", socket.display()))
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let started = Instant::now();
+    let mut stream = loop {
+        match UnixStream::connect(&socket) {
+            Ok(stream) => break stream,
+            Err(_) if started.elapsed() < Duration::from_secs(10) => {
+                std::thread::sleep(Duration::from_millis(50))
+            }
+            Err(e) => panic!("server did not start: {e}"),
+        }
+    };
+    stream
+        .write_all(b"GET /api/status HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    // SAFETY: the child's pid is valid until it is waited for
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+    let exit = child.wait().unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    let body = response.split("\r\n\r\n").nth(1).unwrap();
+    let status: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(status["files"], 1);
+    assert_eq!(exit.code(), Some(0), "Ctrl-C stops serving cleanly");
+    assert!(!socket.exists(), "the socket is removed on exit");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_daemon_answers_queries() {
+    use std::time::{Duration, Instant};
+
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("watched");
+    fs::create_dir(&root).unwrap();
+    File::create(root.join("data.txt"))
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+    let socket = dir.path().join("fs_rs.sock");
+
+    let mut child = fs_rs()
+        .arg(&root)
+        .arg("--daemon")
+        .arg(&socket)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let query = || {
+        fs_rs()
+            .arg(&root)
+            .arg("--query")
+            .arg(&socket)
+            .arg("--json")
+            .output()
+            .unwrap()
+    };
+    let size = |output: &std::process::Output| -> Option<u64> {
+        let answers: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+        answers[0]["size"].as_u64()
+    };
+
+    let started = Instant::now();
+    let mut output = query();
+    while output.status.code() != Some(0) && started.elapsed() < Duration::from_secs(10) {
+        std::thread::sleep(Duration::from_millis(50));
+        output = query();
+    }
+    assert_eq!(size(&output), Some(5));
+
+    // The daemon picks up changes through inotify
+    File::create(root.join("more.txt"))
+        .unwrap()
+        .write_all(&[0; 95])
+        .unwrap();
+    let started = Instant::now();
+    while size(&query()) != Some(100) {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "change not picked up"
+        );
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    // SAFETY: the child's pid is valid until it is waited for
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+
+    let output = query();
+    assert_eq!(output.status.code(), Some(2), "the daemon is gone");
+}
+
+#[test]
+fn test_treemap() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    File::create(dir.path().join("sub").join("data.bin"))
+        .unwrap()
+        .write_all(&[0; 2000])
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .args(["--treemap", "--treemap-size", "30x6", "--no-color"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("┌sub/ 2 kB───"));
+    assert!(stdout.contains("│data.bin"));
+    assert_eq!(stdout.lines().filter(|line| line.ends_with('│')).count(), 4);
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .args(["--treemap", "--json"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_html_report() {
+    let dir = tempdir().unwrap();
+    File::create(dir.path().join("data.bin"))
+        .unwrap()
+        .write_all(&[0; 2000])
+        .unwrap();
+    let report = dir.path().join("report.html");
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .arg("--html")
+        .arg(&report)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let html = fs::read_to_string(&report).unwrap();
+    assert!(html.contains(r#"{"name":"data.bin","size":2000}"#));
+    assert!(html.contains("sunburst"));
+}
+
+#[test]
+fn test_ncdu_export_and_import() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("scanned");
+    fs::create_dir_all(root.join("sub")).unwrap();
+    File::create(root.join("sub").join("data.bin"))
+        .unwrap()
+        .write_all(&[0; 2000])
+        .unwrap();
+    let dump = dir.path().join("dump.json");
+
+    let output = fs_rs()
+        .arg(&root)
+        .arg("--export-ncdu")
+        .arg(&dump)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = fs_rs()
+        .arg("--import")
+        .arg(&dump)
+        .arg("--json")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let entries: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(entries[0]["root"], root.display().to_string());
+    assert_eq!(entries[0]["name"], "sub");
+    assert_eq!(entries[0]["size_bytes"], 2000);
+
+    let output = fs_rs()
+        .args(["--import", "-", "--tree", "--no-color"])
+        .stdin(File::open(&dump).unwrap())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("└── data.bin*  (2 kB)"));
+
+    fs::write(&dump, "[1, 2, {}]").unwrap();
+    let output = fs_rs().arg("--import").arg(&dump).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_import_du_output() {
+    let du = "300\t/srv/www/logo.png\n4396\t/srv/www\n20\t/srv/a.txt\n8512\t/srv\n";
+    let import = |args: &[&str]| {
+        let mut child = fs_rs()
+            .args(["--import", "-", "--no-color"])
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(du.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = import(&["--json", "-s"]);
+    assert!(output.status.success());
+    let entries: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(entries[0]["root"], "/srv");
+    assert_eq!(entries[0]["name"], "www");
+    assert_eq!(entries[0]["size_bytes"], 300);
+    assert_eq!(entries[1]["name"], "a.txt");
+
+    let output = import(&["--tree", "--min-size", "100B"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("/srv (320 B)"));
+    assert!(stdout.contains("└── logo.png*  (300 B)"));
+    assert!(!stdout.contains("a.txt"));
+}
+
+#[test]
+fn test_depth_zero_is_rejected() {
+    let dir = tempdir().unwrap();
+
+    let output = fs_rs().arg(dir.path()).args(["-d", "0"]).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_by_type_json_per_directory_with_depth() {
+    let dir = tempdir().unwrap();
+    let nested = dir.path().join("src").join("deep");
+    fs::create_dir_all(&nested).unwrap();
+    File::create(nested.join("main.rs"))
+        .unwrap()
+        .write_all(&[b'x'; 100])
+        .unwrap();
+    File::create(dir.path().join("src").join("notes.txt"))
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .args(["--by-type", "-d", "1", "--json"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let parsed: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(parsed.len(), 1, "only directories down to the depth");
+    assert_eq!(parsed[0]["name"], "src");
+    assert_eq!(parsed[0]["size_bytes"], 105);
+    assert_eq!(parsed[0]["types"][0]["type"], "rs");
+    assert_eq!(parsed[0]["types"][0]["size_bytes"], 100);
+    assert_eq!(parsed[0]["types"][1]["type"], "txt");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_owners_json_per_directory_with_depth() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempdir().unwrap();
+    let nested = dir.path().join("data").join("deep");
+    fs::create_dir_all(&nested).unwrap();
+    File::create(nested.join("a.txt"))
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+    let uid = fs::metadata(nested.join("a.txt")).unwrap().uid();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .args(["--owners", "-d", "2", "--json"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let parsed: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let names: Vec<&str> = parsed.iter().map(|d| d["name"].as_str().unwrap()).collect();
+    let sep = std::path::MAIN_SEPARATOR;
+    assert_eq!(names, vec!["data".to_string(), format!("data{sep}deep")]);
+    for dir in &parsed {
+        assert_eq!(dir["size_bytes"], 5);
+        assert_eq!(dir["users"][0]["id"], uid);
+        assert_eq!(dir["users"][0]["files"], 1);
+        assert_eq!(dir["groups"][0]["size_bytes"], 5);
+    }
+}
+
+#[test]
+fn test_only_one_mode_and_its_options_are_accepted() {
+    let dir = tempdir().unwrap();
+    let dump = dir.path().join("scan.du");
+    fs::write(&dump, "5\t/srv/a.txt\n5\t/srv\n").unwrap();
+
+    for args in [
+        vec!["--top", "3", "--duplicates"],
+        vec!["--import", dump.to_str().unwrap(), "--by-type"],
+        vec!["--import", dump.to_str().unwrap(), "--label", "env=prod"],
+        vec!["--import", dump.to_str().unwrap(), "--junit", "report.xml"],
+        vec!["--tree", "--label", "env=prod"],
+        vec!["--top", "3", "--categories"],
+    ] {
+        let output = fs_rs().args(&args).output().unwrap();
+        assert_eq!(output.status.code(), Some(2), "{args:?} should be rejected");
+    }
+
+    let output = fs_rs()
+        .arg("--import")
+        .arg(&dump)
+        .arg("--json")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_top_count_is_bounded() {
+    let dir = tempdir().unwrap();
+
+    for count in ["0", "100001", "18446744073709551615"] {
+        let output = fs_rs()
+            .arg(dir.path())
+            .args(["--top", count])
+            .output()
+            .unwrap();
+        assert_eq!(
+            output.status.code(),
+            Some(2),
+            "--top {count} should be rejected"
+        );
+    }
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .args(["--top", "100000", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_filters_are_rejected_where_nothing_is_listed() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    for args in [
+        vec!["--top", "3", "--min-size", "1KB"],
+        vec!["--duplicates", "--max-size", "1KB"],
+        vec!["--duplicate-dirs", "--filter", "size > 1KB"],
+        vec!["--stale", "30", "--min-size", "1KB"],
+        vec!["--by-type", "--filter", "size > 1KB"],
+        vec!["--owners", "--min-size", "1KB"],
+        vec!["--age", "-d", "2", "--max-size", "1KB"],
+    ] {
+        let output = fs_rs().arg(path).args(&args).output().unwrap();
+        assert_eq!(output.status.code(), Some(2), "{args:?} should be rejected");
+    }
+
+    for args in [
+        vec!["--by-type", "-d", "2", "--filter", "size > 1KB"],
+        vec!["--owners", "--tree", "--min-size", "1KB"],
+        vec!["--age", "--tree", "--max-size", "1KB"],
+    ] {
+        let output = fs_rs().arg(path).args(&args).output().unwrap();
+        assert_eq!(output.status.code(), Some(0), "{args:?} should be accepted");
+    }
+}
+
+#[test]
+fn test_progress_json_keeps_every_diagnostic_as_json() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    for args in [
+        vec![path, "--compare"],
+        vec!["--disk-usage", "--fs-type", "nope"],
+        vec![path, "--budget", "/nonexistent/budget.toml"],
+        vec!["--import", "/nonexistent/scan.du"],
+        vec![path, "--html", "/nonexistent/dir/report.html"],
+        vec![path, "--prometheus", "/nonexistent/dir/metrics.prom"],
+    ] {
+        let output = fs_rs().args(&args).arg("--progress-json").output().unwrap();
+        assert_ne!(output.status.code(), Some(0), "{args:?} should fail");
+
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        let events: Vec<serde_json::Value> = stderr
+            .lines()
+            .map(|line| serde_json::from_str(line).expect(line))
+            .collect();
+        assert!(
+            events.iter().any(|e| e["event"] == "warning"),
+            "{args:?} should explain the failure"
+        );
+    }
+}
+
+#[test]
+fn test_unwritable_outputs_are_partial_results() {
+    let dir = tempdir().unwrap();
+    let budgets = dir.path().join("budgets.txt");
+    fs::write(&budgets, "* size=1MB\n").unwrap();
+    let missing = dir.path().join("missing");
+    let budget_file = budgets.to_str().unwrap();
+
+    for (args, output) in [
+        (vec!["--html"], missing.join("report.html")),
+        (vec!["--export-ncdu"], missing.join("scan.json")),
+        (vec!["--prometheus"], missing.join("metrics.prom")),
+        (
+            vec!["--budget", budget_file, "--junit"],
+            missing.join("junit.xml"),
+        ),
+    ] {
+        let result = fs_rs()
+            .arg(dir.path())
+            .args(&args)
+            .arg(&output)
+            .output()
+            .unwrap();
+        assert_eq!(result.status.code(), Some(1), "{args:?} cannot write");
+    }
+}
+
+#[test]
+fn test_serve_reports_an_address_in_use() {
+    let dir = tempdir().unwrap();
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = taken.local_addr().unwrap().to_string();
+
+    let output = fs_rs()
+        .arg(dir.path())
+        .args(["--serve", &address, "--no-color"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty(), "nothing is served");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Cannot listen on"));
+    assert!(stderr.contains(&address));
+}
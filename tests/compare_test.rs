@@ -0,0 +1,51 @@
+use std::fs::{self, File};
+use std::io::Write;
+use tempfile::tempdir;
+
+use fs_rs::compare::{compare_roots, format_delta, sort_by_change};
+
+#[test]
+fn test_compare_roots() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let prod = dir.path().join("prod");
+    let staging = dir.path().join("staging");
+    fs::create_dir_all(prod.join("data")).expect("Failed to create prod/data");
+    fs::create_dir_all(staging.join("data")).expect("Failed to create staging/data");
+    for (path, size) in [
+        (prod.join("data").join("db"), 1000),
+        (staging.join("data").join("db"), 3000),
+        (prod.join("app.log"), 500),
+        (staging.join("extra.txt"), 10),
+    ] {
+        File::create(path)
+            .expect("Failed to create file")
+            .write_all(&vec![b'x'; size])
+            .expect("Failed to write to file");
+    }
+
+    let mut comparison = compare_roots(&[prod.as_path(), staging.as_path()]);
+    let names: Vec<&str> = comparison.entries.iter().map(|e| e.name.as_str()).collect();
+
+    assert_eq!(names, vec!["app.log", "data", "extra.txt"]);
+    assert_eq!(comparison.entries[0].sizes, vec![Some(500), None]);
+    assert_eq!(comparison.entries[0].deltas(), vec![-500]);
+    assert!(comparison.entries[1].is_dir);
+    assert_eq!(comparison.entries[1].deltas(), vec![2000]);
+    assert_eq!(comparison.entries[2].sizes, vec![None, Some(10)]);
+    assert_eq!(comparison.totals, vec![1500, 3010]);
+    assert_eq!(comparison.total_deltas(), vec![1510]);
+
+    sort_by_change(&mut comparison.entries);
+    let names: Vec<&str> = comparison.entries.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, vec!["data", "app.log", "extra.txt"]);
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_format_delta() {
+    assert_eq!(format_delta(2000), "+2 kB");
+    assert_eq!(format_delta(-300), "-300 B");
+    assert_eq!(format_delta(0), "0 B");
+}
@@ -0,0 +1,104 @@
+use std::fs::{self, File};
+use std::io::Write;
+
+use fs_rs::daemon::SizeTree;
+use tempfile::tempdir;
+
+#[test]
+fn test_size_tree_updates() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    fs::create_dir(root.join("a")).unwrap();
+    File::create(root.join("a").join("one.txt"))
+        .unwrap()
+        .write_all(&[0; 100])
+        .unwrap();
+
+    let mut tree = SizeTree::build(std::slice::from_ref(&root));
+    assert_eq!(tree.dirs().len(), 2);
+    let lookup = tree.lookup(&root).unwrap();
+    assert_eq!((lookup.totals.size, lookup.totals.files), (100, 1));
+    assert!(!tree.lookup(&root.join("a").join("one.txt")).unwrap().is_dir);
+    assert!(tree.lookup(&root.join("missing")).is_none());
+
+    // A new directory is scanned and reported for watching
+    let b = root.join("a").join("b");
+    fs::create_dir(&b).unwrap();
+    File::create(b.join("two.txt"))
+        .unwrap()
+        .write_all(&[0; 50])
+        .unwrap();
+    assert_eq!(tree.refresh(&b), vec![b.clone()]);
+    assert_eq!(tree.lookup(&root).unwrap().totals.size, 150);
+    assert_eq!(tree.lookup(&root.join("a")).unwrap().totals.files, 2);
+
+    // A grown file updates every directory above it
+    File::create(b.join("two.txt"))
+        .unwrap()
+        .write_all(&[0; 80])
+        .unwrap();
+    tree.refresh(&b.join("two.txt"));
+    assert_eq!(tree.lookup(&b).unwrap().totals.size, 80);
+    assert_eq!(tree.lookup(&root).unwrap().totals.size, 180);
+
+    fs::remove_dir_all(&b).unwrap();
+    tree.refresh(&b);
+    assert!(tree.lookup(&b).is_none());
+    assert_eq!(tree.lookup(&root).unwrap().totals.size, 100);
+
+    let answer = tree.answer(&format!("size {}\n", root.display()));
+    assert_eq!(answer["size"], 100);
+    assert_eq!(answer["files"], 1);
+    assert_eq!(answer["is_dir"], true);
+    assert_eq!(tree.answer("status")["dirs"], 2);
+    assert!(tree.answer("size").get("error").is_some());
+    assert!(tree.answer("resize /").get("error").is_some());
+}
+
+#[test]
+fn test_new_directories_are_watched_before_they_are_scanned() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let mut tree = SizeTree::build(std::slice::from_ref(&root));
+
+    let new = root.join("new");
+    fs::create_dir_all(new.join("deeper")).unwrap();
+    // A file created as soon as a directory is watched must still be counted
+    let mut watched = Vec::new();
+    tree.refresh_watching(&new, |dir| {
+        File::create(dir.join("late.txt"))
+            .unwrap()
+            .write_all(&[0; 10])
+            .unwrap();
+        watched.push(dir.to_path_buf());
+    });
+    assert_eq!(watched, vec![new.clone(), new.join("deeper")]);
+    assert_eq!(tree.lookup(&new).unwrap().totals.files, 2);
+    assert_eq!(tree.lookup(&root).unwrap().totals.size, 20);
+
+    // Repeated events for entries the scan already saw change nothing
+    tree.refresh(&new.join("late.txt"));
+    assert!(tree.refresh(&new).is_empty());
+    assert_eq!(tree.lookup(&root).unwrap().totals.size, 20);
+
+    let rebuilt = SizeTree::build_watching(std::slice::from_ref(&root), |_| {});
+    assert_eq!(
+        rebuilt.dirs(),
+        vec![root.clone(), new.clone(), new.join("deeper")]
+    );
+    assert_eq!(rebuilt.lookup(&root), tree.lookup(&root));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_reports_a_bad_socket_before_scanning() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let socket = root.join("missing").join("fs_rs.sock");
+
+    let mut ready = false;
+    let result = fs_rs::daemon::run(std::slice::from_ref(&root), &socket, || ready = true);
+
+    assert!(result.is_err());
+    assert!(!ready);
+}
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+use fs_rs::disks::{self, DiskFilter, FileSystem, Inodes};
+use glob::Pattern;
+
+fn file_system(mount_point: &str, file_system: &str, used: u64, available: u64) -> FileSystem {
+    FileSystem {
+        name: "/dev/test".to_string(),
+        mount_point: PathBuf::from(mount_point),
+        file_system: file_system.to_string(),
+        total: used + available,
+        used,
+        available,
+        inodes: Some(Inodes {
+            total: 100,
+            free: 75,
+        }),
+        read_only: false,
+        removable: false,
+    }
+}
+
+#[test]
+fn test_disk_filter_and_threshold() {
+    let root = file_system("/", "ext4", 900, 100);
+    let data = file_system("/mnt/data", "btrfs", 100, 900);
+    let snap = file_system("/snap/core/1", "squashfs", 1000, 0);
+
+    assert_eq!(root.used_percent(), 90.0);
+    assert_eq!(root.inodes.unwrap().used_percent(), 25.0);
+    assert!(disks::over_threshold(&root, Some(89)));
+    assert!(
+        !disks::over_threshold(&root, Some(90)),
+        "only usage above the threshold counts"
+    );
+    assert!(!disks::over_threshold(&data, Some(90)));
+    assert!(!disks::over_threshold(&root, None));
+
+    let all = DiskFilter::default();
+    assert!(all.matches(&root) && all.matches(&data));
+    assert!(!all.matches(&snap), "snap images are hidden by default");
+
+    let squashfs = DiskFilter {
+        fs_types: vec!["SquashFS".to_string()],
+        ..Default::default()
+    };
+    assert!(squashfs.matches(&snap) && !squashfs.matches(&root));
+
+    let mounts = DiskFilter {
+        mounts: vec![Pattern::new("/mnt/*").unwrap()],
+        ..Default::default()
+    };
+    assert!(mounts.matches(&data) && !mounts.matches(&root));
+
+    let json = disks::to_json(&[root, data], Some(89));
+    assert_eq!(json[0]["over_threshold"], true);
+    assert_eq!(json[1]["over_threshold"], false);
+    assert_eq!(json[0]["inodes_used"], 25);
+}
+
+#[test]
+fn test_containing_file_system() {
+    let dir = tempfile::tempdir().unwrap();
+    let file_systems = vec![
+        file_system("/", "ext4", 900, 100),
+        file_system("/nonexistent/mount", "xfs", 1, 1),
+    ];
+
+    let fs = disks::containing(dir.path(), &file_systems).unwrap();
+    assert_eq!(fs.mount_point, PathBuf::from("/"));
+    assert!(disks::containing(&dir.path().join("missing"), &file_systems).is_none());
+
+    let json = disks::share_to_json(fs, 250);
+    assert_eq!(json["mount_point"], "/");
+    assert_eq!(json["share_percent"], 25.0);
+}
@@ -0,0 +1,191 @@
+use std::fs::{self, File};
+use std::io::Write;
+use tempfile::tempdir;
+
+use fs_rs::dupes::{LinkMode, find_duplicate_dirs, find_duplicates, replace_duplicates};
+
+fn write_file(path: &std::path::Path, content: &[u8]) {
+    let mut file = File::create(path).expect("Failed to create file");
+    file.write_all(content).expect("Failed to write to file");
+}
+
+#[test]
+fn test_find_duplicates() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let subdir = dir.path().join("copy");
+    fs::create_dir(&subdir).expect("Failed to create subdir");
+
+    // Same size and same head and tail, only the middle differs
+    let mut large = vec![b'a'; 20_000];
+    let mut large_other = large.clone();
+    large_other[10_000] = b'b';
+    large[0] = b'z';
+    large_other[0] = b'z';
+
+    write_file(&dir.path().join("one.bin"), &large);
+    write_file(&subdir.join("two.bin"), &large);
+    write_file(&subdir.join("other.bin"), &large_other);
+    write_file(&dir.path().join("small1.txt"), b"hello");
+    write_file(&subdir.join("small2.txt"), b"hello");
+    write_file(&dir.path().join("empty1.txt"), b"");
+    write_file(&dir.path().join("empty2.txt"), b"");
+
+    let groups = find_duplicates(&[dir.path()]);
+
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].size, 20_000);
+    assert_eq!(groups[0].wasted(), 20_000);
+    assert_eq!(
+        groups[0].paths,
+        vec![
+            dir.path().join("copy").join("two.bin"),
+            dir.path().join("one.bin")
+        ]
+    );
+    assert_eq!(groups[1].size, 5);
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_replace_duplicates_with_hardlinks() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    write_file(&dir.path().join("a.txt"), b"same content");
+    write_file(&dir.path().join("b.txt"), b"same content");
+
+    let groups = find_duplicates(&[dir.path()]);
+    assert_eq!(groups.len(), 1);
+
+    let (reclaimed, errors) = replace_duplicates(&groups, LinkMode::Hardlink);
+    assert!(errors.is_empty());
+    assert_eq!(reclaimed, 12);
+    assert_eq!(
+        fs::read(dir.path().join("b.txt")).expect("Failed to read b.txt"),
+        b"same content"
+    );
+
+    // Files that are already hard linked together are not reported again
+    if cfg!(unix) {
+        let groups = find_duplicates(&[dir.path()]);
+        assert!(groups.is_empty());
+    }
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_replace_duplicates_skips_files_changed_since_hashing() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    write_file(&dir.path().join("a.txt"), b"same content");
+    write_file(&dir.path().join("b.txt"), b"same content");
+
+    let groups = find_duplicates(&[dir.path()]);
+    assert_eq!(groups.len(), 1);
+
+    // Edited in place after hashing, keeping the same length
+    write_file(&dir.path().join("b.txt"), b"SAME CONTENT");
+
+    let (reclaimed, errors) = replace_duplicates(&groups, LinkMode::Hardlink);
+    assert_eq!(reclaimed, 0);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, dir.path().join("b.txt"));
+    assert_eq!(
+        fs::read(dir.path().join("b.txt")).expect("Failed to read b.txt"),
+        b"SAME CONTENT"
+    );
+    assert_eq!(
+        fs::read_dir(dir.path())
+            .expect("Failed to list dir")
+            .count(),
+        2,
+        "the temporary link is removed"
+    );
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_find_duplicate_dirs_reports_top_most_copy() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    for copy in ["sdk", "vendor/sdk"] {
+        let lib = dir.path().join(copy).join("lib");
+        fs::create_dir_all(&lib).expect("Failed to create lib dir");
+        write_file(&lib.join("core.so"), b"binary content");
+        write_file(&dir.path().join(copy).join("README"), b"read me");
+    }
+    // Same names and sizes, different content
+    let other = dir.path().join("other").join("lib");
+    fs::create_dir_all(&other).expect("Failed to create other dir");
+    write_file(&other.join("core.so"), b"BINARY CONTENT");
+    write_file(&dir.path().join("other").join("README"), b"read me");
+
+    let groups = find_duplicate_dirs(&[dir.path()]);
+
+    assert_eq!(groups.len(), 1);
+    assert!(groups[0].is_dir);
+    assert_eq!(groups[0].size, 21);
+    assert_eq!(
+        groups[0].paths,
+        vec![
+            dir.path().join("sdk"),
+            dir.path().join("vendor").join("sdk")
+        ]
+    );
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_find_duplicate_dirs_confirms_nested_candidates_by_content() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    for (copy, note) in [("x", b"note one"), ("y", b"note two")] {
+        let deep = dir.path().join(copy).join("a").join("b").join("c");
+        fs::create_dir_all(&deep).expect("Failed to create nested dirs");
+        write_file(&deep.join("data.bin"), &[b'd'; 10_000]);
+        write_file(
+            &dir.path().join(copy).join("a").join("b").join("b.txt"),
+            b"b",
+        );
+        // Same name and size at the top, different content
+        write_file(&dir.path().join(copy).join("a").join("note.txt"), note);
+    }
+
+    let groups = find_duplicate_dirs(&[dir.path()]);
+
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].size, 10_001);
+    assert_eq!(
+        groups[0].paths,
+        vec![
+            dir.path().join("x").join("a").join("b"),
+            dir.path().join("y").join("a").join("b")
+        ]
+    );
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_find_duplicate_dirs_scans_overlapping_paths_once() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let sub = dir.path().join("sub");
+    fs::create_dir(&sub).expect("Failed to create sub dir");
+    write_file(&sub.join("data.bin"), b"only one copy");
+
+    let nested = find_duplicate_dirs(&[dir.path(), &sub]);
+    let repeated = find_duplicate_dirs(&[dir.path(), dir.path()]);
+    let spelled = dir.path().join("sub").join("..");
+    let respelled = find_duplicate_dirs(&[dir.path(), &spelled]);
+
+    assert!(nested.is_empty(), "a directory is not its own duplicate");
+    assert!(repeated.is_empty());
+    assert!(respelled.is_empty());
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
@@ -0,0 +1,62 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use tempfile::tempdir;
+
+use fs_rs::filetypes::{Category, breakdown, category_for};
+
+fn write_file(path: &Path, content: &[u8]) {
+    let mut file = File::create(path).expect("Failed to create file");
+    file.write_all(content).expect("Failed to write to file");
+}
+
+#[test]
+fn test_category_for() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let elf = dir.path().join("program");
+    let gzip = dir.path().join("backup");
+    let script = dir.path().join("run");
+    let text = dir.path().join("notes");
+    write_file(&elf, b"\x7fELF\x02\x01\x01");
+    write_file(&gzip, &[0x1f, 0x8b, 0x08, 0x00]);
+    write_file(&script, b"#!/bin/sh\necho hi\n");
+    write_file(&text, b"just some text");
+
+    assert_eq!(category_for(Path::new("photo.JPG")), Category::Images);
+    assert_eq!(category_for(Path::new("server.log")), Category::Logs);
+    assert_eq!(category_for(Path::new("main.rs")), Category::Source);
+    assert_eq!(category_for(Path::new("data.unknown")), Category::Other);
+    assert_eq!(category_for(&elf), Category::Binaries);
+    assert_eq!(category_for(&gzip), Category::Archives);
+    assert_eq!(category_for(&script), Category::Source);
+    assert_eq!(category_for(&text), Category::Other);
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_breakdown() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let subdir = dir.path().join("logs");
+    fs::create_dir(&subdir).expect("Failed to create subdir");
+    write_file(&subdir.join("a.log"), &[b'x'; 300]);
+    write_file(&subdir.join("b.LOG"), &[b'x'; 200]);
+    write_file(&dir.path().join("main.rs"), &[b'x'; 100]);
+    write_file(&dir.path().join("Makefile"), &[b'x'; 10]);
+
+    let by_extension = breakdown(&[dir.path()], false);
+    assert_eq!(by_extension.len(), 3);
+    assert_eq!(by_extension[0].0, "log");
+    assert_eq!(by_extension[0].1.size, 500);
+    assert_eq!(by_extension[0].1.files, 2);
+    assert_eq!(by_extension[1].0, "rs");
+    assert_eq!(by_extension[2].0, "(none)");
+
+    let by_category = breakdown(&[dir.path()], true);
+    let names: Vec<&str> = by_category.iter().map(|(k, _)| k.as_str()).collect();
+    assert_eq!(names, vec!["logs", "source", "other"]);
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
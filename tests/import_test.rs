@@ -0,0 +1,120 @@
+use std::fs;
+use std::io::Write;
+use tempfile::tempdir;
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use fs_rs::filter::Filter;
+use fs_rs::import::{Format, detect, gunzip, load, parse, parse_du, parse_find};
+
+#[test]
+fn test_parse_du() {
+    // du lists directories after their contents, with sizes of their own
+    let du = "300\t/srv/www/img/logo.png\n\
+              4396\t/srv/www/img\n\
+              20\t/srv/www/index.html\n\
+              8512\t/srv/www\n\
+              4096\t/srv/empty\n\
+              12\t/srv/notes with spaces.txt\n\
+              16716\t/srv\n";
+    let snapshot = parse_du(du).unwrap();
+    assert_eq!(snapshot.root.to_str(), Some("/srv"));
+    assert_eq!(snapshot.total_size, 300 + 20 + 12 + 4096);
+    assert_eq!(snapshot.errors, 0);
+
+    let (children, count) = snapshot.children(&Filter::default());
+    assert_eq!(count, 3);
+    let www = children.iter().find(|c| c.name == "www").unwrap();
+    assert_eq!((www.size, www.is_dir), (320, true));
+    // du cannot tell an empty directory from a file
+    let empty = children.iter().find(|c| c.name == "empty").unwrap();
+    assert!(!empty.is_dir);
+
+    let (dirs, count) = snapshot.dirs(2, &Filter::default());
+    assert_eq!(count, 2);
+    assert!(dirs.iter().any(|d| d.name == "www/img" && d.size == 300));
+
+    assert!(parse_du("12 /srv/a\n").is_err());
+    assert!(parse_du("big\t/srv/a\n").is_err());
+}
+
+#[test]
+fn test_parse_find() {
+    let find = "4096 d data\n\
+                4096 d data/logs\n\
+                700 f data/logs/app.log\n\
+                9 l data/current\n\
+                4096 d data/empty\n\
+                50 f data/a file.txt\n";
+    let snapshot = parse_find(find).unwrap();
+    assert_eq!(snapshot.root.to_str(), Some("data"));
+    assert_eq!(snapshot.total_size, 750);
+    assert_eq!(snapshot.len(), 4);
+
+    let tree = snapshot.render_tree(None, true, &Filter::default());
+    assert_eq!(
+        tree.lines().collect::<Vec<_>>(),
+        vec![
+            "+-- a file.txt*  (50 B)",
+            "+-- empty/  (0 B)",
+            "`-- logs/  (700 B)",
+            "    `-- app.log*  (700 B)",
+        ]
+    );
+
+    // A single file is shown in its directory
+    let snapshot = parse_find("5 f /tmp/x.txt\n").unwrap();
+    assert_eq!(snapshot.root.to_str(), Some("/tmp"));
+    assert_eq!(snapshot.total_size, 5);
+
+    assert!(parse_find("5 f\n").is_err());
+}
+
+#[test]
+fn test_detect_and_load() {
+    colored::control::set_override(false);
+    assert_eq!(detect("\n  [1, 2, {}]"), Some(Format::Ncdu));
+    assert_eq!(detect("12\t/a b\n"), Some(Format::Du));
+    assert_eq!(detect("12 f /a b\n"), Some(Format::Find));
+    assert_eq!(detect("12 /a\n"), None);
+    assert!(parse("total 12\n").is_err());
+
+    let dir = tempdir().unwrap();
+    let dump = dir.path().join("find.txt.gz");
+    // `10 f ./a.txt\n4096 d .\n`, gzipped
+    fs::write(
+        &dump,
+        [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x33, 0x34, 0x50, 0x48,
+            0x53, 0xd0, 0xd3, 0x4f, 0xd4, 0x2b, 0xa9, 0x28, 0xe1, 0x32, 0x31, 0xb0, 0x34, 0x53,
+            0x48, 0x51, 0xd0, 0xe3, 0x02, 0x00, 0x4a, 0x59, 0xc7, 0x46, 0x16, 0x00, 0x00, 0x00,
+        ],
+    )
+    .unwrap();
+    let snapshot = load(&dump).unwrap();
+    assert_eq!(snapshot.root.to_str(), Some("."));
+    assert_eq!(snapshot.total_size, 10);
+
+    assert!(load(&dir.path().join("missing")).is_err());
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn test_gunzip_members_and_limit() {
+    let mut data = gzip(b"10\t/srv/a.txt\n");
+    data.extend(gzip(b"10\t/srv\n"));
+    assert_eq!(gunzip(&data, 100).unwrap(), b"10\t/srv/a.txt\n10\t/srv\n");
+    assert!(gunzip(&data[..data.len() - 4], 100).is_err(), "truncated");
+    assert!(gunzip(b"10\t/srv\n", 100).is_err(), "not gzip");
+
+    // A few KB that expand to megabytes stop at the limit
+    let bomb = gzip(&vec![0; 4 << 20]);
+    assert!(bomb.len() < 64 << 10);
+    assert!(gunzip(&bomb, 1 << 20).unwrap_err().contains("more than"));
+    assert_eq!(gunzip(&bomb, 4 << 20).unwrap().len(), 4 << 20);
+}
@@ -0,0 +1,76 @@
+use std::fs::{self, File};
+use std::io::Write;
+use tempfile::tempdir;
+
+use fs_rs::filter::Filter;
+use fs_rs::ncdu::{parse, to_json};
+use fs_rs::tree::{Snapshot, generate_tree};
+
+#[test]
+fn test_round_trip_rebuilds_the_tree() {
+    let dir = tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("sub").join("deeper")).unwrap();
+    fs::create_dir(dir.path().join("empty")).unwrap();
+    File::create(dir.path().join("sub").join("deeper").join("a.bin"))
+        .unwrap()
+        .write_all(&[0; 300])
+        .unwrap();
+    File::create(dir.path().join("b.txt"))
+        .unwrap()
+        .write_all(&[0; 20])
+        .unwrap();
+
+    let snapshot = Snapshot::scan(dir.path(), None);
+    let dump = to_json(&snapshot);
+    assert_eq!(dump[0], 1);
+    assert_eq!(dump[3][0]["name"], dir.path().display().to_string());
+
+    let imported = parse(&dump.to_string()).unwrap();
+    assert_eq!(imported.root, dir.path());
+    assert_eq!(imported.total_size, 320);
+    assert_eq!(imported.len(), snapshot.len());
+    assert_eq!(imported.errors, 0);
+    assert_eq!(
+        imported.render_tree(None, true, &Filter::default()),
+        generate_tree(dir.path(), None, true)
+    );
+    assert_eq!(to_json(&imported)[3], dump[3]);
+}
+
+#[test]
+fn test_parse_ncdu_dump() {
+    // As written by ncdu, with directory sizes of their own, a symlink, an excluded mount and an unreadable directory
+    let dump = r#"[1,2,{"progname":"ncdu","progver":"2.3","timestamp":1700000000},
+        [{"name":"/srv","asize":4096,"dsize":4096,"dev":2049},
+          [{"name":"www","asize":4096,"dsize":4096,"ino":2},
+            {"name":"index.html","asize":1200,"dsize":4096,"ino":3,"mtime":1700000000,"uid":33,"gid":33},
+            {"name":"current","asize":12,"dsize":0,"notreg":true},
+            [{"name":"private","asize":4096,"read_error":true}]],
+          {"name":"mnt","excluded":"otherfs"},
+          {"name":"big.iso","asize":800,"dsize":800}]]"#;
+    let snapshot = parse(dump).unwrap();
+    assert_eq!(snapshot.root.to_str(), Some("/srv"));
+    assert_eq!(snapshot.total_size, 2000);
+    assert_eq!(snapshot.errors, 1);
+    assert_eq!(snapshot.len(), 4);
+
+    let (children, count) = snapshot.children(&Filter::default());
+    assert_eq!(count, 2);
+    let www = children.iter().find(|c| c.name == "www").unwrap();
+    assert_eq!((www.size, www.is_dir), (1200, true));
+
+    let (dirs, count) = snapshot.dirs(2, &Filter::default());
+    assert_eq!(count, 2);
+    assert!(dirs.iter().any(|d| d.name == "www/private" && d.size == 0));
+
+    let filter = Filter {
+        min_size: Some(1000),
+        ..Filter::default()
+    };
+    let (children, count) = snapshot.children(&filter);
+    assert_eq!((children.len(), count), (1, 2));
+
+    assert!(parse("[1, 2, {}]").is_err());
+    assert!(parse(r#"[1, 2, {}, [{"name": "/"}, 42]]"#).is_err());
+    assert!(parse("not json").is_err());
+}
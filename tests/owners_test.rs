@@ -0,0 +1,45 @@
+#![cfg(unix)]
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+use tempfile::tempdir;
+
+use fs_rs::owners::{owner_breakdown, user_name};
+
+#[test]
+fn test_owner_breakdown() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let subdir = dir.path().join("subdir");
+    fs::create_dir(&subdir).expect("Failed to create subdir");
+    let file_path1 = dir.path().join("file1.txt");
+    let file_path2 = subdir.join("file2.txt");
+    File::create(&file_path1)
+        .expect("Failed to create file1")
+        .write_all(&[b'x'; 100])
+        .expect("Failed to write to file1");
+    File::create(&file_path2)
+        .expect("Failed to create file2")
+        .write_all(&[b'x'; 50])
+        .expect("Failed to write to file2");
+
+    let metadata = fs::metadata(&file_path1).expect("Failed to read metadata");
+    let report = owner_breakdown(&[dir.path()]);
+
+    assert_eq!(report.users.len(), 1);
+    assert_eq!(report.users[0].0, user_name(metadata.uid()));
+    assert_eq!(report.users[0].1, metadata.uid());
+    assert_eq!(report.users[0].2.size, 150);
+    assert_eq!(report.users[0].2.files, 2);
+    assert_eq!(report.groups.len(), 1);
+    assert_eq!(report.groups[0].1, metadata.gid());
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_user_name() {
+    assert_eq!(user_name(0), "root");
+    assert_eq!(user_name(4_000_000_000), "4000000000");
+}
@@ -0,0 +1,153 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+use fs_rs::error::Error;
+use fs_rs::progress::{Event, ScanContext};
+use fs_rs::utils::{calculate_dir_size, try_calculate_dir_size};
+
+/// Create `subdir/a.txt` and `subdir/b.txt`, 150 bytes in total, under `dir`
+fn create_files(dir: &Path) {
+    let subdir = dir.join("subdir");
+    fs::create_dir(&subdir).expect("Failed to create subdir");
+    for (name, size) in [("a.txt", 100), ("b.txt", 50)] {
+        File::create(subdir.join(name))
+            .expect("Failed to create file")
+            .write_all(&vec![b'x'; size])
+            .expect("Failed to write to file");
+    }
+}
+
+#[test]
+fn test_progress_and_cancellation() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    create_files(dir.path());
+
+    let context = Arc::new(ScanContext::new());
+    assert_eq!(context.run(|| calculate_dir_size(dir.path())), 150);
+    let snapshot = context.snapshot();
+    assert_eq!(snapshot.files, 2);
+    assert_eq!(snapshot.bytes, 150);
+    assert_eq!(snapshot.errors, 0);
+    assert!(snapshot.current_dir.is_some());
+    assert!(!snapshot.cancelled);
+
+    let receiver = context.watch(Duration::from_millis(10));
+    let received = receiver
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Failed to receive progress");
+    assert_eq!(received.bytes, 150);
+    drop(receiver);
+
+    let token = context.cancel_token();
+    token.cancel();
+    assert!(context.snapshot().cancelled);
+    assert_eq!(
+        context.run(|| calculate_dir_size(dir.path())),
+        0,
+        "cancelled scans stop early"
+    );
+    assert!(matches!(
+        context.run(|| try_calculate_dir_size(dir.path())),
+        Err(Error::Cancelled)
+    ));
+
+    // Scans outside the cancelled context are unaffected
+    assert_eq!(calculate_dir_size(dir.path()), 150);
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_scan_contexts_are_independent() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    create_files(dir.path());
+
+    let cancelled = Arc::new(ScanContext::new());
+    cancelled.cancel_token().cancel();
+    let running = Arc::new(ScanContext::new());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&events);
+    running.set_observer(move |event| seen.lock().unwrap().push(event.clone()));
+
+    let scans: Vec<_> = [Arc::clone(&cancelled), Arc::clone(&running)]
+        .into_iter()
+        .map(|context| {
+            let path = dir.path().to_path_buf();
+            thread::spawn(move || context.run(|| calculate_dir_size(&path)))
+        })
+        .collect();
+    let sizes: Vec<u64> = scans.into_iter().map(|scan| scan.join().unwrap()).collect();
+
+    assert_eq!(sizes, [0, 150]);
+    assert_eq!(cancelled.snapshot().files, 0);
+    assert_eq!(running.snapshot().files, 2);
+    assert!(!running.snapshot().cancelled);
+
+    // Errors are only reported to the observer of the scan that hit them
+    let missing = dir.path().join("missing");
+    cancelled.run(|| calculate_dir_size(&missing));
+    assert_eq!(running.snapshot().errors, 0);
+    running.run(|| calculate_dir_size(&missing));
+    assert_eq!(running.snapshot().errors, 1);
+    assert!(matches!(
+        events.lock().unwrap().as_slice(),
+        [Event::Error { .. }]
+    ));
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_child_context_counts_its_own_part() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    create_files(dir.path());
+    let missing = dir.path().join("missing");
+
+    let context = Arc::new(ScanContext::new());
+    context.run(|| calculate_dir_size(&missing));
+    let child = Arc::new(context.child());
+    assert_eq!(child.run(|| calculate_dir_size(dir.path())), 150);
+    child.run(|| calculate_dir_size(&missing));
+
+    assert_eq!(child.snapshot().errors, 1, "earlier errors are not counted");
+    assert_eq!(child.snapshot().files, 2);
+    assert_eq!(context.snapshot().errors, 2);
+    assert_eq!(context.snapshot().files, 2);
+
+    context.cancel_token().cancel();
+    assert!(
+        child.snapshot().cancelled,
+        "children stop with their parent"
+    );
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_quiet_contexts_pass_warnings_to_the_observer() {
+    let context = Arc::new(ScanContext::new());
+    let child = Arc::new(context.child());
+    assert!(!child.is_quiet());
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&events);
+    context.set_observer(move |event| seen.lock().unwrap().push(event.clone()));
+    context.set_quiet();
+    child.warn("disk is slow");
+
+    assert!(child.is_quiet(), "parts of a quiet scan are quiet too");
+    assert_eq!(
+        events.lock().unwrap().as_slice(),
+        [Event::Warning {
+            message: "disk is slow".to_string()
+        }]
+    );
+}
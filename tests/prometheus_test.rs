@@ -0,0 +1,55 @@
+use std::fs::{self, File};
+use std::io::Write;
+
+use fs_rs::prometheus;
+use tempfile::tempdir;
+
+#[test]
+fn test_prometheus_metrics() {
+    let dir = tempdir().unwrap();
+    let deep = dir.path().join("a").join("b");
+    fs::create_dir_all(&deep).unwrap();
+    File::create(dir.path().join("top.txt"))
+        .unwrap()
+        .write_all(&[0; 10])
+        .unwrap();
+    File::create(deep.join("deep.txt"))
+        .unwrap()
+        .write_all(&[0; 100])
+        .unwrap();
+
+    let metrics = prometheus::dir_metrics(dir.path(), 1);
+    assert_eq!(metrics.len(), 2, "the root and a, with b folded into a");
+    assert_eq!(metrics[0].path, dir.path());
+    assert_eq!(metrics[0].totals.size, 110);
+    assert_eq!(metrics[0].totals.files, 2);
+    assert_eq!(metrics[1].depth, 1);
+    assert_eq!(metrics[1].totals.size, 100);
+    assert_eq!(prometheus::dir_metrics(dir.path(), 2).len(), 3);
+
+    let labels = vec![("env".to_string(), "a \"quoted\" value".to_string())];
+    let text = prometheus::render(&[(dir.path(), metrics)], &labels, 0);
+    assert!(text.contains("# TYPE fs_rs_directory_size_bytes gauge\n"));
+    let root_series = format!(
+        "fs_rs_directory_size_bytes{{root=\"{0}\",path=\"{0}\",depth=\"0\",env=\"a \\\"quoted\\\" value\"}} 110\n",
+        dir.path().display()
+    );
+    assert!(text.contains(&root_series), "{text}");
+    assert!(text.contains("fs_rs_scan_errors{env=\"a \\\"quoted\\\" value\"} 0\n"));
+
+    let output = dir.path().join("fs.prom");
+    prometheus::write_atomic(&output, &text).unwrap();
+    prometheus::write_atomic(&output, "replaced\n").unwrap();
+    assert_eq!(fs::read_to_string(&output).unwrap(), "replaced\n");
+    let leftovers = fs::read_dir(dir.path())
+        .unwrap()
+        .filter(|e| {
+            e.as_ref()
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .ends_with(".tmp")
+        })
+        .count();
+    assert_eq!(leftovers, 0);
+}
@@ -0,0 +1,96 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use fs_rs::age::FileTimes;
+use fs_rs::filter::EntryInfo;
+use fs_rs::query::Expression;
+
+fn entry(path: &Path, size: u64, is_dir: bool, age_days: u64) -> EntryInfo<'_> {
+    let modified = SystemTime::now() - Duration::from_secs(age_days * 24 * 60 * 60);
+    EntryInfo {
+        path,
+        size,
+        is_dir,
+        times: FileTimes {
+            modified: Some(modified),
+            ..Default::default()
+        },
+        owner: Some((1000, 1000)),
+    }
+}
+
+#[test]
+fn test_expression_matches() {
+    let log = Path::new("/var/log/app/old.LOG");
+    let kept = Path::new("/var/log/keep/old.log");
+    let dir = Path::new("/var/log/app");
+
+    let expr =
+        Expression::parse("size > 100MB and mtime < 1y and not path ~ \"*/keep/*\"").unwrap();
+    assert!(expr.matches(&entry(log, 200_000_000, false, 400)));
+    assert!(
+        !expr.matches(&entry(log, 200_000_000, false, 10)),
+        "too new"
+    );
+    assert!(!expr.matches(&entry(log, 1_000, false, 400)), "too small");
+    assert!(
+        !expr.matches(&entry(kept, 200_000_000, false, 400)),
+        "excluded path"
+    );
+
+    let expr = Expression::parse("ext in (log, gz) AND type = file").unwrap();
+    assert!(
+        expr.matches(&entry(log, 1, false, 0)),
+        "extensions ignore case"
+    );
+    assert!(!expr.matches(&entry(dir, 1, true, 0)));
+
+    let expr = Expression::parse("(name = app or size >= 1KiB) and owner = 1000").unwrap();
+    assert!(expr.matches(&entry(dir, 0, true, 0)));
+    assert!(expr.matches(&entry(log, 1024, false, 0)));
+    assert!(!expr.matches(&entry(log, 1023, false, 0)));
+
+    let expr = Expression::parse("mtime < 2000-01-01 or group != 1000").unwrap();
+    assert!(!expr.matches(&entry(log, 1, false, 0)));
+}
+
+#[test]
+fn test_ages_and_dates_agree_on_older() {
+    let path = Path::new("/srv/data.bin");
+    let old = entry(path, 1, false, 2000);
+    let new = entry(path, 1, false, 1);
+
+    // `<` means older for both an age and a date
+    let expr = Expression::parse("mtime < 30d and mtime < 2024-01-01").unwrap();
+    assert!(expr.matches(&old));
+    assert!(!expr.matches(&new));
+
+    let expr = Expression::parse("mtime > 30d or mtime > 2024-01-01").unwrap();
+    assert!(!expr.matches(&old));
+    assert!(expr.matches(&new));
+
+    let expr = Expression::parse("mtime < 30d and mtime > 2000-01-01").unwrap();
+    assert!(expr.matches(&old));
+    assert!(!expr.matches(&new));
+}
+
+#[test]
+fn test_expression_parse_errors() {
+    let error = Expression::parse("size > 100XB").unwrap_err();
+    assert_eq!((error.start, error.end), (7, 12));
+    assert!(error.to_string().contains("         ^^^^^"), "{error}");
+
+    let error = Expression::parse("colour = red").unwrap_err();
+    assert!(error.message.contains("unknown field 'colour'"));
+    assert_eq!((error.start, error.end), (0, 6));
+
+    let error = Expression::parse("size > 1 and (ext = rs").unwrap_err();
+    assert!(error.message.contains("expected ')'"));
+
+    let error = Expression::parse("name = \"unterminated").unwrap_err();
+    assert!(error.message.contains("unterminated string"));
+
+    assert!(Expression::parse("mtime < soon").is_err());
+    assert!(Expression::parse("size > 1 size").is_err());
+    assert!(Expression::parse("").is_err());
+}
@@ -0,0 +1,53 @@
+use std::fs::{self, File};
+use std::io::Write;
+use tempfile::tempdir;
+
+use fs_rs::report::{render, scan_tree};
+
+#[test]
+fn test_scan_tree() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    File::create(dir.path().join("sub").join("big.bin"))
+        .unwrap()
+        .write_all(&[0; 3000])
+        .unwrap();
+    File::create(dir.path().join("small.txt"))
+        .unwrap()
+        .write_all(&[0; 1000])
+        .unwrap();
+    File::create(dir.path().join("empty.txt")).unwrap();
+
+    let root = scan_tree(dir.path(), None);
+    assert_eq!(root["name"], dir.path().display().to_string());
+    assert_eq!(root["size"], 4000);
+    let children = root["children"].as_array().unwrap();
+    assert_eq!(children.len(), 3);
+    assert_eq!(children[0]["name"], "sub");
+    assert_eq!(children[0]["children"][0]["name"], "big.bin");
+    assert_eq!(children[1]["name"], "small.txt");
+    assert!(children[1].get("children").is_none());
+    // Empty files take no space, so they are folded away
+    assert_eq!(children[2]["name"], "(1 smaller entries)");
+    assert_eq!(children[2]["folded"], 1);
+
+    // At the depth limit, directories keep their size but not their children
+    let root = scan_tree(dir.path(), Some(1));
+    assert_eq!(root["children"][0]["size"], 3000);
+    assert_eq!(root["children"][0]["children"].as_array().unwrap().len(), 0);
+}
+
+#[test]
+fn test_render_embeds_data() {
+    let root = serde_json::json!({
+        "name": "/data",
+        "size": 10,
+        "children": [{ "name": "</script><b>", "size": 10 }],
+    });
+    let html = render(&[root]);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(!html.contains("/*DATA*/"));
+    assert!(html.contains(r#""name":"/data""#));
+    assert!(html.contains(r#""name":"<\/script><b>""#));
+    assert_eq!(html.matches("</script>").count(), 1);
+}
@@ -0,0 +1,76 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+use fs_rs::serve::{Address, Server};
+use tempfile::tempdir;
+
+#[test]
+fn test_address_parsing() {
+    assert_eq!(
+        "127.0.0.1:7878".parse(),
+        Ok(Address::Tcp("127.0.0.1:7878".to_string()))
+    );
+    assert_eq!(
+        "unix:/run/fs_rs.sock".parse(),
+        Ok(Address::Unix(PathBuf::from("/run/fs_rs.sock")))
+    );
+    assert!("localhost".parse::<Address>().is_err());
+    assert!("unix:".parse::<Address>().is_err());
+}
+
+#[test]
+fn test_api_responses() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let sub = root.join("sub dir");
+    fs::create_dir(&sub).unwrap();
+    File::create(sub.join("big.bin"))
+        .unwrap()
+        .write_all(&[0; 300])
+        .unwrap();
+    File::create(root.join("small.txt"))
+        .unwrap()
+        .write_all(&[0; 20])
+        .unwrap();
+
+    let server = Server::new(vec![root.clone()], None);
+
+    let response = server.respond("/api/status");
+    assert_eq!(response.status, 200);
+    let status: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(status["files"], 2);
+    assert_eq!(status["size_bytes"], 320);
+    assert_eq!(status["scans"], 1);
+
+    // Without a path, the only root is listed
+    let children: serde_json::Value =
+        serde_json::from_str(&server.respond("/api/children").body).unwrap();
+    assert_eq!(children["path"], root.display().to_string());
+    assert_eq!(children["entries"][0]["name"], "sub dir");
+    assert_eq!(children["entries"][0]["size_bytes"], 300);
+    assert_eq!(children["entries"][1]["name"], "small.txt");
+    assert!(children["parent"].is_null());
+
+    let encoded = sub
+        .display()
+        .to_string()
+        .replace('/', "%2F")
+        .replace(' ', "+");
+    let size: serde_json::Value =
+        serde_json::from_str(&server.respond(&format!("/api/size?path={encoded}")).body).unwrap();
+    assert_eq!(size["size_bytes"], 300);
+    assert_eq!(size["is_dir"], true);
+
+    let top: serde_json::Value =
+        serde_json::from_str(&server.respond("/api/top?n=1").body).unwrap();
+    assert_eq!(top.as_array().unwrap().len(), 1);
+    assert_eq!(top[0]["size_bytes"], 300);
+
+    assert_eq!(server.respond("/api/size?path=/etc").status, 404);
+    assert_eq!(server.respond("/api/top?n=many").status, 400);
+    assert_eq!(server.respond("/nope").status, 404);
+    let page = server.respond("/");
+    assert_eq!(page.status, 200);
+    assert!(page.body.contains("/api/children"));
+}
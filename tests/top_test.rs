@@ -0,0 +1,38 @@
+use std::fs::{self, File};
+use std::io::Write;
+use tempfile::tempdir;
+
+use fs_rs::top::largest_files;
+
+#[test]
+fn test_largest_files() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let subdir = dir.path().join("nested").join("deeper");
+    fs::create_dir_all(&subdir).expect("Failed to create subdir");
+
+    for (path, size) in [
+        (dir.path().join("small.txt"), 10),
+        (dir.path().join("nested").join("medium.txt"), 500),
+        (subdir.join("large.bin"), 2000),
+        (subdir.join("tiny.txt"), 1),
+    ] {
+        let mut file = File::create(&path).expect("Failed to create file");
+        file.write_all(&vec![b'x'; size])
+            .expect("Failed to write to file");
+    }
+
+    let files = largest_files(&[dir.path()], 2);
+
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].size, 2000);
+    assert!(files[0].path.ends_with("large.bin"));
+    assert_eq!(files[1].size, 500);
+    assert!(files[1].path.ends_with("medium.txt"));
+    assert!(files[0].modified.is_some());
+
+    let none = largest_files(&[dir.path()], 0);
+    assert!(none.is_empty());
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
@@ -2,7 +2,14 @@ use std::fs::{self, File};
 use std::io::Write;
 use tempfile::tempdir;
 
-use fs_rs::tree::generate_tree;
+use fs_rs::age::TimeField;
+use fs_rs::error::Error;
+use fs_rs::filter::Filter;
+use fs_rs::query::Expression;
+use fs_rs::tree::{
+    Annotations, breakdown_dirs, flatten_dirs, generate_annotated_tree, generate_tree,
+    try_generate_tree,
+};
 
 #[test]
 fn test_generate_tree() {
@@ -38,4 +45,155 @@ fn test_generate_tree() {
 
     dir.close()
         .expect("Failed to delete the temporary directory");
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_flatten_dirs() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let nested = dir.path().join("a").join("b").join("c");
+    fs::create_dir_all(&nested).expect("Failed to create nested dirs");
+    let mut file = File::create(nested.join("file.txt")).expect("Failed to create file");
+    file.write_all(&[b'x'; 100])
+        .expect("Failed to write to file");
+    drop(file);
+
+    let (dirs, total_size) = flatten_dirs(dir.path(), 2);
+    let names: Vec<&str> = dirs.iter().map(|d| d.name.as_str()).collect();
+    let sep = std::path::MAIN_SEPARATOR;
+
+    assert_eq!(total_size, 100);
+    assert_eq!(names, vec!["a".to_string(), format!("a{sep}b")]);
+    assert!(dirs.iter().all(|d| d.is_dir && d.size == 100));
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_generate_annotated_tree() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let nested = dir.path().join("src").join("deep");
+    fs::create_dir_all(&nested).expect("Failed to create nested dirs");
+    let mut file = File::create(nested.join("main.rs")).expect("Failed to create file");
+    file.write_all(&[b'x'; 100])
+        .expect("Failed to write to file");
+    drop(file);
+
+    colored::control::set_override(false);
+    let by_type = Annotations {
+        by_type: true,
+        ..Default::default()
+    };
+    let tree = generate_annotated_tree(dir.path(), Some(1), true, &by_type, &Filter::default());
+
+    assert!(tree.contains("src/  (100 B)  [rs 100 B]"));
+    assert!(!tree.contains("deep"), "depth should limit the shown tree");
+
+    let newest = Annotations {
+        newest: Some(TimeField::Modified),
+        ..Default::default()
+    };
+    let tree = generate_annotated_tree(dir.path(), None, true, &newest, &Filter::default());
+
+    assert!(tree.contains("src/  (100 B)  newest "));
+    assert!(tree.contains("deep/  (100 B)  newest "));
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_generate_annotated_tree_keeps_ancestors_of_matches() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let nested = dir.path().join("outer").join("inner");
+    fs::create_dir_all(&nested).expect("Failed to create nested dirs");
+    for (path, size) in [
+        (nested.join("big.bin"), 5000),
+        (nested.join("small.txt"), 10),
+        (dir.path().join("tiny.txt"), 1),
+    ] {
+        let mut file = File::create(path).expect("Failed to create file");
+        file.write_all(&vec![b'x'; size])
+            .expect("Failed to write to file");
+    }
+
+    let filter = Filter {
+        min_size: Some(1000),
+        max_size: Some(2000),
+        ..Default::default()
+    };
+    let tree = generate_annotated_tree(dir.path(), None, true, &Annotations::default(), &filter);
+    assert!(tree.is_empty(), "nothing is between 1 kB and 2 kB");
+
+    let filter = Filter {
+        max_size: Some(100),
+        ..Default::default()
+    };
+    let tree = generate_annotated_tree(dir.path(), None, true, &Annotations::default(), &filter);
+    assert!(tree.contains("outer/"), "ancestors of matches are kept");
+    assert!(tree.contains("inner/"), "ancestors of matches are kept");
+    assert!(tree.contains("small.txt"));
+    assert!(tree.contains("tiny.txt"));
+    assert!(!tree.contains("big.bin"));
+
+    let filter = Filter {
+        expression: Some(Expression::parse("ext = bin or name = tiny.txt").unwrap()),
+        ..Default::default()
+    };
+    let tree = generate_annotated_tree(dir.path(), None, true, &Annotations::default(), &filter);
+    assert!(tree.contains("big.bin"));
+    assert!(tree.contains("tiny.txt"));
+    assert!(!tree.contains("small.txt"));
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_try_generate_tree() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let subdir = dir.path().join("subdir");
+    fs::create_dir(&subdir).expect("Failed to create subdir");
+    File::create(subdir.join("file.txt")).expect("Failed to create file");
+
+    let tree = try_generate_tree(dir.path(), None, true).expect("Failed to generate tree");
+    assert_eq!(tree, generate_tree(dir.path(), None, true));
+    assert!(tree.contains("file.txt"));
+
+    let missing = dir.path().join("missing");
+    let error = try_generate_tree(&missing, None, true).unwrap_err();
+    assert!(matches!(error, Error::NotFound(_)));
+    assert_eq!(error.path(), Some(missing.as_path()));
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
+
+#[test]
+fn test_breakdown_dirs() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let nested = dir.path().join("src").join("deep");
+    fs::create_dir_all(&nested).expect("Failed to create nested dirs");
+    let mut file = File::create(nested.join("main.rs")).expect("Failed to create file");
+    file.write_all(&[b'x'; 100])
+        .expect("Failed to write to file");
+    drop(file);
+
+    let by_type = Annotations {
+        by_type: true,
+        ..Default::default()
+    };
+    let (dirs, total_size) = breakdown_dirs(dir.path(), 2, &by_type, &Filter::default());
+    let sep = std::path::MAIN_SEPARATOR;
+
+    assert_eq!(total_size, 100);
+    assert_eq!(dirs.len(), 2);
+    assert_eq!(dirs[0].name, "src");
+    assert_eq!(dirs[1].name, format!("src{sep}deep"));
+    assert!(dirs.iter().all(|d| d.size == 100));
+    assert_eq!(dirs[1].types[0].0, "rs");
+    assert_eq!(dirs[1].types[0].1.files, 1);
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}
@@ -0,0 +1,92 @@
+use std::fs::{self, File};
+use std::io::Write;
+use tempfile::tempdir;
+
+use fs_rs::treemap::{Rect, TreemapOptions, generate_treemap, parse_dimensions, squarify};
+
+#[test]
+fn test_squarify_is_proportional() {
+    let values = [6, 6, 4, 3, 2, 2, 1, 0];
+    let area = Rect {
+        x: 1.0,
+        y: 2.0,
+        w: 6.0,
+        h: 4.0,
+    };
+    let rects = squarify(&values, area);
+    assert_eq!(rects.len(), values.len());
+
+    // Each value gets its share of the 24 units of area, inside the bounds
+    for (value, rect) in values.iter().zip(&rects) {
+        assert!((rect.w * rect.h - *value as f64).abs() < 1e-9, "{rect:?}");
+        if *value > 0 {
+            assert!(rect.x >= area.x - 1e-9 && rect.x + rect.w <= area.x + area.w + 1e-9);
+            assert!(rect.y >= area.y - 1e-9 && rect.y + rect.h <= area.y + area.h + 1e-9);
+        }
+    }
+    assert_eq!(rects[7], Rect::default());
+
+    // The two largest values share the first row, along the shorter left side
+    assert_eq!(rects[0].x, 1.0);
+    assert_eq!(rects[1].x, 1.0);
+    assert!((rects[0].w - 3.0).abs() < 1e-9);
+
+    assert!(
+        squarify(&[0, 0], area)
+            .iter()
+            .all(|r| *r == Rect::default())
+    );
+}
+
+#[test]
+fn test_generate_treemap() {
+    colored::control::set_override(false);
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("big")).unwrap();
+    File::create(dir.path().join("big").join("inner.bin"))
+        .unwrap()
+        .write_all(&[0; 3000])
+        .unwrap();
+    File::create(dir.path().join("small.txt"))
+        .unwrap()
+        .write_all(&[0; 1000])
+        .unwrap();
+    File::create(dir.path().join("empty.txt")).unwrap();
+
+    let options = TreemapOptions {
+        width: 40,
+        height: 10,
+        depth: None,
+        ascii: true,
+    };
+    let (map, total_size) = generate_treemap(dir.path(), &options);
+    assert_eq!(total_size, 4000);
+
+    let lines: Vec<&str> = map.lines().collect();
+    assert_eq!(lines.len(), 10);
+    assert!(lines.iter().all(|line| line.chars().count() == 40));
+    // The directory takes the left three quarters as a box with the file nested inside
+    assert!(lines[0].starts_with("+big/ 3 kB---"));
+    assert_eq!(lines[0].chars().nth(29), Some('+'));
+    assert!(lines[1].starts_with("|inner.bin"));
+    assert!(lines[9].starts_with("+----"));
+    assert_eq!(&lines[0][30..], "small.txt ");
+    assert_eq!(&lines[1][30..], "1 kB      ");
+    assert!(!map.contains("empty.txt"));
+
+    // Without nesting, the directory is an empty box
+    let options = TreemapOptions {
+        depth: Some(1),
+        ..options
+    };
+    let (map, _) = generate_treemap(dir.path(), &options);
+    assert!(!map.contains("inner.bin"));
+    assert!(map.contains("big/ 3 kB"));
+}
+
+#[test]
+fn test_parse_dimensions() {
+    assert_eq!(parse_dimensions("80X24"), Ok((80, 24)));
+    assert!(parse_dimensions("80x").is_err());
+    assert!(parse_dimensions("axb").is_err());
+}
@@ -3,7 +3,8 @@ use std::io::Write;
 use std::path::Path;
 use tempfile::tempdir;
 
-use fs_rs::utils::{Sizes, calculate_dir_size};
+use fs_rs::error::Error;
+use fs_rs::utils::{Sizes, calculate_dir_size, try_calculate_dir_size};
 
 #[test]
 fn test_calculate_dir_size() {
@@ -95,3 +96,35 @@ fn test_truncate_filename() {
     );
 }
 
+#[test]
+fn test_parse_size() {
+    assert_eq!(fs_rs::utils::parse_size("512"), Ok(512));
+    assert_eq!(fs_rs::utils::parse_size("10MB"), Ok(10_000_000));
+    assert_eq!(fs_rs::utils::parse_size("10 mb"), Ok(10_000_000));
+    assert_eq!(fs_rs::utils::parse_size("1.5GiB"), Ok(1_610_612_736));
+    assert_eq!(fs_rs::utils::parse_size("2k"), Ok(2000));
+    assert!(fs_rs::utils::parse_size("MB").is_err());
+    assert!(fs_rs::utils::parse_size("10 parsecs").is_err());
+}
+
+#[test]
+fn test_try_calculate_dir_size() {
+    let dir = tempdir().expect("Failed to create a temporary directory");
+    let subdir = dir.path().join("subdir");
+    std::fs::create_dir(&subdir).expect("Failed to create subdir");
+    let mut file = File::create(subdir.join("file.txt")).expect("Failed to create file");
+    file.write_all(&[b'x'; 100])
+        .expect("Failed to write to file");
+    drop(file);
+
+    assert_eq!(try_calculate_dir_size(dir.path()).unwrap(), 100);
+
+    let missing = dir.path().join("missing");
+    match try_calculate_dir_size(&missing) {
+        Err(Error::NotFound(path)) => assert_eq!(path, missing),
+        other => panic!("expected NotFound, got {other:?}"),
+    }
+
+    dir.close()
+        .expect("Failed to delete the temporary directory");
+}